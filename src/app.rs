@@ -6,8 +6,7 @@ use std::{
     process::Command,
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc::{self, TryRecvError},
-        Arc,
+        mpsc, Arc,
     },
     time::{Duration, Instant},
 };
@@ -24,7 +23,7 @@ use notify::{
 };
 use octocrab::models::repos::Release;
 use parking_lot::RwLock;
-use tracing::{debug, trace};
+use tracing::{debug, error, trace};
 
 use serde::{Deserialize, Serialize};
 
@@ -33,24 +32,37 @@ use wows_replays::{analyzer::battle_controller::GameMessage, ReplayFile};
 use wowsunpack::data::idx::FileNode;
 
 use crate::{
+    asset_gallery::AssetGalleryState,
+    build_diff::{BuildDiffTabState, FileChange},
+    content_search::ContentSearchState,
     error::ToolkitError,
-    file_unpacker::{UnpackerProgress, UNPACKER_STOP},
-    game_params::game_params_bin_path,
+    extraction_manifest::{ConversionOptions, ExtractionReport},
+    game_params_browser::GameParamsBrowserTabState,
+    game_params_diff::GameParamsDiffTabState,
     icons,
+    localization::LocalizationTabState,
+    logging::LogTabState,
     plaintext_viewer::PlaintextFileViewer,
-    player_tracker::PlayerTracker,
-    replay_parser::{Replay, SharedReplayParserTabState},
+    player_store::{player_store_path, PlayerStore},
+    player_tracker::{PlayerTracker, PlayerTrackerTabState},
+    replay_archive,
+    replay_parser::{Replay, ReplayAnnotation, SharedReplayParserTabState},
+    saved_filters::{SavedFilter, SavedFiltersState},
+    settings_migrations::{self, CURRENT_SETTINGS_VERSION},
+    ship_stats::ShipStatsTabState,
+    sound_banks::SoundBankTabState,
     task::{self, BackgroundTask, BackgroundTaskCompletion, BackgroundTaskKind},
+    task_manager::{FinishedTask, TaskManager},
     twitch::{Token, TwitchState},
+    unpacker_jobs::{JobOutcome, UnpackerJob},
     wows_data::WorldOfWarshipsData,
 };
 
 #[macro_export]
-macro_rules! update_background_task {
-    ($saved_task:expr, $background_task:expr) => {
-        let task = $background_task;
-        if task.is_some() {
-            $saved_task = task;
+macro_rules! start_background_task {
+    ($task_manager:expr, $background_task:expr) => {
+        if let Some(task) = $background_task {
+            $task_manager.start(task);
         }
     };
 }
@@ -61,6 +73,13 @@ pub enum Tab {
     ReplayParser,
     Settings,
     PlayerTracker,
+    BuildDiff,
+    GameParamsDiff,
+    GameParamsBrowser,
+    ShipStats,
+    Localization,
+    SoundBanks,
+    Log,
 }
 
 impl Tab {
@@ -70,6 +89,13 @@ impl Tab {
             Tab::Settings => format!("{} Settings", icons::GEAR_FINE),
             Tab::ReplayParser => format!("{} Replay Inspector", icons::MAGNIFYING_GLASS),
             Tab::PlayerTracker => format!("{} Player Tracker", icons::DETECTIVE),
+            Tab::BuildDiff => format!("{} Compare Builds", icons::GIT_DIFF),
+            Tab::GameParamsDiff => format!("{} Compare GameParams", icons::SCALES),
+            Tab::GameParamsBrowser => format!("{} GameParams", icons::TREE_STRUCTURE),
+            Tab::ShipStats => format!("{} Ship Stats", icons::ANCHOR),
+            Tab::Localization => format!("{} Localization", icons::TRANSLATE),
+            Tab::SoundBanks => format!("{} Sound Banks", icons::SPEAKER_HIGH),
+            Tab::Log => format!("{} Log", icons::SCROLL),
         }
     }
 }
@@ -119,7 +145,7 @@ impl ToolkitTabViewer<'_> {
                                     let path = Path::new(&self.tab_state.settings.wows_dir).to_owned();
                                     if path.exists() && path.join("bin").exists() {
                                         self.tab_state.prevent_changing_wows_dir();
-                                        crate::update_background_task!(self.tab_state.background_task, Some(self.tab_state.load_game_data(path)));
+                                        crate::start_background_task!(self.tab_state.background_tasks, Some(self.tab_state.load_game_data(path)));
                                     }
                                 }
                             });
@@ -128,19 +154,69 @@ impl ToolkitTabViewer<'_> {
                                     let folder = rfd::FileDialog::new().pick_folder();
                                     if let Some(folder) = folder {
                                         self.tab_state.prevent_changing_wows_dir();
-                                        crate::update_background_task!(self.tab_state.background_task, Some(self.tab_state.load_game_data(folder)));
+                                        crate::start_background_task!(self.tab_state.background_tasks, Some(self.tab_state.load_game_data(folder)));
                                     }
                                 }
                             });
                         });
                     });
+                    ui.horizontal(|ui| {
+                        ui.label("GameParams cache limit");
+                        ui.add(
+                            egui::DragValue::new(&mut self.tab_state.settings.game_params_cache_size_mb)
+                                .range(64..=16384)
+                                .suffix(" MB"),
+                        )
+                        .on_hover_text("Parsed GameParams are cached per game build. The oldest builds are removed once the cache exceeds this size.");
+                    });
                 })
             });
+            ui.label("User Data");
+            ui.group(|ui| {
+                self.build_user_data_settings(ui);
+            });
             ui.label("Replay Settings");
             ui.group(|ui| {
                 ui.checkbox(&mut self.tab_state.settings.replay_settings.show_game_chat, "Show Game Chat");
                 ui.checkbox(&mut self.tab_state.settings.replay_settings.show_entity_id, "Show Entity ID Column");
                 ui.checkbox(&mut self.tab_state.settings.replay_settings.show_observed_damage, "Show Observed Damage Column");
+                ui.horizontal(|ui| {
+                    ui.label("Archive replays older than");
+                    ui.add(egui::DragValue::new(&mut self.tab_state.settings.replay_archive_age_days).range(1..=3650).suffix(" days"));
+
+                    let archive_params = self.tab_state.world_of_warships_data.as_ref().and_then(|wows_data| {
+                        let wows_data = wows_data.read();
+                        wows_data.game_metadata.clone().map(|metadata| (wows_data.replays_dir.clone(), metadata))
+                    });
+                    let archiving = self
+                        .tab_state
+                        .background_tasks
+                        .is_running(|kind| matches!(kind, BackgroundTaskKind::ArchivingReplays));
+                    if ui
+                        .add_enabled(archive_params.is_some() && !archiving, egui::Button::new(format!("{} Archive Now", icons::ARCHIVE)))
+                        .on_hover_text("Moves old replays into a compressed archive. Archived replays are still listed in the Replay Inspector.")
+                        .clicked()
+                    {
+                        if let Some((replays_dir, metadata)) = archive_params {
+                            let max_age = Duration::from_secs(60 * 60 * 24 * self.tab_state.settings.replay_archive_age_days as u64);
+                            crate::start_background_task!(
+                                self.tab_state.background_tasks,
+                                Some(replay_archive::start_archiving_replays(replays_dir, max_age, metadata))
+                            );
+                        }
+                    }
+                });
+                if let Some(wows_data) = self.tab_state.world_of_warships_data.as_ref() {
+                    let wows_data = wows_data.read();
+                    let archive = &wows_data.replay_archive;
+                    if !archive.is_empty() {
+                        ui.label(format!(
+                            "{} replays archived ({} uncompressed)",
+                            archive.len(),
+                            humansize::format_size(archive.total_size(), humansize::DECIMAL)
+                        ));
+                    }
+                }
             });
             ui.label("Twitch Settings");
             ui.group(|ui| {
@@ -201,6 +277,13 @@ impl TabViewer for ToolkitTabViewer<'_> {
             Tab::Settings => self.build_settings_tab(ui),
             Tab::ReplayParser => self.build_replay_parser_tab(ui),
             Tab::PlayerTracker => self.build_player_tracker_tab(ui),
+            Tab::BuildDiff => self.build_build_diff_tab(ui),
+            Tab::GameParamsDiff => self.build_game_params_diff_tab(ui),
+            Tab::GameParamsBrowser => self.build_game_params_browser_tab(ui),
+            Tab::ShipStats => self.build_ship_stats_tab(ui),
+            Tab::Localization => self.build_localization_tab(ui),
+            Tab::SoundBanks => self.build_sound_banks_tab(ui),
+            Tab::Log => self.build_log_tab(ui),
         }
     }
 }
@@ -226,8 +309,12 @@ pub const fn default_bool<const V: bool>() -> bool {
     V
 }
 
-pub fn default_sent_replays() -> Arc<RwLock<HashSet<String>>> {
-    Default::default()
+pub const fn default_replay_archive_age_days() -> u32 {
+    90
+}
+
+pub const fn default_game_params_cache_size_mb() -> u32 {
+    1024
 }
 
 #[derive(Serialize, Deserialize)]
@@ -243,18 +330,34 @@ pub struct Settings {
     pub check_for_updates: bool,
     #[serde(default = "default_bool::<true>")]
     pub send_replay_data: bool,
-    #[serde(default = "default_bool::<false>")]
-    pub has_default_value_fix_015: bool,
-    #[serde(default = "default_sent_replays")]
-    pub sent_replays: Arc<RwLock<HashSet<String>>>,
-    #[serde(default = "default_bool::<false>")]
-    pub has_019_game_params_update: bool,
+    /// Replays sent by versions which kept them in the settings. These are only
+    /// read so that they can be moved into the [PlayerStore].
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub sent_replays: HashSet<String>,
     #[serde(default)]
     pub player_tracker: Arc<RwLock<PlayerTracker>>,
     #[serde(default)]
     pub twitch_token: Option<Token>,
     #[serde(default)]
     pub twitch_monitored_channel: String,
+    #[serde(default = "default_replay_archive_age_days")]
+    pub replay_archive_age_days: u32,
+    /// User annotations keyed by replay file name
+    #[serde(default)]
+    pub replay_annotations: HashMap<String, ReplayAnnotation>,
+    /// Named resource unpacker filters
+    #[serde(default)]
+    pub saved_filters: Vec<SavedFilter>,
+    /// Bookmarked resource paths, relative to `res/`
+    #[serde(default)]
+    pub bookmarks: Vec<String>,
+    /// Size limit for cached GameParams across all builds
+    #[serde(default = "default_game_params_cache_size_mb")]
+    pub game_params_cache_size_mb: u32,
+    /// Which of the [settings_migrations] have been applied. Settings saved before
+    /// migrations existed are at version 0.
+    #[serde(default)]
+    pub schema_version: u32,
 }
 
 impl Default for Settings {
@@ -267,12 +370,16 @@ impl Default for Settings {
             replay_settings: Default::default(),
             check_for_updates: true,
             send_replay_data: true,
-            has_default_value_fix_015: true,
             sent_replays: Default::default(),
-            has_019_game_params_update: false,
             player_tracker: Default::default(),
             twitch_token: Default::default(),
             twitch_monitored_channel: Default::default(),
+            replay_archive_age_days: default_replay_archive_age_days(),
+            replay_annotations: Default::default(),
+            saved_filters: Default::default(),
+            bookmarks: Default::default(),
+            game_params_cache_size_mb: default_game_params_cache_size_mb(),
+            schema_version: CURRENT_SETTINGS_VERSION,
         }
     }
 }
@@ -280,6 +387,9 @@ impl Default for Settings {
 #[derive(Default)]
 pub struct ReplayParserTabState {
     pub game_chat: Vec<GameMessage>,
+    pub tag_filter: Option<String>,
+    pub favorites_only: bool,
+    pub new_tag: String,
 }
 
 #[derive(Debug)]
@@ -331,11 +441,15 @@ pub struct TabState {
 
     pub output_dir: String,
 
+    pub convert_dds_to_png: bool,
+
+    pub decode_packed_xml: bool,
+
     #[serde(skip)]
-    pub unpacker_progress: Option<mpsc::Receiver<UnpackerProgress>>,
+    pub unpacker_jobs: Vec<UnpackerJob>,
 
     #[serde(skip)]
-    pub last_progress: Option<UnpackerProgress>,
+    pub last_extraction_report: Option<ExtractionReport>,
 
     #[serde(skip)]
     pub replay_parser_tab: SharedReplayParserTabState,
@@ -343,6 +457,43 @@ pub struct TabState {
     #[serde(skip)]
     pub file_viewer: Mutex<Vec<PlaintextFileViewer>>,
 
+    #[serde(skip)]
+    pub build_diff_tab: BuildDiffTabState,
+
+    #[serde(skip)]
+    pub game_params_diff_tab: GameParamsDiffTabState,
+
+    #[serde(skip)]
+    pub game_params_browser_tab: GameParamsBrowserTabState,
+
+    #[serde(skip)]
+    pub ship_stats_tab: ShipStatsTabState,
+
+    #[serde(skip)]
+    pub localization_tab: LocalizationTabState,
+
+    #[serde(skip)]
+    pub log_tab: LogTabState,
+
+    #[serde(skip)]
+    pub player_tracker_tab: PlayerTrackerTabState,
+
+    /// `None` if neither the player database nor an in-memory fallback could be opened
+    #[serde(skip)]
+    pub player_store: Option<Arc<PlayerStore>>,
+
+    #[serde(skip)]
+    pub sound_bank_tab: SoundBankTabState,
+
+    #[serde(skip)]
+    pub content_search: ContentSearchState,
+
+    #[serde(skip)]
+    pub saved_filters: SavedFiltersState,
+
+    #[serde(skip)]
+    pub asset_gallery: AssetGalleryState,
+
     #[serde(skip)]
     pub file_watcher: Option<RecommendedWatcher>,
 
@@ -353,7 +504,7 @@ pub struct TabState {
     pub replay_files: Option<HashMap<PathBuf, Arc<RwLock<Replay>>>>,
 
     #[serde(skip)]
-    pub background_task: Option<BackgroundTask>,
+    pub background_tasks: TaskManager,
 
     #[serde(skip)]
     pub timed_message: RwLock<Option<TimedMessage>>,
@@ -386,14 +537,28 @@ impl Default for TabState {
             settings: Default::default(),
             translations: Default::default(),
             output_dir: Default::default(),
-            unpacker_progress: Default::default(),
-            last_progress: Default::default(),
+            convert_dds_to_png: false,
+            decode_packed_xml: false,
+            unpacker_jobs: Default::default(),
+            last_extraction_report: Default::default(),
             replay_parser_tab: Default::default(),
             file_viewer: Default::default(),
+            build_diff_tab: Default::default(),
+            game_params_diff_tab: Default::default(),
+            game_params_browser_tab: Default::default(),
+            ship_stats_tab: Default::default(),
+            localization_tab: Default::default(),
+            log_tab: Default::default(),
+            player_tracker_tab: Default::default(),
+            player_store: None,
+            sound_bank_tab: Default::default(),
+            content_search: Default::default(),
+            saved_filters: Default::default(),
+            asset_gallery: Default::default(),
             file_watcher: None,
             replay_files: None,
             file_receiver: None,
-            background_task: None,
+            background_tasks: Default::default(),
             can_change_wows_dir: true,
             timed_message: RwLock::new(None),
             current_replay: None,
@@ -430,7 +595,7 @@ impl TabState {
 
                                         if self.auto_load_latest_replay {
                                             if let Some(wows_data) = self.world_of_warships_data.as_ref() {
-                                                update_background_task!(self.background_task, wows_data.read().load_replay(replay));
+                                                start_background_task!(self.background_tasks, wows_data.read().load_replay(replay));
                                             }
                                         }
 
@@ -461,7 +626,9 @@ impl TabState {
                         }
 
                         if let Ok(replay_file) = ReplayFile::from_decrypted_parts(meta_data.unwrap(), Vec::with_capacity(0)) {
-                            self.settings.player_tracker.write().update_from_live_arena_info(&replay_file.meta);
+                            if let Err(e) = self.settings.player_tracker.write().update_from_live_arena_info(&replay_file.meta) {
+                                error!("failed to update player tracker from live game: {}", e);
+                            }
                         }
                     }
                 }
@@ -469,6 +636,31 @@ impl TabState {
         }
     }
 
+    pub fn conversion_options(&self) -> ConversionOptions {
+        ConversionOptions {
+            dds_to_png: self.convert_dds_to_png,
+            decode_packed_xml: self.decode_packed_xml,
+        }
+    }
+
+    /// Moves players and sent replays saved in the settings by older versions into the
+    /// [PlayerStore]. They stay in the settings if this fails so that it's retried on the next start.
+    fn migrate_legacy_player_data(&mut self) -> Result<(), ToolkitError> {
+        let mut player_tracker = self.settings.player_tracker.write();
+        if !player_tracker.has_legacy_players() && self.settings.sent_replays.is_empty() {
+            return Ok(());
+        }
+
+        let player_store = self.player_store.as_ref().ok_or(ToolkitError::PlayerStoreUnavailable)?;
+        let sent_replays: Vec<String> = self.settings.sent_replays.iter().cloned().collect();
+        player_store.import(&player_tracker.legacy_players(), &sent_replays, false)?;
+
+        player_tracker.clear_legacy_players();
+        self.settings.sent_replays.clear();
+
+        Ok(())
+    }
+
     fn prevent_changing_wows_dir(&mut self) {
         self.can_change_wows_dir = false;
     }
@@ -487,15 +679,10 @@ impl TabState {
             let (tx, rx) = mpsc::channel();
             let (background_tx, background_rx) = mpsc::channel();
 
-            if let Some(wows_data) = self.world_of_warships_data.clone() {
+            // Sent replays are tracked in the player store, so nothing is sent without one
+            if let (Some(wows_data), Some(player_store)) = (self.world_of_warships_data.clone(), self.player_store.clone()) {
                 self.should_send_replays.store(self.settings.send_replay_data, Ordering::SeqCst);
-                task::start_background_parsing_thread(
-                    background_rx,
-                    Arc::clone(&self.settings.sent_replays),
-                    wows_data,
-                    self.should_send_replays.clone(),
-                    Arc::clone(&self.settings.player_tracker),
-                );
+                task::start_background_parsing_thread(background_rx, wows_data, self.should_send_replays.clone(), player_store);
             }
 
             let watcher = notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| match res {
@@ -561,14 +748,12 @@ impl TabState {
     pub fn load_game_data(&self, wows_directory: PathBuf) -> BackgroundTask {
         let (tx, rx) = mpsc::channel();
         let locale = self.settings.locale.clone().unwrap();
+        let max_cache_size = self.settings.game_params_cache_size_mb as u64 * 1024 * 1024;
         let _join_handle = std::thread::spawn(move || {
-            let _ = tx.send(task::load_wows_files(wows_directory, locale.as_str()));
+            let _ = tx.send(task::load_wows_files(wows_directory, locale.as_str(), max_cache_size));
         });
 
-        BackgroundTask {
-            receiver: rx,
-            kind: BackgroundTaskKind::LoadingData,
-        }
+        BackgroundTask::new(rx, BackgroundTaskKind::LoadingData)
     }
 }
 
@@ -605,7 +790,22 @@ impl Default for WowsToolkitApp {
             latest_release: None,
             show_about_window: false,
             tab_state: Default::default(),
-            dock_state: DockState::new([Tab::ReplayParser, Tab::PlayerTracker, Tab::Unpacker, Tab::Settings].to_vec()),
+            dock_state: DockState::new(
+                [
+                    Tab::ReplayParser,
+                    Tab::PlayerTracker,
+                    Tab::Unpacker,
+                    Tab::GameParamsBrowser,
+                    Tab::ShipStats,
+                    Tab::Localization,
+                    Tab::SoundBanks,
+                    Tab::BuildDiff,
+                    Tab::GameParamsDiff,
+                    Tab::Log,
+                    Tab::Settings,
+                ]
+                .to_vec(),
+            ),
             show_error_window: false,
             error_to_show: None,
             runtime: Runtime::new().expect("failed to create tokio runtime"),
@@ -632,18 +832,7 @@ impl WowsToolkitApp {
             let mut saved_state: Self = eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
             saved_state.tab_state.settings.locale = Some("en".to_string());
 
-            if saved_state.tab_state.settings.has_default_value_fix_015 {
-                saved_state.tab_state.settings.check_for_updates = true;
-                saved_state.tab_state.settings.send_replay_data = true;
-                saved_state.tab_state.settings.has_default_value_fix_015 = true;
-            }
-
-            if !saved_state.tab_state.settings.has_019_game_params_update {
-                saved_state.tab_state.settings.has_019_game_params_update = true;
-
-                // Remove the old game params
-                let _ = std::fs::remove_file(game_params_bin_path());
-            }
+            settings_migrations::migrate_settings(&mut saved_state.tab_state.settings);
 
             saved_state
                 .tab_state
@@ -651,7 +840,8 @@ impl WowsToolkitApp {
                 .store(saved_state.tab_state.settings.send_replay_data, Ordering::Relaxed);
 
             if !saved_state.tab_state.settings.wows_dir.is_empty() {
-                saved_state.tab_state.background_task = Some(saved_state.tab_state.load_game_data(PathBuf::from(saved_state.tab_state.settings.wows_dir.clone())));
+                let task = saved_state.tab_state.load_game_data(PathBuf::from(saved_state.tab_state.settings.wows_dir.clone()));
+                saved_state.tab_state.background_tasks.start(task);
             }
 
             saved_state
@@ -665,12 +855,35 @@ impl WowsToolkitApp {
             let default_wows_path = Path::new(default_wows_dir);
             if default_wows_path.exists() {
                 this.tab_state.settings.wows_dir = default_wows_dir.to_string();
-                this.tab_state.background_task = Some(this.tab_state.load_game_data(default_wows_path.to_path_buf()));
+                let task = this.tab_state.load_game_data(default_wows_path.to_path_buf());
+                this.tab_state.background_tasks.start(task);
             }
 
             this
         };
 
+        match PlayerStore::open(&player_store_path()) {
+            Ok(player_store) => {
+                state.tab_state.player_store = Some(Arc::new(player_store));
+                if let Err(e) = state.tab_state.migrate_legacy_player_data() {
+                    error!("failed to move tracked players into the player data store: {}", e);
+                    state.show_error_window = true;
+                    state.error_to_show = Some(Box::new(e));
+                }
+            }
+            Err(e) => {
+                // Tracked players are kept in memory for this session only
+                error!("failed to open the player data store: {}", e);
+                state.show_error_window = true;
+                state.error_to_show = Some(Box::new(e));
+
+                match PlayerStore::open_in_memory() {
+                    Ok(player_store) => state.tab_state.player_store = Some(Arc::new(player_store)),
+                    Err(e) => error!("failed to create an in-memory player data store: {}", e),
+                }
+            }
+        }
+
         let (tx, rx) = tokio::sync::mpsc::channel(1);
         state.tab_state.twitch_update_sender = Some(tx);
         task::begin_startup_tasks(&state, rx);
@@ -678,113 +891,142 @@ impl WowsToolkitApp {
         state
     }
 
-    pub fn build_bottom_panel(&mut self, ui: &mut Ui) {
-        ui.horizontal(|ui| {
-            // TODO: Merge these channels
-            if let Some(task) = &mut self.tab_state.background_task {
-                let desc = task.build_description(ui);
-                trace!("Task description: {:?}", desc);
-                if let Some(result) = desc {
-                    match &task.kind {
-                        BackgroundTaskKind::LoadingData => {
-                            self.tab_state.allow_changing_wows_dir();
-                        }
-                        BackgroundTaskKind::LoadingReplay => {
-                            // nothing to do
-                        }
-                        BackgroundTaskKind::Updating {
-                            rx: _rx,
-                            last_progress: _last_progress,
-                        } => {
-                            // do nothing
-                        }
-                        BackgroundTaskKind::PopulatePlayerInspectorFromReplays => {
-                            // do nothing
-                        }
-                    }
+    fn handle_finished_task(&mut self, finished: FinishedTask) {
+        // A superseded load finishing doesn't mean the newer one has
+        if matches!(finished.kind, BackgroundTaskKind::LoadingData) && !self.tab_state.background_tasks.is_running(|kind| matches!(kind, BackgroundTaskKind::LoadingData))
+        {
+            self.tab_state.allow_changing_wows_dir();
+        }
 
-                    match result {
-                        Ok(data) => match data {
-                            BackgroundTaskCompletion::DataLoaded { new_dir, wows_data, replays } => {
-                                let replays_dir = wows_data.replays_dir.clone();
-                                if let Some(old_wows_data) = &self.tab_state.world_of_warships_data {
-                                    *old_wows_data.write() = wows_data;
-                                } else {
-                                    self.tab_state.world_of_warships_data = Some(Arc::new(RwLock::new(wows_data)));
-                                }
-                                self.tab_state.update_wows_dir(&new_dir, &replays_dir);
-                                self.tab_state.replay_files = replays;
-                                self.tab_state.filtered_file_list = None;
-                                self.tab_state.used_filter = None;
+        match finished.result {
+            Ok(data) => match data {
+                BackgroundTaskCompletion::DataLoaded { new_dir, wows_data, replays } => {
+                    let replays_dir = wows_data.replays_dir.clone();
+                    if let Some(old_wows_data) = &self.tab_state.world_of_warships_data {
+                        *old_wows_data.write() = wows_data;
+                    } else {
+                        self.tab_state.world_of_warships_data = Some(Arc::new(RwLock::new(wows_data)));
+                    }
+                    self.tab_state.update_wows_dir(&new_dir, &replays_dir);
+                    self.tab_state.replay_files = replays;
+                    self.tab_state.filtered_file_list = None;
+                    self.tab_state.used_filter = None;
+                    self.tab_state.asset_gallery.clear();
 
-                                *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Successfully loaded game data", icons::CHECK_CIRCLE)))
-                            }
-                            BackgroundTaskCompletion::ReplayLoaded { replay } => {
-                                {
-                                    self.tab_state.replay_parser_tab.lock().game_chat.clear();
-                                }
-                                {
-                                    self.tab_state.settings.player_tracker.write().update_from_replay(&*replay.read());
-                                }
-                                self.tab_state.current_replay = Some(replay);
-                                *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Successfully loaded replay", icons::CHECK_CIRCLE)))
-                            }
-                            BackgroundTaskCompletion::UpdateDownloaded(new_exe) => {
-                                let current_process = env::args().next().expect("current process has no path?");
-                                let current_process_new_path = format!("{}.old", current_process);
-                                // Rename this process
-                                std::fs::rename(current_process.clone(), &current_process_new_path).expect("failed to rename current process");
-                                // Rename the new exe
-                                std::fs::rename(new_exe, &current_process).expect("failed to rename new process");
-
-                                Command::new(current_process)
-                                    .arg(current_process_new_path)
-                                    .spawn()
-                                    .expect("failed to execute updated process");
-
-                                std::process::exit(0);
-                            }
-                            BackgroundTaskCompletion::PopulatePlayerInspectorFromReplays => {
-                                // do nothing
-                            }
-                        },
-                        Err(ToolkitError::BackgroundTaskCompleted) => {
-                            self.tab_state.background_task = None;
-                        }
-                        Err(e) => {
+                    *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Successfully loaded game data", icons::CHECK_CIRCLE)))
+                }
+                BackgroundTaskCompletion::ReplayLoaded { replay } => {
+                    {
+                        self.tab_state.replay_parser_tab.lock().game_chat.clear();
+                    }
+                    if let Some(player_store) = self.tab_state.player_store.as_ref() {
+                        if let Err(e) = player_store.record_replay(&*replay.read()) {
                             self.show_error_window = true;
                             self.error_to_show = Some(Box::new(e));
                         }
                     }
+                    self.tab_state.current_replay = Some(replay);
+                    *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Successfully loaded replay", icons::CHECK_CIRCLE)))
+                }
+                BackgroundTaskCompletion::UpdateDownloaded(new_exe) => {
+                    let current_process = env::args().next().expect("current process has no path?");
+                    let current_process_new_path = format!("{}.old", current_process);
+                    // Rename this process
+                    std::fs::rename(current_process.clone(), &current_process_new_path).expect("failed to rename current process");
+                    // Rename the new exe
+                    std::fs::rename(new_exe, &current_process).expect("failed to rename new process");
+
+                    Command::new(current_process)
+                        .arg(current_process_new_path)
+                        .spawn()
+                        .expect("failed to execute updated process");
+
+                    std::process::exit(0);
+                }
+                BackgroundTaskCompletion::PopulatePlayerInspectorFromReplays => {
+                    // do nothing
+                }
+                BackgroundTaskCompletion::ReplaysArchived { archive, replays } => {
+                    let archived_count = replays.len();
+                    if let Some(wows_data) = &self.tab_state.world_of_warships_data {
+                        wows_data.write().replay_archive = archive;
+                    }
+                    // The originals are removed through the file watcher
+                    self.tab_state.replay_files.get_or_insert_with(HashMap::new).extend(replays);
+
+                    *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Archived {} replays", icons::CHECK_CIRCLE, archived_count)))
+                }
+                BackgroundTaskCompletion::BuildsCompared(diff) => {
+                    // Files which could not be compared aren't known to differ
+                    let changed_count = diff.entries.len() - diff.count(FileChange::Uncompared);
+                    self.tab_state.build_diff_tab.diff = Some(diff);
+
+                    *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!(
+                        "{} Found {} differences between builds",
+                        icons::CHECK_CIRCLE,
+                        changed_count
+                    )))
+                }
+                BackgroundTaskCompletion::GameParamsCompared(diff) => {
+                    let changed_count = diff.change_count();
+                    self.tab_state.game_params_diff_tab.diff = Some(diff);
+
+                    *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Found {} changed GameParams", icons::CHECK_CIRCLE, changed_count)))
                 }
-            } else if let Some(rx) = &self.tab_state.unpacker_progress {
-                if ui.button("Stop").clicked() {
-                    UNPACKER_STOP.store(true, Ordering::Relaxed);
+                BackgroundTaskCompletion::ShipStatsLoaded(database) => {
+                    self.tab_state.ship_stats_tab.database = Some(database);
+
+                    *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Successfully loaded ship data", icons::CHECK_CIRCLE)))
                 }
-                let mut done = false;
-                loop {
-                    match rx.try_recv() {
-                        Ok(progress) => {
-                            self.tab_state.last_progress = Some(progress);
+            },
+            Err(ToolkitError::BackgroundTaskCompleted) | Err(ToolkitError::BackgroundTaskCancelled) => {}
+            Err(e) => {
+                self.show_error_window = true;
+                self.error_to_show = Some(Box::new(e));
+            }
+        }
+    }
+
+    pub fn build_bottom_panel(&mut self, ui: &mut Ui) {
+        for finished in self.tab_state.background_tasks.poll() {
+            trace!("Task finished: {:?}", finished.result);
+            self.handle_finished_task(finished);
+        }
+
+        ui.horizontal(|ui| {
+            self.tab_state.background_tasks.build_summary(ui);
+
+            if !self.tab_state.unpacker_jobs.is_empty() {
+                let (finished, running): (Vec<UnpackerJob>, Vec<UnpackerJob>) =
+                    std::mem::take(&mut self.tab_state.unpacker_jobs).into_iter().partition(|job| job.is_finished());
+                self.tab_state.unpacker_jobs = running;
+
+                ui.vertical(|ui| {
+                    for job in &self.tab_state.unpacker_jobs {
+                        if job.build_progress(ui) {
+                            job.cancel();
                         }
-                        Err(TryRecvError::Empty) => {
-                            if let Some(last_progress) = self.tab_state.last_progress.as_ref() {
-                                ui.add(egui::ProgressBar::new(last_progress.progress).text(last_progress.file_name.as_str()));
-                            }
-                            break;
+                    }
+                });
+                ui.ctx().request_repaint_after(Duration::from_millis(100));
+
+                for job in finished {
+                    match job.join() {
+                        Ok(JobOutcome::Report(report)) => {
+                            let icon = if report.has_problems() { icons::WARNING } else { icons::CHECK_CIRCLE };
+                            *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} {}", icon, report.summary())));
+                            self.tab_state.last_extraction_report = Some(report);
                         }
-                        Err(TryRecvError::Disconnected) => {
-                            done = true;
-                            break;
+                        Ok(JobOutcome::Message(message)) => {
+                            *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} {}", icons::CHECK_CIRCLE, message)));
+                        }
+                        Err(e) => {
+                            self.show_error_window = true;
+                            self.error_to_show = Some(Box::new(e));
                         }
                     }
                 }
-
-                if done {
-                    self.tab_state.unpacker_progress.take();
-                    self.tab_state.last_progress.take();
-                }
-            } else {
+            } else if self.tab_state.background_tasks.is_empty() {
                 let reset_message = if let Some(timed_message) = &*self.tab_state.timed_message.read() {
                     if !timed_message.is_expired() {
                         ui.label(timed_message.message.as_str());
@@ -876,7 +1118,7 @@ impl eframe::App for WowsToolkitApp {
                                 #[cfg(target_os = "windows")]
                                 {
                                     if ui.button("Install Update").clicked() {
-                                        self.tab_state.background_task = Some(crate::task::start_download_update_task(&self.runtime, asset));
+                                        self.tab_state.background_tasks.start(crate::task::start_download_update_task(&self.runtime, asset));
                                     }
                                 }
                                 if ui.button("View Release").clicked() {
@@ -940,6 +1182,12 @@ impl eframe::App for WowsToolkitApp {
             self.build_bottom_panel(ui);
         });
 
+        if self.tab_state.background_tasks.expanded {
+            egui::TopBottomPanel::bottom("task_panel").resizable(true).default_height(150.0).show(ctx, |ui| {
+                self.tab_state.background_tasks.build_task_list(ui);
+            });
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             // The central panel the region left after adding TopPanel's and SidePanel's
             DockArea::new(&mut self.dock_state)
@@ -0,0 +1,179 @@
+//! Writing extracted files straight into a `.zip` or `.tar.gz` archive instead
+//! of loose files on disk.
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use rayon::prelude::*;
+use wowsunpack::data::{idx::FileNode, pkg::PkgFileLoader};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    error::ToolkitError,
+    extraction_manifest::{self, ConversionOptions},
+    unpacker_jobs::JobProgress,
+};
+
+/// Files are read and converted in parallel in batches of this size, then
+/// written to the archive in order. This bounds how much is held in memory.
+const BATCH_SIZE: usize = 64;
+
+/// Zip entries at least this large need the zip64 extensions
+const ZIP64_THRESHOLD: usize = 0xFFFF_FFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz",
+        }
+    }
+
+    pub fn default_file_name(&self) -> String {
+        format!("res.{}", self.extension())
+    }
+}
+
+enum ArchiveWriter {
+    Zip(ZipWriter<BufWriter<File>>),
+    TarGz(tar::Builder<GzEncoder<BufWriter<File>>>),
+}
+
+impl ArchiveWriter {
+    fn new(file: File, format: ArchiveFormat) -> Self {
+        let file = BufWriter::new(file);
+
+        match format {
+            ArchiveFormat::Zip => ArchiveWriter::Zip(ZipWriter::new(file)),
+            ArchiveFormat::TarGz => ArchiveWriter::TarGz(tar::Builder::new(GzEncoder::new(file, Compression::default()))),
+        }
+    }
+
+    fn add(&mut self, path: &str, data: &[u8], mtime: u64) -> Result<(), ToolkitError> {
+        match self {
+            ArchiveWriter::Zip(zip) => {
+                let options = FileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .large_file(data.len() >= ZIP64_THRESHOLD);
+                zip.start_file(path, options).map_err(io::Error::from)?;
+                zip.write_all(data)?;
+            }
+            ArchiveWriter::TarGz(tar) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(mtime);
+                header.set_cksum();
+                tar.append_data(&mut header, path, data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), ToolkitError> {
+        match self {
+            ArchiveWriter::Zip(mut zip) => {
+                zip.finish().map_err(io::Error::from)?.flush()?;
+            }
+            ArchiveWriter::TarGz(tar) => {
+                tar.into_inner()?.finish()?.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects every file under `items`, descending into folders
+pub fn collect_files(items: &[FileNode]) -> Vec<FileNode> {
+    let mut file_queue = items.to_vec();
+    let mut files: HashSet<FileNode> = HashSet::default();
+    while let Some(file) = file_queue.pop() {
+        if file.is_file() {
+            files.insert(file);
+        } else {
+            for child in file.children().values() {
+                file_queue.push(child.clone());
+            }
+        }
+    }
+
+    files.into_iter().collect()
+}
+
+/// Writes `files` into a new archive at `archive_path`, keeping their paths
+/// relative to `res/`. Returns the number of files written. The archive is
+/// written to a temporary file next to `archive_path` and only moved into place
+/// once complete, so a failed or cancelled job leaves nothing behind.
+pub fn write_archive(
+    archive_path: &Path,
+    format: ArchiveFormat,
+    mut files: Vec<FileNode>,
+    pkg_loader: &PkgFileLoader,
+    options: ConversionOptions,
+    progress: &JobProgress,
+) -> Result<usize, ToolkitError> {
+    // Sort so that archives of the same files are laid out identically
+    files.sort_by_key(|file| file.path().unwrap_or_default());
+
+    let bytes_total = files
+        .iter()
+        .map(|file| file.file_info().map(|info| info.unpacked_size as u64).unwrap_or_default())
+        .sum();
+    progress.set_totals(files.len(), bytes_total);
+
+    let mtime = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default();
+    let archive_dir = archive_path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(archive_dir)?;
+    // Dropping the temporary file on any early return deletes it
+    let temp_file = tempfile::Builder::new().prefix(".wows_toolkit_").tempfile_in(archive_dir)?;
+    let mut writer = ArchiveWriter::new(temp_file.reopen()?, format);
+    let mut written = 0;
+
+    for batch in files.chunks(BATCH_SIZE) {
+        if progress.is_cancelled() {
+            return Ok(written);
+        }
+
+        let contents: Vec<Result<(PathBuf, Vec<u8>, u64), ToolkitError>> = batch
+            .par_iter()
+            .map(|file| {
+                let parent_path = file.parent().map(|parent| parent.path()).transpose()?.unwrap_or_default();
+                progress.file_started(parent_path.join(file.filename()).to_string_lossy().into_owned());
+
+                let (file_name, data, _converted) = extraction_manifest::extracted_contents(file, pkg_loader, options)?;
+                let unpacked_size = file.file_info().map(|info| info.unpacked_size as u64).unwrap_or_default();
+
+                Ok((Path::new("res").join(parent_path).join(file_name), data, unpacked_size))
+            })
+            .collect();
+
+        for result in contents {
+            let (path, data, unpacked_size) = result?;
+            // Archives always use forward slashes regardless of platform
+            let path = path.to_string_lossy().replace('\\', "/");
+            writer.add(&path, &data, mtime)?;
+
+            written += 1;
+            progress.file_finished(unpacked_size);
+        }
+    }
+
+    writer.finish()?;
+    temp_file.persist(archive_path).map_err(|err| err.error)?;
+
+    Ok(written)
+}
@@ -0,0 +1,355 @@
+//! A thumbnail grid for filtered image files in the resource unpacker.
+//!
+//! Thumbnails are only requested for cells which are scrolled into view and are
+//! decoded on the rayon thread pool, so large folders like `gui/` stay responsive.
+//! At most [MAX_IN_FLIGHT_DECODES] are decoded at once, and queued requests for
+//! cells which have since scrolled out of view are dropped. At most
+//! [MAX_CACHED_THUMBNAILS] are kept, evicting the least recently drawn.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+};
+
+use egui::{Color32, ColorImage, Image, ImageSource, Sense, TextureHandle, TextureOptions, Vec2};
+use image::imageops::FilterType;
+use tracing::debug;
+use wowsunpack::data::{idx::FileNode, pkg::PkgFileLoader};
+
+use crate::{
+    app::ToolkitTabViewer,
+    dds,
+    error::ToolkitError,
+    file_unpacker::{IMAGE_FILE_TYPES, TEXTURE_FILE_TYPES},
+    icons,
+};
+
+const THUMBNAIL_SIZE: u32 = 128;
+const CELL_PADDING: f32 = 8.0;
+const LABEL_HEIGHT: f32 = 18.0;
+const MAX_CACHED_THUMBNAILS: usize = 1024;
+const MAX_IN_FLIGHT_DECODES: usize = 16;
+
+/// A decoded thumbnail, before it is uploaded to the GPU
+enum DecodedThumbnail {
+    Pixels(ColorImage),
+    /// SVGs are rasterized by egui's image loaders when drawn
+    Svg(Arc<[u8]>),
+}
+
+enum Thumbnail {
+    Loading,
+    Texture(TextureHandle),
+    Svg(Arc<[u8]>),
+    Failed,
+}
+
+struct CachedThumbnail {
+    thumbnail: Thumbnail,
+    /// The frame this thumbnail was last requested in
+    last_used: u64,
+}
+
+pub struct AssetGalleryState {
+    pub enabled: bool,
+    thumbnails: HashMap<PathBuf, CachedThumbnail>,
+    frame: u64,
+    /// URIs of evicted SVG thumbnails which egui's image loaders still hold on to
+    forgotten_svgs: Vec<String>,
+    selected: HashSet<PathBuf>,
+    /// Requested thumbnails waiting for a decode slot, most recent last
+    pending: Vec<(PathBuf, FileNode, Arc<PkgFileLoader>)>,
+    in_flight: usize,
+    sender: mpsc::Sender<(PathBuf, Result<DecodedThumbnail, ToolkitError>)>,
+    receiver: mpsc::Receiver<(PathBuf, Result<DecodedThumbnail, ToolkitError>)>,
+}
+
+impl Default for AssetGalleryState {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        AssetGalleryState {
+            enabled: false,
+            thumbnails: Default::default(),
+            frame: 0,
+            forgotten_svgs: Vec::new(),
+            selected: Default::default(),
+            pending: Vec::new(),
+            in_flight: 0,
+            sender,
+            receiver,
+        }
+    }
+}
+
+pub fn is_gallery_file(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    IMAGE_FILE_TYPES.iter().chain(TEXTURE_FILE_TYPES.iter()).any(|extension| path.ends_with(extension))
+}
+
+fn svg_uri(path: &Path) -> String {
+    format!("bytes://gallery/{}", path.to_string_lossy())
+}
+
+fn decode_thumbnail(node: &FileNode, pkg_loader: &PkgFileLoader) -> Result<DecodedThumbnail, ToolkitError> {
+    let mut data = Vec::with_capacity(node.file_info().map(|info| info.unpacked_size as usize).unwrap_or_default());
+    node.read_file(pkg_loader, &mut data)?;
+
+    if node.filename().ends_with(".svg") {
+        return Ok(DecodedThumbnail::Svg(data.into()));
+    }
+
+    let image = if node.filename().ends_with(".dds") {
+        dds::decode_dds(&data)?
+    } else {
+        image::load_from_memory(&data)?.into_rgba8()
+    };
+
+    let thumbnail = if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+        image::DynamicImage::ImageRgba8(image)
+            .resize(THUMBNAIL_SIZE, THUMBNAIL_SIZE, FilterType::Triangle)
+            .into_rgba8()
+    } else {
+        image
+    };
+
+    Ok(DecodedThumbnail::Pixels(ColorImage::from_rgba_unmultiplied(
+        [thumbnail.width() as usize, thumbnail.height() as usize],
+        thumbnail.as_raw(),
+    )))
+}
+
+impl AssetGalleryState {
+    /// Uploads any thumbnails which finished decoding since the last frame and
+    /// evicts the least recently used ones once the cache is full
+    fn poll(&mut self, ctx: &egui::Context) {
+        self.frame += 1;
+
+        while let Ok((path, result)) = self.receiver.try_recv() {
+            self.in_flight -= 1;
+            // The thumbnail was evicted or the cache cleared while it was decoding
+            let Some(cached) = self.thumbnails.get_mut(&path) else {
+                continue;
+            };
+            cached.thumbnail = match result {
+                Ok(DecodedThumbnail::Pixels(image)) => Thumbnail::Texture(ctx.load_texture(path.to_string_lossy(), image, TextureOptions::LINEAR)),
+                Ok(DecodedThumbnail::Svg(data)) => Thumbnail::Svg(data),
+                Err(e) => {
+                    debug!("failed to decode thumbnail for {:?}: {:?}", path, e);
+                    Thumbnail::Failed
+                }
+            };
+        }
+
+        if self.thumbnails.len() > MAX_CACHED_THUMBNAILS {
+            // Sorting by path as well breaks ties so exactly the excess is evicted
+            let mut by_age: Vec<(u64, &PathBuf)> = self.thumbnails.iter().map(|(path, cached)| (cached.last_used, path)).collect();
+            by_age.sort_unstable();
+            let excess = self.thumbnails.len() - MAX_CACHED_THUMBNAILS;
+            let evicted: HashSet<PathBuf> = by_age.into_iter().take(excess).map(|(_, path)| path.clone()).collect();
+            self.evict(|path, _| !evicted.contains(path));
+        }
+
+        for uri in self.forgotten_svgs.drain(..) {
+            ctx.forget_image(&uri);
+        }
+
+        self.start_decodes(ctx);
+    }
+
+    /// Starts decoding queued thumbnails until [MAX_IN_FLIGHT_DECODES] are running
+    fn start_decodes(&mut self, ctx: &egui::Context) {
+        while self.in_flight < MAX_IN_FLIGHT_DECODES {
+            let Some((path, node, pkg_loader)) = self.pending.pop() else {
+                break;
+            };
+
+            match self.thumbnails.get(&path) {
+                // Evicted while waiting
+                None => continue,
+                // Not drawn since the last frame, so it will be requested again if scrolled back into view
+                Some(cached) if cached.last_used + 1 < self.frame => {
+                    self.thumbnails.remove(&path);
+                    continue;
+                }
+                Some(_) => {}
+            }
+
+            self.in_flight += 1;
+            let sender = self.sender.clone();
+            let ctx = ctx.clone();
+            rayon::spawn(move || {
+                let result = decode_thumbnail(&node, &pkg_loader);
+                let _ = sender.send((path, result));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Removes the thumbnails `keep` returns false for. Textures are freed with their
+    /// handle, but rasterized SVGs have to be forgotten by egui's loaders on the next poll.
+    fn evict(&mut self, mut keep: impl FnMut(&PathBuf, &CachedThumbnail) -> bool) {
+        let forgotten_svgs = &mut self.forgotten_svgs;
+        self.thumbnails.retain(|path, cached| {
+            let keep = keep(path, cached);
+            if !keep && matches!(cached.thumbnail, Thumbnail::Svg(_)) {
+                forgotten_svgs.push(svg_uri(path));
+            }
+            keep
+        });
+    }
+
+    fn request(&mut self, path: &Path, node: &FileNode, pkg_loader: &Arc<PkgFileLoader>, ctx: &egui::Context) {
+        if let Some(cached) = self.thumbnails.get_mut(path) {
+            cached.last_used = self.frame;
+            return;
+        }
+        self.thumbnails.insert(
+            path.to_owned(),
+            CachedThumbnail {
+                thumbnail: Thumbnail::Loading,
+                last_used: self.frame,
+            },
+        );
+
+        self.pending.push((path.to_owned(), node.clone(), Arc::clone(pkg_loader)));
+        self.start_decodes(ctx);
+    }
+
+    /// Drops all thumbnails, e.g. after the game data is reloaded
+    pub fn clear(&mut self) {
+        self.evict(|_, _| false);
+        self.pending.clear();
+        self.selected.clear();
+    }
+
+    /// Drops the thumbnails of the previous filter's files
+    pub fn filter_changed(&mut self) {
+        self.evict(|_, _| false);
+        self.pending.clear();
+    }
+}
+
+impl ToolkitTabViewer<'_> {
+    /// Builds a grid of thumbnails for the image files in `files`
+    pub(crate) fn build_asset_gallery(&mut self, ui: &mut egui::Ui, files: &[(wowsunpack::Rc<PathBuf>, FileNode)]) {
+        let Some(pkg_loader) = self.tab_state.world_of_warships_data.as_ref().map(|wows_data| wows_data.read().pkg_loader.clone()) else {
+            return;
+        };
+
+        let images: Vec<&(wowsunpack::Rc<PathBuf>, FileNode)> = files.iter().filter(|(path, node)| node.is_file() && is_gallery_file(path)).collect();
+
+        let state = &mut self.tab_state.asset_gallery;
+        state.poll(ui.ctx());
+
+        let mut add_selected = false;
+        ui.horizontal(|ui| {
+            ui.label(format!("{} images, {} selected", images.len(), state.selected.len()));
+            if ui.button("Select All").clicked() {
+                state.selected.extend(images.iter().map(|(path, _)| PathBuf::from(&**path)));
+            }
+            if ui.add_enabled(!state.selected.is_empty(), egui::Button::new("Clear Selection")).clicked() {
+                state.selected.clear();
+            }
+            if ui
+                .add_enabled(!state.selected.is_empty(), egui::Button::new(format!("{} Add Selected", icons::PLUS)))
+                .clicked()
+            {
+                add_selected = true;
+            }
+        });
+        ui.separator();
+
+        let cell_size = Vec2::new(THUMBNAIL_SIZE as f32 + CELL_PADDING, THUMBNAIL_SIZE as f32 + LABEL_HEIGHT + CELL_PADDING);
+        let columns = ((ui.available_width() / (cell_size.x + ui.spacing().item_spacing.x)).floor() as usize).max(1);
+        let rows = images.len().div_ceil(columns);
+
+        egui::ScrollArea::vertical()
+            .id_source("asset_gallery_scroll_area")
+            .show_rows(ui, cell_size.y, rows, |ui, row_range| {
+                for row in row_range {
+                    ui.horizontal(|ui| {
+                        for (path, node) in images.iter().skip(row * columns).take(columns) {
+                            let path = PathBuf::from(&**path);
+                            let response = self.build_gallery_cell(ui, &path, node, &pkg_loader, cell_size);
+                            self.add_view_file_menu(&response, node);
+                        }
+                    });
+                }
+            });
+
+        if add_selected {
+            let state = &mut self.tab_state.asset_gallery;
+            let mut items_to_extract = self.tab_state.items_to_extract.lock();
+            items_to_extract.extend(
+                images
+                    .iter()
+                    .filter(|(path, _)| state.selected.contains(&PathBuf::from(&**path)))
+                    .map(|(_, node)| node.clone()),
+            );
+            state.selected.clear();
+        }
+    }
+
+    fn build_gallery_cell(&mut self, ui: &mut egui::Ui, path: &Path, node: &FileNode, pkg_loader: &Arc<PkgFileLoader>, cell_size: Vec2) -> egui::Response {
+        let state = &mut self.tab_state.asset_gallery;
+        state.request(path, node, pkg_loader, ui.ctx());
+
+        let is_selected = state.selected.contains(path);
+        let (rect, response) = ui.allocate_exact_size(cell_size, Sense::click());
+        if is_selected {
+            ui.painter().rect_filled(rect, 4.0, ui.visuals().selection.bg_fill);
+        } else if response.hovered() {
+            ui.painter().rect_filled(rect, 4.0, ui.visuals().widgets.hovered.bg_fill);
+        }
+
+        let image_rect = egui::Rect::from_min_size(rect.min + Vec2::splat(CELL_PADDING / 2.0), Vec2::splat(THUMBNAIL_SIZE as f32));
+        match state.thumbnails.get(path).map(|cached| &cached.thumbnail) {
+            Some(Thumbnail::Texture(texture)) => {
+                Image::new(texture).maintain_aspect_ratio(true).shrink_to_fit().paint_at(ui, image_rect);
+            }
+            Some(Thumbnail::Svg(data)) => {
+                Image::new(ImageSource::Bytes {
+                    uri: svg_uri(path).into(),
+                    bytes: Arc::clone(data).into(),
+                })
+                .fit_to_exact_size(image_rect.size())
+                .paint_at(ui, image_rect);
+            }
+            Some(Thumbnail::Failed) => {
+                ui.painter().text(
+                    image_rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    icons::FILE_X,
+                    egui::FontId::proportional(32.0),
+                    Color32::GRAY,
+                );
+            }
+            Some(Thumbnail::Loading) | None => {
+                egui::Spinner::new().paint_at(ui, egui::Rect::from_center_size(image_rect.center(), Vec2::splat(24.0)));
+            }
+        }
+
+        let label_rect = egui::Rect::from_min_max(egui::pos2(rect.min.x, image_rect.max.y), rect.max);
+        ui.painter().with_clip_rect(label_rect).text(
+            label_rect.center(),
+            egui::Align2::CENTER_CENTER,
+            node.filename(),
+            egui::FontId::proportional(11.0),
+            ui.visuals().text_color(),
+        );
+
+        let response = response.on_hover_text(Path::new("res").join(path).to_string_lossy());
+        if response.double_clicked() {
+            self.tab_state.items_to_extract.lock().push(node.clone());
+        } else if response.clicked() {
+            if is_selected {
+                state.selected.remove(path);
+            } else {
+                state.selected.insert(path.to_owned());
+            }
+        }
+
+        response
+    }
+}
@@ -0,0 +1,461 @@
+//! Comparison of the resource trees of two game builds.
+//!
+//! After a patch both the old and new `bin/<build>` directories exist for a
+//! while, and both builds' packages live side by side in `res_packages`. This
+//! module loads the idx files for each build and reports which files were
+//! added, removed or changed between them.
+
+use std::{
+    collections::BTreeMap,
+    fs::read_dir,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+};
+
+use egui::{Label, Sense};
+use egui_extras::{Column, TableBuilder};
+use tracing::debug;
+use wowsunpack::data::{
+    idx::{self, FileNode},
+    pkg::PkgFileLoader,
+};
+
+use crate::{
+    app::ToolkitTabViewer,
+    error::ToolkitError,
+    extraction_manifest::HashingWriter,
+    icons,
+    task::{BackgroundTask, BackgroundTaskCompletion, BackgroundTaskKind},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    Added,
+    Removed,
+    Changed,
+    /// The sizes matched but the contents of one of the builds could not be read
+    Uncompared,
+}
+
+impl FileChange {
+    fn label(&self) -> String {
+        match self {
+            FileChange::Added => format!("{} Added", icons::PLUS_CIRCLE),
+            FileChange::Removed => format!("{} Removed", icons::MINUS_CIRCLE),
+            FileChange::Changed => format!("{} Changed", icons::PENCIL_SIMPLE),
+            FileChange::Uncompared => format!("{} Could not compare", icons::QUESTION),
+        }
+    }
+}
+
+pub struct BuildDiffEntry {
+    pub path: PathBuf,
+    pub change: FileChange,
+    pub old_size: Option<u64>,
+    pub new_size: Option<u64>,
+    /// The file in the new build. This is `None` for removed files.
+    pub node: Option<FileNode>,
+}
+
+pub struct BuildDiff {
+    pub old_build: usize,
+    pub new_build: usize,
+    /// Whether files with identical sizes and no idx checksum had their contents compared
+    pub compared_contents: bool,
+    pub entries: Vec<BuildDiffEntry>,
+}
+
+impl BuildDiff {
+    pub fn count(&self, change: FileChange) -> usize {
+        self.entries.iter().filter(|entry| entry.change == change).count()
+    }
+}
+
+#[derive(Default)]
+pub struct BuildDiffTabState {
+    /// Builds found in the game's `bin` directory, newest first
+    pub available_builds: Vec<usize>,
+    pub old_build: Option<usize>,
+    pub new_build: Option<usize>,
+    pub compare_contents: bool,
+    pub filter: String,
+    pub hide_added: bool,
+    pub hide_removed: bool,
+    pub hide_changed: bool,
+    pub hide_uncompared: bool,
+    pub diff: Option<Arc<BuildDiff>>,
+    visible: Option<VisibleEntries>,
+}
+
+/// The entries shown for the current filter. Rebuilt only when the diff or filter changes.
+struct VisibleEntries {
+    diff: Arc<BuildDiff>,
+    filter: String,
+    hidden: [bool; 4],
+    /// Indices into the diff's entries
+    indices: Arc<Vec<usize>>,
+    extractable: Arc<Vec<FileNode>>,
+}
+
+/// Returns all build numbers in `<wows_dir>/bin`, newest first
+pub fn available_builds(wows_directory: &Path) -> Result<Vec<usize>, ToolkitError> {
+    let mut builds: Vec<usize> = read_dir(wows_directory.join("bin"))?
+        .flatten()
+        .filter(|entry| entry.file_type().map(|ty| ty.is_dir()).unwrap_or(false))
+        .filter_map(|entry| entry.file_name().to_str().and_then(|name| name.parse().ok()))
+        .collect();
+
+    builds.sort_unstable_by(|a, b| b.cmp(a));
+
+    Ok(builds)
+}
+
+/// Loads the file tree for a specific build from its idx files
+pub fn load_build_file_tree(wows_directory: &Path, build: usize) -> Result<FileNode, ToolkitError> {
+    let idx_dir = wows_directory.join("bin").join(build.to_string()).join("idx");
+    if !idx_dir.exists() {
+        return Err(ToolkitError::InvalidWowsDirectory(idx_dir));
+    }
+
+    let mut idx_files = Vec::new();
+    for file in read_dir(idx_dir)?.flatten() {
+        if !file.file_type()?.is_file() {
+            continue;
+        }
+
+        let file_data = std::fs::read(file.path())?;
+        idx_files.push(idx::parse(&mut Cursor::new(file_data.as_slice()))?);
+    }
+
+    Ok(idx::build_file_tree(idx_files.as_slice()))
+}
+
+fn files_by_path(file_tree: &FileNode) -> BTreeMap<PathBuf, FileNode> {
+    file_tree
+        .paths()
+        .into_iter()
+        .filter(|(_path, node)| node.is_file())
+        .map(|(path, node)| (PathBuf::from(&*path), node))
+        .collect()
+}
+
+fn unpacked_size(node: &FileNode) -> u64 {
+    node.file_info().map(|info| info.unpacked_size as u64).unwrap_or_default()
+}
+
+/// CRC32 recorded for the file in the idx files
+fn idx_crc32(node: &FileNode) -> Option<u32> {
+    node.file_info().map(|info| info.crc32).filter(|crc32| *crc32 != 0)
+}
+
+fn checksum(node: &FileNode, pkg_loader: &PkgFileLoader) -> Result<u32, ToolkitError> {
+    let mut hasher = HashingWriter::new(io::sink());
+    node.read_file(pkg_loader, &mut hasher)?;
+
+    Ok(hasher.finish().1)
+}
+
+fn diff_builds(
+    wows_directory: PathBuf,
+    pkg_loader: Arc<PkgFileLoader>,
+    old_build: usize,
+    new_build: usize,
+    compare_contents: bool,
+) -> Result<BackgroundTaskCompletion, ToolkitError> {
+    debug!("Comparing build {} to build {}", old_build, new_build);
+
+    let old_files = files_by_path(&load_build_file_tree(&wows_directory, old_build)?);
+    let mut new_files = files_by_path(&load_build_file_tree(&wows_directory, new_build)?);
+
+    let mut entries = Vec::new();
+    for (path, old_node) in old_files {
+        let old_size = unpacked_size(&old_node);
+        let Some(new_node) = new_files.remove(&path) else {
+            entries.push(BuildDiffEntry {
+                path,
+                change: FileChange::Removed,
+                old_size: Some(old_size),
+                new_size: None,
+                node: None,
+            });
+            continue;
+        };
+
+        let new_size = unpacked_size(&new_node);
+        let change = if old_size != new_size {
+            Some(FileChange::Changed)
+        } else if let (Some(old_crc32), Some(new_crc32)) = (idx_crc32(&old_node), idx_crc32(&new_node)) {
+            (old_crc32 != new_crc32).then_some(FileChange::Changed)
+        } else if compare_contents {
+            // Only files the idx files have no checksum for are read
+            match (checksum(&old_node, &pkg_loader), checksum(&new_node, &pkg_loader)) {
+                (Ok(old_checksum), Ok(new_checksum)) => (old_checksum != new_checksum).then_some(FileChange::Changed),
+                (old_result, new_result) => {
+                    // The old build's packages may already have been removed by the launcher
+                    debug!("could not compare contents of {:?}: {:?} {:?}", path, old_result.err(), new_result.err());
+                    Some(FileChange::Uncompared)
+                }
+            }
+        } else {
+            None
+        };
+
+        if let Some(change) = change {
+            entries.push(BuildDiffEntry {
+                path,
+                change,
+                old_size: Some(old_size),
+                new_size: Some(new_size),
+                node: Some(new_node),
+            });
+        }
+    }
+
+    entries.extend(new_files.into_iter().map(|(path, node)| BuildDiffEntry {
+        path,
+        change: FileChange::Added,
+        old_size: None,
+        new_size: Some(unpacked_size(&node)),
+        node: Some(node),
+    }));
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(BackgroundTaskCompletion::BuildsCompared(Arc::new(BuildDiff {
+        old_build,
+        new_build,
+        compared_contents: compare_contents,
+        entries,
+    })))
+}
+
+pub fn start_comparing_builds(wows_directory: PathBuf, pkg_loader: Arc<PkgFileLoader>, old_build: usize, new_build: usize, compare_contents: bool) -> BackgroundTask {
+    let (tx, rx) = mpsc::channel();
+
+    let _join_handle = std::thread::spawn(move || {
+        let _ = tx.send(diff_builds(wows_directory, pkg_loader, old_build, new_build, compare_contents));
+    });
+
+    BackgroundTask::new(rx, BackgroundTaskKind::ComparingBuilds)
+}
+
+impl VisibleEntries {
+    fn new(diff: Arc<BuildDiff>, filter: &str, hidden: [bool; 4]) -> Self {
+        let [hide_added, hide_removed, hide_changed, hide_uncompared] = hidden;
+        let glob = if filter.contains('*') { glob::Pattern::new(filter).ok() } else { None };
+        let indices: Vec<usize> = diff
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| match entry.change {
+                FileChange::Added => !hide_added,
+                FileChange::Removed => !hide_removed,
+                FileChange::Changed => !hide_changed,
+                FileChange::Uncompared => !hide_uncompared,
+            })
+            .filter(|(_, entry)| {
+                if let Some(glob) = &glob {
+                    glob.matches_path(&entry.path)
+                } else {
+                    filter.is_empty() || entry.path.to_string_lossy().contains(filter)
+                }
+            })
+            .map(|(index, _)| index)
+            .collect();
+        let extractable = indices.iter().filter_map(|index| diff.entries[*index].node.clone()).collect();
+
+        VisibleEntries {
+            diff,
+            filter: filter.to_owned(),
+            hidden,
+            indices: Arc::new(indices),
+            extractable: Arc::new(extractable),
+        }
+    }
+}
+
+fn format_size(size: Option<u64>) -> String {
+    size.map(|size| humansize::format_size(size, humansize::DECIMAL)).unwrap_or_default()
+}
+
+impl ToolkitTabViewer<'_> {
+    fn build_selector(ui: &mut egui::Ui, id: &str, label: &str, selected: &mut Option<usize>, builds: &[usize]) {
+        egui::ComboBox::new(id, label)
+            .selected_text(selected.map(|build| build.to_string()).unwrap_or_else(|| "Select build".to_string()))
+            .show_ui(ui, |ui| {
+                for build in builds {
+                    ui.selectable_value(selected, Some(*build), build.to_string());
+                }
+            });
+    }
+
+    /// Builds the "Compare Builds" tab
+    pub fn build_build_diff_tab(&mut self, ui: &mut egui::Ui) {
+        let wows_dir = PathBuf::from(&self.tab_state.settings.wows_dir);
+        let pkg_loader = self.tab_state.world_of_warships_data.as_ref().map(|wows_data| wows_data.read().pkg_loader.clone());
+
+        let state = &mut self.tab_state.build_diff_tab;
+        if state.available_builds.is_empty() && wows_dir.join("bin").exists() {
+            state.available_builds = available_builds(&wows_dir).unwrap_or_default();
+            state.new_build = state.available_builds.first().copied();
+            state.old_build = state.available_builds.get(1).copied();
+        }
+
+        ui.horizontal(|ui| {
+            Self::build_selector(ui, "build_diff_old_build", "Old build", &mut state.old_build, &state.available_builds);
+            Self::build_selector(ui, "build_diff_new_build", "New build", &mut state.new_build, &state.available_builds);
+
+            ui.checkbox(&mut state.compare_contents, "Compare contents")
+                .on_hover_text("Read and checksum files whose sizes did not change when the idx files have no checksum for them. This is much slower.");
+
+            if ui.button(format!("{} Refresh Builds", icons::ARROW_CLOCKWISE)).clicked() {
+                state.available_builds = available_builds(&wows_dir).unwrap_or_default();
+            }
+
+            let can_compare = !self.tab_state.background_tasks.is_running(|kind| matches!(kind, BackgroundTaskKind::ComparingBuilds))
+                && pkg_loader.is_some()
+                && state.old_build.is_some()
+                && state.new_build.is_some()
+                && state.old_build != state.new_build;
+            if ui.add_enabled(can_compare, egui::Button::new(format!("{} Compare", icons::GIT_DIFF))).clicked() {
+                if let (Some(pkg_loader), Some(old_build), Some(new_build)) = (pkg_loader, state.old_build, state.new_build) {
+                    self.tab_state
+                        .background_tasks
+                        .start(start_comparing_builds(wows_dir.clone(), pkg_loader, old_build, new_build, state.compare_contents));
+                }
+            }
+        });
+
+        if state.available_builds.len() < 2 {
+            ui.label("At least two builds must be present in the game's bin directory to compare them.");
+        }
+
+        let Some(diff) = state.diff.clone() else {
+            return;
+        };
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "Build {} {} {}: {} added, {} removed, {} changed{}",
+                diff.old_build,
+                icons::ARROW_RIGHT,
+                diff.new_build,
+                diff.count(FileChange::Added),
+                diff.count(FileChange::Removed),
+                diff.count(FileChange::Changed),
+                if diff.compared_contents { "" } else { " (by size)" }
+            ));
+            let uncompared = diff.count(FileChange::Uncompared);
+            if uncompared > 0 {
+                ui.label(format!("{} {} could not be compared", icons::WARNING, uncompared))
+                    .on_hover_text("These files have the same size in both builds, but one of the builds' packages could not be read");
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.filter).hint_text("Filter"));
+            let mut show_added = !state.hide_added;
+            let mut show_removed = !state.hide_removed;
+            let mut show_changed = !state.hide_changed;
+            let mut show_uncompared = !state.hide_uncompared;
+            ui.checkbox(&mut show_added, "Added");
+            ui.checkbox(&mut show_removed, "Removed");
+            ui.checkbox(&mut show_changed, "Changed");
+            ui.checkbox(&mut show_uncompared, "Could not compare");
+            state.hide_added = !show_added;
+            state.hide_removed = !show_removed;
+            state.hide_changed = !show_changed;
+            state.hide_uncompared = !show_uncompared;
+        });
+
+        let hidden = [state.hide_added, state.hide_removed, state.hide_changed, state.hide_uncompared];
+        let is_current = state
+            .visible
+            .as_ref()
+            .map(|visible| Arc::ptr_eq(&visible.diff, &diff) && visible.filter == state.filter && visible.hidden == hidden)
+            .unwrap_or(false);
+        if !is_current {
+            state.visible = Some(VisibleEntries::new(Arc::clone(&diff), &state.filter, hidden));
+        }
+        let (visible, extractable) = state
+            .visible
+            .as_ref()
+            .map(|visible| (Arc::clone(&visible.indices), Arc::clone(&visible.extractable)))
+            .expect("visible entries were just built");
+
+        let mut extract_clicked = false;
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    !extractable.is_empty(),
+                    egui::Button::new(format!("{} Extract {} Files", icons::FLOPPY_DISK, extractable.len())),
+                )
+                .on_hover_text("Extracts the shown added and changed files from the new build to the unpacker's output directory")
+                .clicked()
+            {
+                extract_clicked = true;
+            }
+
+            if ui.button(format!("{} Add to Unpacker Selection", icons::PLUS)).clicked() {
+                self.tab_state.items_to_extract.lock().extend(extractable.iter().cloned());
+            }
+        });
+
+        let table = TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::initial(100.0).clip(true))
+            .column(Column::initial(90.0).clip(true))
+            .column(Column::initial(90.0).clip(true))
+            .column(Column::remainder())
+            .min_scrolled_height(0.0)
+            .id_salt("build_diff_table");
+
+        table
+            .header(20.0, |mut header| {
+                header.col(|ui| {
+                    ui.strong("Change");
+                });
+                header.col(|ui| {
+                    ui.strong("Old Size");
+                });
+                header.col(|ui| {
+                    ui.strong("New Size");
+                });
+                header.col(|ui| {
+                    ui.strong("Path");
+                });
+            })
+            .body(|body| {
+                body.rows(20.0, visible.len(), |mut row| {
+                    let entry = &diff.entries[visible[row.index()]];
+                    row.col(|ui| {
+                        ui.label(entry.change.label());
+                    });
+                    row.col(|ui| {
+                        ui.label(format_size(entry.old_size));
+                    });
+                    row.col(|ui| {
+                        ui.label(format_size(entry.new_size));
+                    });
+                    row.col(|ui| {
+                        let label = ui.add(Label::new(Path::new("res").join(&entry.path).to_string_lossy().into_owned()).sense(Sense::click()));
+                        if let Some(node) = &entry.node {
+                            if label.double_clicked() {
+                                self.tab_state.items_to_extract.lock().push(node.clone());
+                            }
+                        }
+                    });
+                });
+            });
+
+        if extract_clicked {
+            let output_dir = Path::new(self.tab_state.output_dir.as_str()).join(diff.new_build.to_string()).join("res");
+            self.extract_files(&output_dir, &extractable);
+        }
+    }
+}
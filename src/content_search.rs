@@ -0,0 +1,279 @@
+//! Searching the contents of text-like files in the packed resources.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, TryRecvError},
+        Arc,
+    },
+};
+
+use egui::{CollapsingHeader, Label, RichText};
+use regex::{Regex, RegexBuilder};
+use tracing::debug;
+use wowsunpack::data::{idx::FileNode, pkg::PkgFileLoader};
+
+use crate::{
+    app::{TimedMessage, ToolkitTabViewer},
+    error::ToolkitError,
+    icons,
+    unpacker_jobs::{JobOutcome, JobProgress, UnpackerJob},
+};
+
+/// File types which are searched. Gettext catalogs are searched through their raw strings.
+const SEARCHABLE_FILE_TYPES: [&str; 8] = [".xml", ".json", ".py", ".txt", ".mo", ".lua", ".visual", ".def"];
+
+/// Only the first few matching lines of each file are kept
+const MAX_LINES_PER_FILE: usize = 5;
+const MAX_PREVIEW_LEN: usize = 200;
+
+pub struct ContentSearchMatch {
+    pub path: PathBuf,
+    pub node: FileNode,
+    /// Line number and contents of each matching line
+    pub lines: Vec<(usize, String)>,
+    /// Total number of matching lines, including those not kept in `lines`
+    pub match_count: usize,
+}
+
+#[derive(Default)]
+pub struct ContentSearchState {
+    pub query: String,
+    pub use_regex: bool,
+    pub case_sensitive: bool,
+    /// Matches from the running search. The search runs as an [UnpackerJob], which reports its progress.
+    pub receiver: Option<mpsc::Receiver<ContentSearchMatch>>,
+    /// Progress of the running search's job, used to stop it from the search bar
+    job_progress: Option<Arc<JobProgress>>,
+    pub results: Option<Vec<ContentSearchMatch>>,
+}
+
+fn preview(line: &str) -> String {
+    let line = line.trim();
+    match line.char_indices().nth(MAX_PREVIEW_LEN) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_owned(),
+    }
+}
+
+fn search_file(node: &FileNode, pkg_loader: &PkgFileLoader, pattern: &Regex) -> Option<(Vec<(usize, String)>, usize)> {
+    let mut data = Vec::with_capacity(node.file_info().map(|info| info.unpacked_size as usize).unwrap_or_default());
+    if let Err(e) = node.read_file(pkg_loader, &mut data) {
+        debug!("failed to read {:?} for content search: {:?}", node.path(), e);
+        return None;
+    }
+
+    let contents = String::from_utf8_lossy(&data);
+
+    let mut lines = Vec::new();
+    let mut match_count = 0;
+    // .mo files separate strings with NUL rather than newlines
+    for (line_number, line) in contents.split(['\n', '\0']).enumerate() {
+        if pattern.is_match(line) {
+            match_count += 1;
+            if lines.len() < MAX_LINES_PER_FILE {
+                lines.push((line_number + 1, preview(line)));
+            }
+        }
+    }
+
+    (match_count > 0).then_some((lines, match_count))
+}
+
+fn unpacked_size(node: &FileNode) -> u64 {
+    node.file_info().map(|info| info.unpacked_size as u64).unwrap_or_default()
+}
+
+fn search_contents(
+    files: Vec<(PathBuf, FileNode)>,
+    pkg_loader: Arc<PkgFileLoader>,
+    pattern: Regex,
+    tx: mpsc::Sender<ContentSearchMatch>,
+    progress: &JobProgress,
+) -> Result<JobOutcome, ToolkitError> {
+    progress.set_totals(files.len(), files.iter().map(|(_, node)| unpacked_size(node)).sum());
+
+    let mut matching_files = 0;
+    for (path, node) in files {
+        if progress.is_cancelled() {
+            return Ok(JobOutcome::Message(format!("Stopped searching after {} matching files", matching_files)));
+        }
+
+        progress.file_started(path.to_string_lossy().into_owned());
+        let size = unpacked_size(&node);
+        if let Some((lines, match_count)) = search_file(&node, &pkg_loader, &pattern) {
+            matching_files += 1;
+            if tx.send(ContentSearchMatch { path, node, lines, match_count }).is_err() {
+                // The results were replaced by a newer search, so nobody cares about these anymore
+                return Ok(JobOutcome::Message("Content search was replaced by a newer one".to_owned()));
+            }
+        }
+        progress.file_finished(size);
+    }
+
+    Ok(JobOutcome::Message(format!("Found {} files matching the content search", matching_files)))
+}
+
+impl ContentSearchState {
+    /// Drains any pending updates from a running search
+    fn poll(&mut self) {
+        let Some(rx) = self.receiver.as_ref() else {
+            return;
+        };
+
+        loop {
+            match rx.try_recv() {
+                Ok(found) => {
+                    self.results.get_or_insert_with(Vec::new).push(found);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.receiver = None;
+                    self.job_progress = None;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(progress) = self.job_progress.as_ref() {
+            progress.cancel();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+}
+
+impl ToolkitTabViewer<'_> {
+    fn start_content_search(&mut self) {
+        let Some(wows_data) = self.tab_state.world_of_warships_data.as_ref() else {
+            return;
+        };
+
+        let state = &mut self.tab_state.content_search;
+        let pattern = if state.use_regex { state.query.clone() } else { regex::escape(&state.query) };
+        let pattern = match RegexBuilder::new(&pattern).case_insensitive(!state.case_sensitive).build() {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Invalid search pattern: {}", icons::WARNING, e)));
+                return;
+            }
+        };
+
+        let (files, pkg_loader) = {
+            let wows_data = wows_data.read();
+            let files: Vec<(PathBuf, FileNode)> = wows_data
+                .filtered_files
+                .iter()
+                .filter(|(path, node)| node.is_file() && SEARCHABLE_FILE_TYPES.iter().any(|extension| path.to_string_lossy().ends_with(extension)))
+                .map(|(path, node)| (PathBuf::from(&**path), node.clone()))
+                .collect();
+
+            (files, wows_data.pkg_loader.clone())
+        };
+
+        // Only the newest search's results are shown
+        state.stop();
+
+        let (tx, rx) = mpsc::channel();
+        let job = UnpackerJob::spawn(format!("Searching contents for \"{}\"", state.query), move |progress| {
+            search_contents(files, pkg_loader, pattern, tx, progress)
+        });
+        state.receiver = Some(rx);
+        state.job_progress = Some(job.progress());
+        state.results = Some(Vec::new());
+
+        self.tab_state.unpacker_jobs.push(job);
+    }
+
+    /// Builds the search bar for searching file contents
+    pub(crate) fn build_content_search_bar(&mut self, ui: &mut egui::Ui) {
+        self.tab_state.content_search.poll();
+
+        ui.horizontal(|ui| {
+            let state = &mut self.tab_state.content_search;
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut state.query)
+                    .hint_text("Search contents")
+                    .desired_width(ui.available_width() - 150.0),
+            );
+            ui.toggle_value(&mut state.use_regex, ".*").on_hover_text("Use a regular expression");
+            ui.toggle_value(&mut state.case_sensitive, "Aa").on_hover_text("Case sensitive");
+
+            if state.is_running() {
+                if ui.button(icons::STOP).on_hover_text("Stop searching").clicked() {
+                    state.stop();
+                }
+            } else {
+                let search_requested = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                let can_search = !state.query.is_empty();
+                if (ui
+                    .add_enabled(can_search, egui::Button::new(icons::MAGNIFYING_GLASS))
+                    .on_hover_text("Search")
+                    .clicked()
+                    || search_requested)
+                    && can_search
+                {
+                    self.start_content_search();
+                }
+            }
+        });
+
+        if self.tab_state.content_search.is_running() {
+            ui.ctx().request_repaint();
+        }
+    }
+
+    /// Builds the list of files matched by a content search. Returns `false` if there are no results to show.
+    pub(crate) fn build_content_search_results(&mut self, ui: &mut egui::Ui) -> bool {
+        let state = &self.tab_state.content_search;
+        let Some(results) = state.results.as_ref() else {
+            return false;
+        };
+
+        let mut clear = false;
+        ui.horizontal(|ui| {
+            // Progress is shown with the other unpacker jobs
+            if state.is_running() {
+                ui.spinner();
+            }
+            ui.label(format!("{} matching files", results.len()));
+
+            if ui.button(format!("{} Add All", icons::PLUS)).clicked() {
+                self.tab_state.items_to_extract.lock().extend(results.iter().map(|result| result.node.clone()));
+            }
+
+            if !state.is_running() && ui.button(format!("{} Clear", icons::X)).clicked() {
+                clear = true;
+            }
+        });
+
+        egui::ScrollArea::both().id_source("content_search_results_scroll_area").show(ui, |ui| {
+            for result in results {
+                let title = format!("{} ({})", Path::new("res").join(&result.path).to_string_lossy(), result.match_count);
+                let header = CollapsingHeader::new(title).id_source(&result.path).show(ui, |ui| {
+                    for (line_number, line) in &result.lines {
+                        ui.add(Label::new(RichText::new(format!("{:>6}: {}", line_number, line)).monospace()).wrap_mode(egui::TextWrapMode::Extend));
+                    }
+                    if result.match_count > result.lines.len() {
+                        ui.label(format!("… and {} more", result.match_count - result.lines.len()));
+                    }
+                });
+
+                self.add_view_file_menu(&header.header_response, &result.node);
+                if header.header_response.double_clicked() {
+                    self.tab_state.items_to_extract.lock().push(result.node.clone());
+                }
+            }
+        });
+
+        if clear {
+            self.tab_state.content_search.results = None;
+        }
+
+        true
+    }
+}
@@ -0,0 +1,210 @@
+use std::io::Cursor;
+
+use image::{ImageOutputFormat, RgbaImage};
+
+use crate::error::ToolkitError;
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const DDS_HEADER_SIZE: usize = 124;
+const DX10_HEADER_SIZE: usize = 20;
+
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+
+/// Block-compressed formats we know how to decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextureFormat {
+    Bc1,
+    Bc3,
+    Bc5,
+    Bc7,
+    Bgra8,
+    Rgba8,
+}
+
+impl TextureFormat {
+    fn from_fourcc(fourcc: &[u8]) -> Result<Self, ToolkitError> {
+        match fourcc {
+            b"DXT1" => Ok(TextureFormat::Bc1),
+            b"DXT5" => Ok(TextureFormat::Bc3),
+            b"ATI2" | b"BC5U" => Ok(TextureFormat::Bc5),
+            other => Err(ToolkitError::UnsupportedDdsFormat(String::from_utf8_lossy(other).into_owned())),
+        }
+    }
+
+    fn from_dxgi(format: u32) -> Result<Self, ToolkitError> {
+        match format {
+            // DXGI_FORMAT_BC1_UNORM(_SRGB)
+            71 | 72 => Ok(TextureFormat::Bc1),
+            // DXGI_FORMAT_BC3_UNORM(_SRGB)
+            77 | 78 => Ok(TextureFormat::Bc3),
+            // DXGI_FORMAT_BC5_UNORM
+            83 => Ok(TextureFormat::Bc5),
+            // DXGI_FORMAT_BC7_UNORM(_SRGB)
+            98 | 99 => Ok(TextureFormat::Bc7),
+            // DXGI_FORMAT_R8G8B8A8_UNORM(_SRGB)
+            28 | 29 => Ok(TextureFormat::Rgba8),
+            // DXGI_FORMAT_B8G8R8A8_UNORM(_SRGB)
+            87 | 91 => Ok(TextureFormat::Bgra8),
+            other => Err(ToolkitError::UnsupportedDdsFormat(format!("DXGI format {}", other))),
+        }
+    }
+
+    /// Size in bytes of a `width` x `height` mip in this format, or `None` if it overflows
+    fn mip_size(self, width: usize, height: usize) -> Option<usize> {
+        let block_bytes = match self {
+            TextureFormat::Rgba8 | TextureFormat::Bgra8 => return width.checked_mul(height)?.checked_mul(4),
+            TextureFormat::Bc1 => 8,
+            TextureFormat::Bc3 | TextureFormat::Bc5 | TextureFormat::Bc7 => 16,
+        };
+
+        width.div_ceil(4).checked_mul(height.div_ceil(4))?.checked_mul(block_bytes)
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().expect("slice is exactly 4 bytes"))
+}
+
+/// Decodes the top-level mip of a DDS texture
+pub fn decode_dds(data: &[u8]) -> Result<RgbaImage, ToolkitError> {
+    if data.len() < DDS_MAGIC.len() + DDS_HEADER_SIZE || &data[..4] != DDS_MAGIC {
+        return Err(ToolkitError::InvalidDds("missing DDS header"));
+    }
+
+    let header = &data[4..4 + DDS_HEADER_SIZE];
+    let height = read_u32(header, 8) as usize;
+    let width = read_u32(header, 12) as usize;
+
+    // The pixel format struct starts at offset 72 in the header
+    let pf_flags = read_u32(header, 76);
+    let fourcc = &header[80..84];
+    let rgb_bit_count = read_u32(header, 84);
+    let r_mask = read_u32(header, 88);
+
+    let mut data_offset = DDS_MAGIC.len() + DDS_HEADER_SIZE;
+    let format = if pf_flags & DDPF_FOURCC != 0 {
+        if fourcc == b"DX10" {
+            if data.len() < data_offset + DX10_HEADER_SIZE {
+                return Err(ToolkitError::InvalidDds("truncated DX10 header"));
+            }
+            let dxgi_format = read_u32(data, data_offset);
+            data_offset += DX10_HEADER_SIZE;
+            TextureFormat::from_dxgi(dxgi_format)?
+        } else {
+            TextureFormat::from_fourcc(fourcc)?
+        }
+    } else if pf_flags & DDPF_RGB != 0 && rgb_bit_count == 32 {
+        if r_mask == 0x00ff0000 {
+            TextureFormat::Bgra8
+        } else {
+            TextureFormat::Rgba8
+        }
+    } else {
+        return Err(ToolkitError::UnsupportedDdsFormat(format!("pixel format flags {:#x}", pf_flags)));
+    };
+
+    if width == 0 || height == 0 {
+        return Err(ToolkitError::InvalidDds("texture has no dimensions"));
+    }
+
+    // The dimensions come from the file, so check them against the data before allocating for the decoded pixels
+    let pixel_count = width.checked_mul(height).ok_or(ToolkitError::InvalidDds("texture dimensions are too large"))?;
+    let mip_size = format.mip_size(width, height).ok_or(ToolkitError::InvalidDds("texture dimensions are too large"))?;
+    let pixel_data = &data[data_offset..];
+    if pixel_data.len() < mip_size {
+        return Err(ToolkitError::InvalidDds("truncated pixel data"));
+    }
+
+    let rgba = match format {
+        TextureFormat::Rgba8 | TextureFormat::Bgra8 => {
+            let mut rgba = pixel_data[..mip_size].to_vec();
+            if format == TextureFormat::Bgra8 {
+                for pixel in rgba.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            rgba
+        }
+        _ => {
+            let mut pixels = vec![0u32; pixel_count];
+            let result = match format {
+                TextureFormat::Bc1 => texture2ddecoder::decode_bc1(pixel_data, width, height, &mut pixels),
+                TextureFormat::Bc3 => texture2ddecoder::decode_bc3(pixel_data, width, height, &mut pixels),
+                TextureFormat::Bc5 => texture2ddecoder::decode_bc5(pixel_data, width, height, &mut pixels),
+                TextureFormat::Bc7 => texture2ddecoder::decode_bc7(pixel_data, width, height, &mut pixels),
+                TextureFormat::Rgba8 | TextureFormat::Bgra8 => unreachable!("uncompressed formats are handled above"),
+            };
+            result.map_err(ToolkitError::InvalidDds)?;
+
+            // The decoder produces BGRA pixels
+            pixels
+                .iter()
+                .flat_map(|pixel| {
+                    let [b, g, r, a] = pixel.to_le_bytes();
+                    [r, g, b, a]
+                })
+                .collect()
+        }
+    };
+
+    RgbaImage::from_raw(width as u32, height as u32, rgba).ok_or(ToolkitError::InvalidDds("pixel data does not match dimensions"))
+}
+
+/// Decodes a DDS texture and re-encodes it as a PNG
+pub fn dds_to_png(data: &[u8]) -> Result<Vec<u8>, ToolkitError> {
+    let image = decode_dds(data)?;
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageOutputFormat::Png)?;
+
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dds_header(width: u32, height: u32, fourcc: &[u8; 4], pf_flags: u32, pixel_data_len: usize) -> Vec<u8> {
+        let mut header = [0u8; DDS_HEADER_SIZE];
+        header[0..4].copy_from_slice(&(DDS_HEADER_SIZE as u32).to_le_bytes());
+        header[8..12].copy_from_slice(&height.to_le_bytes());
+        header[12..16].copy_from_slice(&width.to_le_bytes());
+        header[76..80].copy_from_slice(&pf_flags.to_le_bytes());
+        header[80..84].copy_from_slice(fourcc);
+        header[84..88].copy_from_slice(&32u32.to_le_bytes());
+
+        let mut data = DDS_MAGIC.to_vec();
+        data.extend_from_slice(&header);
+        data.resize(data.len() + pixel_data_len, 0);
+        data
+    }
+
+    #[test]
+    fn decodes_small_texture() {
+        let data = dds_header(4, 4, b"DXT1", DDPF_FOURCC, 8);
+        let image = decode_dds(&data).expect("failed to decode texture");
+
+        assert_eq!(image.dimensions(), (4, 4));
+    }
+
+    #[test]
+    fn oversized_compressed_header_is_rejected() {
+        let data = dds_header(u32::MAX, u32::MAX, b"DXT1", DDPF_FOURCC, 64);
+
+        assert!(matches!(decode_dds(&data), Err(ToolkitError::InvalidDds(_))));
+    }
+
+    #[test]
+    fn oversized_uncompressed_header_is_rejected() {
+        let data = dds_header(u32::MAX, u32::MAX, &[0; 4], DDPF_RGB, 64);
+
+        assert!(matches!(decode_dds(&data), Err(ToolkitError::InvalidDds(_))));
+    }
+
+    #[test]
+    fn truncated_pixel_data_is_rejected() {
+        let data = dds_header(64, 64, b"DXT5", DDPF_FOURCC, 16);
+
+        assert!(matches!(decode_dds(&data), Err(ToolkitError::InvalidDds(_))));
+    }
+}
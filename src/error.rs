@@ -25,12 +25,96 @@ pub enum ToolkitError {
     #[error("Replay version {replay_version:?} does not match loaded game version {game_version:?}")]
     ReplayVersionMismatch { game_version: String, replay_version: String },
 
+    #[error("Could not read replay {path:?}: {reason}")]
+    InvalidReplay { path: PathBuf, reason: String },
+
+    #[error("Replay has an unrecognized client version {0:?}")]
+    InvalidReplayVersion(String),
+
+    #[error("Replay has an invalid date {0:?}")]
+    InvalidReplayDate(String),
+
+    #[error("Could not read game index file {path:?}: {reason}")]
+    InvalidIdxFile { path: PathBuf, reason: String },
+
+    #[error("Could not load ship icon {0}")]
+    MissingShipIcon(String),
+
+    #[error("Could not read game text catalog {path:?}: {reason}")]
+    InvalidGameTextCatalog { path: PathBuf, reason: String },
+
     #[error("Background task completed")]
     BackgroundTaskCompleted,
 
+    #[error("Background task was cancelled")]
+    BackgroundTaskCancelled,
+
     #[error("A network error occurred while downloading an update: {0}")]
     UpdateHttpError(#[from] reqwest::Error),
 
+    #[error("Could not read the replay archive index: {0}")]
+    ReplayArchiveIndex(serde_json::Error),
+
+    #[error("Archived replay {0:?} could not be read")]
+    InvalidArchivedReplay(String),
+
+    #[error("Unsupported DDS texture format {0}")]
+    UnsupportedDdsFormat(String),
+
+    #[error("Invalid DDS texture: {0}")]
+    InvalidDds(&'static str),
+
+    #[error("Could not encode image: {0}")]
+    ImageEncode(#[from] image::ImageError),
+
+    #[error("Invalid model geometry: {0}")]
+    InvalidGeometry(&'static str),
+
+    #[error("Model geometry uses an unsupported compressed encoding")]
+    UnsupportedGeometryEncoding,
+
+    #[error("Could not read the extraction manifest: {0}")]
+    ExtractionManifest(serde_json::Error),
+
+    #[error("Invalid GameParams cache entry {0}")]
+    GameParamsCache(String),
+
+    #[error("Could not read GameParams snapshot {0}")]
+    GameParamsSnapshot(String),
+
+    #[error("Invalid sound bank: {0}")]
+    InvalidSoundBank(&'static str),
+
+    #[error("Sounds using the {0} codec can't be converted to WAV")]
+    UnsupportedSoundCodec(String),
+
+    #[error("Invalid packed XML: {0}")]
+    InvalidPackedXml(&'static str),
+
+    #[error("Invalid localization catalog: {0}")]
+    InvalidCatalog(&'static str),
+
+    #[error("The {0} job stopped unexpectedly")]
+    UnpackerJobFailed(String),
+
+    #[error("Could not serialize settings: {0}")]
+    SettingsSerialization(serde_json::Error),
+
+    #[error("Could not read user data {0}")]
+    UserDataFile(String),
+
+    #[error("User data format version {0} is newer than this version of the toolkit supports")]
+    UnsupportedUserDataVersion(u32),
+
+    #[error("Could not access the player data store: {0}")]
+    PlayerStore(#[from] rusqlite::Error),
+
+    #[error("The player data store could not be opened")]
+    PlayerStoreUnavailable,
+
+    #[error("Player data store version {0} is newer than this version of the toolkit supports")]
+    UnsupportedPlayerStoreVersion(u32),
+
     #[error("Could not not read update ZipArchive")]
     ZipReadError(#[from] zip::result::ZipError),
 }
@@ -0,0 +1,520 @@
+//! Records what was extracted to an output directory so that later extractions
+//! can skip unchanged files and the output can be verified against the game.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::Crc;
+use parking_lot::Mutex;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+use wowsunpack::data::{idx::FileNode, pkg::PkgFileLoader};
+
+use crate::{dds, error::ToolkitError, packed_xml, unpacker_jobs::JobProgress};
+
+/// Name of the manifest written into the root of an extraction's output directory
+pub const MANIFEST_FILE_NAME: &str = ".wows_toolkit_manifest.json";
+
+/// Held while a manifest is read, merged and written back so that extractions
+/// running at the same time don't drop each other's entries
+static MANIFEST_LOCK: Mutex<()> = parking_lot::const_mutex(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path the file was written to, relative to the output directory
+    pub output: PathBuf,
+    pub unpacked_size: u64,
+    pub packed_size: u64,
+    /// Size of the written file. This differs from `unpacked_size` for converted textures.
+    pub written_size: u64,
+    /// CRC32 of the written file
+    pub crc32: u32,
+    /// Whether the file was converted (e.g. DDS to PNG) while extracting
+    #[serde(default)]
+    pub converted: bool,
+    /// CRC32 of the packed source as recorded in the game's idx files
+    #[serde(default)]
+    pub source_crc32: Option<u32>,
+    /// Conversions enabled when the file was extracted
+    #[serde(default)]
+    pub conversions: Option<ConversionOptions>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExtractionManifest {
+    /// Entries keyed by the file's path in the packed resources
+    files: BTreeMap<PathBuf, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractionReportKind {
+    Extracted,
+    Verified,
+}
+
+#[derive(Debug)]
+pub struct ExtractionReport {
+    pub kind: ExtractionReportKind,
+    pub output_dir: PathBuf,
+    /// Files written (extraction) or found to match the packed sources (verification)
+    pub ok: usize,
+    /// Files skipped because they were unchanged since the last extraction
+    pub skipped: usize,
+    /// Files which were extracted before but no longer exist in the game
+    pub stale: Vec<PathBuf>,
+    /// Files whose output no longer matches the packed source
+    pub modified: Vec<PathBuf>,
+    /// Files in the manifest which are missing from the output directory
+    pub missing: Vec<PathBuf>,
+    /// Files which could not be checked, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl ExtractionReport {
+    pub fn new(kind: ExtractionReportKind, output_dir: &Path) -> Self {
+        ExtractionReport {
+            kind,
+            output_dir: output_dir.to_owned(),
+            ok: 0,
+            skipped: 0,
+            stale: Vec::new(),
+            modified: Vec::new(),
+            missing: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match self.kind {
+            ExtractionReportKind::Extracted => format!(
+                "Extracted {} files, skipped {} unchanged files, {} stale files",
+                self.ok,
+                self.skipped,
+                self.stale.len()
+            ),
+            ExtractionReportKind::Verified => format!(
+                "Verified {} files: {} modified, {} missing, {} stale, {} could not be checked",
+                self.ok + self.modified.len() + self.missing.len() + self.stale.len() + self.failed.len(),
+                self.modified.len(),
+                self.missing.len(),
+                self.stale.len(),
+                self.failed.len()
+            ),
+        }
+    }
+
+    pub fn has_problems(&self) -> bool {
+        !(self.stale.is_empty() && self.modified.is_empty() && self.missing.is_empty() && self.failed.is_empty())
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+
+    crc.sum()
+}
+
+/// Computes the CRC32 and size of everything written through it
+pub struct HashingWriter<W> {
+    inner: W,
+    crc: Crc,
+    len: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        HashingWriter { inner, crc: Crc::new(), len: 0 }
+    }
+
+    /// Returns the inner writer along with the CRC32 and size of the data written
+    pub fn finish(self) -> (W, u32, u64) {
+        (self.inner, self.crc.sum(), self.len)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc.update(&buf[..written]);
+        self.len += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Passes a file through to its output unless it starts with the packed XML
+/// magic, in which case it is kept in memory so that it can be decoded
+struct PackedXmlSniffer<'a, W> {
+    output: &'a mut W,
+    head: Vec<u8>,
+    is_packed: Option<bool>,
+}
+
+impl<'a, W: Write> PackedXmlSniffer<'a, W> {
+    const HEAD_LEN: usize = 4;
+
+    fn new(output: &'a mut W) -> Self {
+        PackedXmlSniffer {
+            output,
+            head: Vec::with_capacity(Self::HEAD_LEN),
+            is_packed: None,
+        }
+    }
+
+    /// Returns the file's contents if it is packed XML. Otherwise everything has been written to the output.
+    fn finish(self) -> io::Result<Option<Vec<u8>>> {
+        match self.is_packed {
+            Some(true) => Ok(Some(self.head)),
+            Some(false) => Ok(None),
+            None => {
+                // Too short to be packed XML
+                self.output.write_all(&self.head)?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for PackedXmlSniffer<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.is_packed {
+            Some(false) => self.output.write(buf),
+            Some(true) => {
+                self.head.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            None => {
+                let taken = (Self::HEAD_LEN - self.head.len()).min(buf.len());
+                self.head.extend_from_slice(&buf[..taken]);
+                if self.head.len() == Self::HEAD_LEN {
+                    let is_packed = packed_xml::is_packed_xml(&self.head);
+                    self.is_packed = Some(is_packed);
+                    if !is_packed {
+                        self.output.write_all(&self.head)?;
+                        self.head = Vec::new();
+                    }
+                }
+
+                Ok(taken)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Conversions applied to files while extracting them
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversionOptions {
+    pub dds_to_png: bool,
+    pub decode_packed_xml: bool,
+}
+
+impl ConversionOptions {
+    /// Every conversion. A file is only affected by the conversion for its type,
+    /// so this reproduces whatever was done to a file recorded as converted.
+    pub fn all() -> Self {
+        ConversionOptions {
+            dds_to_png: true,
+            decode_packed_xml: true,
+        }
+    }
+
+    /// Whether extracting `file_name` with `other` produces the same output as with these options
+    fn same_output_for(&self, other: &ConversionOptions, file_name: &str) -> bool {
+        if file_name.ends_with(".dds") {
+            self.dds_to_png == other.dds_to_png
+        } else {
+            self.decode_packed_xml == other.decode_packed_xml
+        }
+    }
+}
+
+/// Reads a file from the packed resources and produces the data which should be
+/// written for it, converting it if requested. Returns the output file name
+/// along with the data and whether it was converted.
+pub fn extracted_contents(file: &FileNode, pkg_loader: &PkgFileLoader, options: ConversionOptions) -> Result<(String, Vec<u8>, bool), ToolkitError> {
+    let mut data = Vec::with_capacity(file.file_info().map(|info| info.unpacked_size as usize).unwrap_or_default());
+    file.read_file(pkg_loader, &mut data)?;
+
+    if options.decode_packed_xml && packed_xml::is_packed_xml(&data) {
+        match packed_xml::decode(&data, file.filename()) {
+            Ok(xml) => return Ok((file.filename().to_owned(), xml.into_bytes(), true)),
+            Err(e) => {
+                // Keep the original file if we can't decode it
+                debug!("failed to decode packed XML {}: {:?}", file.filename(), e);
+            }
+        }
+    }
+
+    if options.dds_to_png && file.filename().ends_with(".dds") {
+        match dds::dds_to_png(&data) {
+            Ok(png) => return Ok((Path::new(file.filename()).with_extension("png").to_string_lossy().into_owned(), png, true)),
+            Err(e) => {
+                // Keep the original texture if we can't convert it
+                debug!("failed to convert {} to PNG: {:?}", file.filename(), e);
+            }
+        }
+    }
+
+    Ok((file.filename().to_owned(), data, false))
+}
+
+/// A file written by [extract_to_dir]
+pub struct ExtractedFile {
+    pub file_name: String,
+    pub written_size: u64,
+    pub crc32: u32,
+    pub converted: bool,
+}
+
+/// Writes a file from the packed resources into `dir`, converting it if requested.
+/// Files are streamed to disk unless a conversion needs the whole file in memory.
+pub fn extract_to_dir(file: &FileNode, pkg_loader: &PkgFileLoader, options: ConversionOptions, dir: &Path) -> Result<ExtractedFile, ToolkitError> {
+    if options.dds_to_png && file.filename().ends_with(".dds") {
+        let (file_name, data, converted) = extracted_contents(file, pkg_loader, options)?;
+        fs::write(dir.join(&file_name), &data)?;
+
+        return Ok(ExtractedFile {
+            file_name,
+            written_size: data.len() as u64,
+            crc32: crc32(&data),
+            converted,
+        });
+    }
+
+    let mut output = HashingWriter::new(BufWriter::new(File::create(dir.join(file.filename()))?));
+    let mut converted = false;
+    if options.decode_packed_xml {
+        let mut sniffer = PackedXmlSniffer::new(&mut output);
+        file.read_file(pkg_loader, &mut sniffer)?;
+        if let Some(packed) = sniffer.finish()? {
+            match packed_xml::decode(&packed, file.filename()) {
+                Ok(xml) => {
+                    output.write_all(xml.as_bytes())?;
+                    converted = true;
+                }
+                Err(e) => {
+                    // Keep the original file if we can't decode it
+                    debug!("failed to decode packed XML {}: {:?}", file.filename(), e);
+                    output.write_all(&packed)?;
+                }
+            }
+        }
+    } else {
+        file.read_file(pkg_loader, &mut output)?;
+    }
+
+    let (writer, crc32, written_size) = output.finish();
+    writer.into_inner().map_err(io::Error::from)?;
+
+    Ok(ExtractedFile {
+        file_name: file.filename().to_owned(),
+        written_size,
+        crc32,
+        converted,
+    })
+}
+
+fn source_sizes(file: &FileNode) -> (u64, u64) {
+    file.file_info().map(|info| (info.unpacked_size as u64, info.size as u64)).unwrap_or_default()
+}
+
+fn source_crc32(file: &FileNode) -> Option<u32> {
+    file.file_info().map(|info| info.crc32)
+}
+
+enum VerifyResult {
+    Ok,
+    Stale(PathBuf),
+    Missing(PathBuf),
+    Modified(PathBuf),
+    Failed(PathBuf, String),
+    Cancelled,
+}
+
+impl ManifestEntry {
+    pub fn new(file: &FileNode, output: PathBuf, extracted: &ExtractedFile, conversions: ConversionOptions) -> Self {
+        let (unpacked_size, packed_size) = source_sizes(file);
+        ManifestEntry {
+            output,
+            unpacked_size,
+            packed_size,
+            written_size: extracted.written_size,
+            crc32: extracted.crc32,
+            converted: extracted.converted,
+            source_crc32: source_crc32(file),
+            conversions: Some(conversions),
+        }
+    }
+}
+
+impl ExtractionManifest {
+    pub fn load(output_dir: &Path) -> Result<ExtractionManifest, ToolkitError> {
+        let manifest_path = output_dir.join(MANIFEST_FILE_NAME);
+        if !manifest_path.exists() {
+            return Ok(Default::default());
+        }
+
+        serde_json::from_reader(File::open(manifest_path)?).map_err(ToolkitError::ExtractionManifest)
+    }
+
+    /// Writes the manifest to a temporary file before moving it over the old
+    /// manifest so that an interrupted extraction never truncates it.
+    fn save(&self, output_dir: &Path) -> Result<(), ToolkitError> {
+        fs::create_dir_all(output_dir)?;
+
+        let manifest_path = output_dir.join(MANIFEST_FILE_NAME);
+        // Unique per process so that another toolkit instance never writes to the same file
+        let tmp_path = output_dir.join(format!("{}.{}.tmp", MANIFEST_FILE_NAME, std::process::id()));
+        let result = File::create(&tmp_path)
+            .map_err(ToolkitError::from)
+            .and_then(|mut file| {
+                serde_json::to_writer_pretty(&mut file, self).map_err(ToolkitError::ExtractionManifest)?;
+                file.sync_all()?;
+                Ok(())
+            })
+            .and_then(|_| fs::rename(&tmp_path, manifest_path).map_err(ToolkitError::from));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+
+        result
+    }
+
+    /// Adds `entries` to the manifest currently on disk and saves it. Entries
+    /// written by other extractions since this one loaded the manifest are kept.
+    /// Returns the merged manifest.
+    pub fn update(output_dir: &Path, entries: Vec<(PathBuf, ManifestEntry)>) -> Result<ExtractionManifest, ToolkitError> {
+        let _lock = MANIFEST_LOCK.lock();
+
+        let mut manifest = Self::load(output_dir).unwrap_or_else(|e| {
+            error!("failed to load extraction manifest, replacing it: {:?}", e);
+            Default::default()
+        });
+        manifest.files.extend(entries);
+        manifest.save(output_dir)?;
+
+        Ok(manifest)
+    }
+
+    /// Whether `file` was extracted before with the same conversions, has not
+    /// changed in the game since, and its output is still on disk. Entries written
+    /// before the source CRC and conversions were recorded are never unchanged.
+    pub fn is_unchanged(&self, output_dir: &Path, source: &Path, file: &FileNode, conversions: ConversionOptions) -> bool {
+        let Some(entry) = self.files.get(source) else {
+            return false;
+        };
+        let (Some(entry_crc32), Some(entry_conversions)) = (entry.source_crc32, entry.conversions) else {
+            return false;
+        };
+
+        let (unpacked_size, packed_size) = source_sizes(file);
+        let output_size = fs::metadata(output_dir.join(&entry.output)).map(|meta| meta.len()).ok();
+
+        entry.unpacked_size == unpacked_size
+            && entry.packed_size == packed_size
+            && source_crc32(file) == Some(entry_crc32)
+            && entry_conversions.same_output_for(&conversions, file.filename())
+            && output_size == Some(entry.written_size)
+    }
+
+    /// Files which were extracted before but no longer exist in `file_tree`
+    pub fn stale_entries(&self, file_tree: &FileNode) -> Vec<PathBuf> {
+        self.files
+            .keys()
+            .filter(|source| file_tree.find(&source.to_string_lossy()).is_err())
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+fn file_crc32(path: &Path) -> Result<u32, ToolkitError> {
+    let mut hasher = HashingWriter::new(io::sink());
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(hasher.finish().1)
+}
+
+/// CRC32 of what extracting `file` produces. Only converted files are read into memory.
+fn expected_crc32(file: &FileNode, pkg_loader: &PkgFileLoader, converted: bool) -> Result<u32, ToolkitError> {
+    if converted {
+        let (_name, expected, _converted) = extracted_contents(file, pkg_loader, ConversionOptions::all())?;
+        return Ok(crc32(&expected));
+    }
+
+    let mut hasher = HashingWriter::new(io::sink());
+    file.read_file(pkg_loader, &mut hasher)?;
+
+    Ok(hasher.finish().1)
+}
+
+/// Re-checks every file recorded in an output directory's manifest against the packed sources.
+/// Files which can't be read are reported rather than stopping the verification.
+pub fn verify_extraction(output_dir: &Path, file_tree: &FileNode, pkg_loader: &PkgFileLoader, progress: &JobProgress) -> Result<ExtractionReport, ToolkitError> {
+    let manifest = ExtractionManifest::load(output_dir)?;
+
+    let bytes_total = manifest.files.values().map(|entry| entry.written_size).sum();
+    progress.set_totals(manifest.len(), bytes_total);
+
+    let results: Vec<VerifyResult> = manifest
+        .files
+        .par_iter()
+        .map(|(source, entry)| {
+            if progress.is_cancelled() {
+                return VerifyResult::Cancelled;
+            }
+
+            let output_path = output_dir.join(&entry.output);
+            progress.file_started(output_path.to_string_lossy().into_owned());
+
+            let result = match file_tree.find(&source.to_string_lossy()) {
+                Err(_) => VerifyResult::Stale(source.clone()),
+                Ok(_) if !output_path.exists() => VerifyResult::Missing(entry.output.clone()),
+                Ok(file) => match (file_crc32(&output_path), expected_crc32(&file, pkg_loader, entry.converted)) {
+                    (Ok(on_disk), Ok(expected)) if on_disk == expected => VerifyResult::Ok,
+                    (Ok(_), Ok(_)) => VerifyResult::Modified(entry.output.clone()),
+                    (Err(e), _) | (_, Err(e)) => VerifyResult::Failed(entry.output.clone(), e.to_string()),
+                },
+            };
+
+            progress.file_finished(entry.written_size);
+
+            result
+        })
+        .collect();
+
+    let mut report = ExtractionReport::new(ExtractionReportKind::Verified, output_dir);
+    for result in results {
+        match result {
+            VerifyResult::Ok => report.ok += 1,
+            VerifyResult::Stale(path) => report.stale.push(path),
+            VerifyResult::Missing(path) => report.missing.push(path),
+            VerifyResult::Modified(path) => report.modified.push(path),
+            VerifyResult::Failed(path, reason) => report.failed.push((path, reason)),
+            VerifyResult::Cancelled => {}
+        }
+    }
+
+    Ok(report)
+}
@@ -1,17 +1,14 @@
 use std::{
-    collections::HashSet,
     fs::{self, File},
     path::{Path, PathBuf},
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        mpsc, Arc,
-    },
+    sync::{atomic::AtomicBool, Arc},
 };
 
 use egui::{mutex::Mutex, CollapsingHeader, Label, Response, Sense, Ui};
 use egui_extras::{Size, StripBuilder};
 use egui_phosphor::regular as icons;
-use tracing::debug;
+use rayon::prelude::*;
+use tracing::{debug, error};
 use wowsunpack::{
     data::{idx::FileNode, pkg::PkgFileLoader},
     game_params::{
@@ -21,20 +18,26 @@ use wowsunpack::{
 };
 
 use crate::{
-    app::ToolkitTabViewer,
-    plaintext_viewer::{self, FileType},
+    app::{TimedMessage, ToolkitTabViewer},
+    archive_output::{self, ArchiveFormat},
+    asset_gallery, dds,
+    error::ToolkitError,
+    extraction_manifest::{self, ExtractionManifest, ExtractionReport, ExtractionReportKind, ManifestEntry},
+    geometry, packed_xml,
+    plaintext_viewer::{self, BinarySource, FileType, HexViewer},
+    unpacker_jobs::{JobOutcome, UnpackerJob},
     wows_data,
 };
-pub static UNPACKER_STOP: AtomicBool = AtomicBool::new(false);
+pub(crate) const IMAGE_FILE_TYPES: [&str; 3] = [".jpg", ".png", ".svg"];
+const PLAINTEXT_FILE_TYPES: [&str; 3] = [".xml", ".json", ".txt"];
+pub(crate) const TEXTURE_FILE_TYPES: [&str; 1] = [".dds"];
 
-pub struct UnpackerProgress {
-    pub file_name: String,
-    pub progress: f32,
+enum ExtractResult {
+    Written(PathBuf, ManifestEntry),
+    Unchanged,
+    Cancelled,
 }
 
-const IMAGE_FILE_TYPES: [&str; 3] = [".jpg", ".png", ".svg"];
-const PLAINTEXT_FILE_TYPES: [&str; 3] = [".xml", ".json", ".txt"];
-
 #[derive(Eq, PartialEq)]
 enum GameParamsFormat {
     Json,
@@ -48,46 +51,122 @@ impl ToolkitTabViewer<'_> {
         self.tab_state.world_of_warships_data.as_ref().map(|wows_data| wows_data.read().pkg_loader.clone())
     }
 
-    fn add_view_file_menu(&self, file_label: &Response, node: &FileNode) {
+    pub(crate) fn add_view_file_menu(&self, file_label: &Response, node: &FileNode) {
         let is_plaintext_file = PLAINTEXT_FILE_TYPES.iter().find(|extension| node.filename().ends_with(**extension));
         let is_image_file = IMAGE_FILE_TYPES.iter().find(|extension| node.filename().ends_with(**extension));
+        let is_texture_file = TEXTURE_FILE_TYPES.iter().any(|extension| node.filename().ends_with(*extension));
+
+        file_label.context_menu(|ui| {
+            if let Some(pkg_loader) = self.pkg_loader() {
+                if ui.button("View Contents").clicked() {
+                    let file_size = node.file_info().unwrap().unpacked_size as u64;
+                    let is_large_binary =
+                        is_plaintext_file.is_none() && is_image_file.is_none() && !is_texture_file && file_size > plaintext_viewer::LARGE_BINARY_FILE_THRESHOLD;
+
+                    let file_type = if is_large_binary {
+                        // Filled in once the file has been copied to disk below
+                        Some(FileType::Loading)
+                    } else {
+                        let mut file_contents: Vec<u8> = Vec::with_capacity(file_size as usize);
 
-        if is_plaintext_file.is_some() || is_image_file.is_some() {
-            file_label.context_menu(|ui| {
-                if let Some(pkg_loader) = self.pkg_loader() {
-                    if ui.button("View Contents").clicked() {
-                        let mut file_contents: Vec<u8> = Vec::with_capacity(node.file_info().unwrap().unpacked_size as usize);
-
-                        node.read_file(&pkg_loader, &mut file_contents).expect("failed to read file");
-
-                        let file_type = match (is_plaintext_file, is_image_file) {
-                            (Some(ext), None) => String::from_utf8(file_contents)
-                                .ok()
-                                .map(|contents| FileType::PlainTextFile { ext: ext.to_string(), contents }),
-                            (None, Some(ext)) => Some(FileType::Image {
-                                ext: ext.to_string(),
-                                contents: file_contents,
-                            }),
-                            (None, None) => None,
-                            _ => unreachable!("this should be impossible"),
-                        };
+                        if let Err(e) = node.read_file(&pkg_loader, &mut file_contents) {
+                            error!("failed to read {}: {:?}", node.filename(), e);
+                            *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Failed to read {}: {}", icons::WARNING, node.filename(), e)));
+                            None
+                        } else if is_texture_file {
+                            match dds::dds_to_png(&file_contents) {
+                                Ok(png) => Some(FileType::Image {
+                                    ext: ".png".to_string(),
+                                    contents: png,
+                                }),
+                                Err(e) => {
+                                    debug!("failed to decode texture {}: {:?}", node.filename(), e);
+                                    Some(FileType::Binary(HexViewer::new(BinarySource::Memory(file_contents))))
+                                }
+                            }
+                        } else {
+                            match (is_plaintext_file, is_image_file) {
+                                // Packed XML may use any extension (.xml, .visual, .model, ...)
+                                _ if packed_xml::is_packed_xml(&file_contents) => match packed_xml::decode(&file_contents, node.filename()) {
+                                    Ok(contents) => Some(FileType::PlainTextFile {
+                                        ext: ".xml".to_string(),
+                                        contents,
+                                    }),
+                                    Err(e) => {
+                                        debug!("failed to decode packed XML {}: {:?}", node.filename(), e);
+                                        Some(FileType::Binary(HexViewer::new(BinarySource::Memory(file_contents))))
+                                    }
+                                },
+                                (Some(ext), None) => match String::from_utf8(file_contents) {
+                                    Ok(contents) => Some(FileType::PlainTextFile { ext: ext.to_string(), contents }),
+                                    // Not actually plaintext, fall back to the hex view
+                                    Err(e) => Some(FileType::Binary(HexViewer::new(BinarySource::Memory(e.into_bytes())))),
+                                },
+                                (None, Some(ext)) => Some(FileType::Image {
+                                    ext: ext.to_string(),
+                                    contents: file_contents,
+                                }),
+                                (None, None) => Some(FileType::Binary(HexViewer::new(BinarySource::Memory(file_contents)))),
+                                _ => unreachable!("this should be impossible"),
+                            }
+                        }
+                    };
 
-                        if let Some(file_type) = file_type {
-                            let viewer = plaintext_viewer::PlaintextFileViewer {
-                                title: Arc::new(Path::new("res").join(node.path().unwrap()).to_str().unwrap().to_string()),
-                                file_info: Arc::new(Mutex::new(file_type)),
-                                open: Arc::new(AtomicBool::new(true)),
-                            };
+                    if let Some(file_type) = file_type {
+                        let viewer = plaintext_viewer::PlaintextFileViewer {
+                            title: Arc::new(Path::new("res").join(node.path().unwrap()).to_str().unwrap().to_string()),
+                            file_info: Arc::new(Mutex::new(file_type)),
+                            open: Arc::new(AtomicBool::new(true)),
+                        };
 
-                            self.tab_state.file_viewer.lock().push(viewer);
+                        if is_large_binary {
+                            read_large_binary(node.clone(), Arc::clone(&pkg_loader), Arc::clone(&viewer.file_info));
                         }
 
-                        ui.close_menu();
+                        self.tab_state.file_viewer.lock().push(viewer);
                     }
+
+                    ui.close_menu();
+                }
+
+                if node.filename().ends_with(".geometry") && ui.button("Export Model as OBJ...").clicked() {
+                    let default_name = Path::new(node.filename()).with_extension("obj");
+                    if let Some(obj_path) = rfd::FileDialog::new().set_file_name(default_name.to_string_lossy()).save_file() {
+                        let message = match self.export_model(&pkg_loader, node, &obj_path) {
+                            Ok(path) => format!("{} Exported model to {}", icons::CHECK_CIRCLE, path.display()),
+                            Err(e) => format!("{} Failed to export model: {}", icons::WARNING, e),
+                        };
+                        *self.tab_state.timed_message.write() = Some(TimedMessage::new(message));
+                    }
+
+                    ui.close_menu();
+                }
+            }
+        });
+    }
+
+    /// Exports a `.geometry` file, using the `.visual` file next to it for materials if present
+    fn export_model(&self, pkg_loader: &PkgFileLoader, node: &FileNode, obj_path: &Path) -> Result<PathBuf, ToolkitError> {
+        let mut geometry_data = Vec::with_capacity(node.file_info().unwrap().unpacked_size as usize);
+        node.read_file(pkg_loader, &mut geometry_data)?;
+
+        let visual_name = Path::new(node.filename()).with_extension("visual").to_string_lossy().into_owned();
+        let visual_xml = node
+            .parent()
+            .and_then(|parent| parent.children().values().find(|child| child.filename() == visual_name).cloned())
+            .and_then(|visual_node| {
+                let mut visual_data = Vec::new();
+                visual_node.read_file(pkg_loader, &mut visual_data).ok()?;
+                if packed_xml::is_packed_xml(&visual_data) {
+                    packed_xml::decode(&visual_data, &visual_name).ok()
+                } else {
+                    String::from_utf8(visual_data).ok()
                 }
             });
-        }
+
+        geometry::export_obj(&geometry_data, visual_xml.as_deref(), obj_path)
     }
+
     /// Builds a resource tree node from a [FileNode]
     fn build_resource_tree_node(&self, ui: &mut egui::Ui, file_tree: &FileNode) {
         let header = CollapsingHeader::new(if file_tree.is_root() { "res" } else { file_tree.filename() })
@@ -138,54 +217,141 @@ impl ToolkitTabViewer<'_> {
         });
     }
 
-    fn extract_files(&mut self, output_dir: &Path, items_to_unpack: &[FileNode]) {
-        if let Some(pkg_loader) = self.pkg_loader() {
-            let (tx, rx) = mpsc::channel();
-
-            self.tab_state.unpacker_progress = Some(rx);
-            UNPACKER_STOP.store(false, Ordering::Relaxed);
-
-            if !items_to_unpack.is_empty() {
-                let output_dir = output_dir.to_owned();
-                let mut file_queue = items_to_unpack.to_vec();
-                let _unpacker_thread = Some(std::thread::spawn(move || {
-                    let mut files_to_extract: HashSet<FileNode> = HashSet::default();
-                    let mut folders_created: HashSet<PathBuf> = HashSet::default();
-                    while let Some(file) = file_queue.pop() {
-                        if file.is_file() {
-                            files_to_extract.insert(file);
-                        } else {
-                            for child in file.children().values() {
-                                file_queue.push(child.clone());
-                            }
-                        }
+    pub(crate) fn extract_files(&mut self, output_dir: &Path, items_to_unpack: &[FileNode]) {
+        if items_to_unpack.is_empty() {
+            return;
+        }
+        let Some(pkg_loader) = self.pkg_loader() else {
+            return;
+        };
+        let Some(file_tree) = self.tab_state.world_of_warships_data.as_ref().map(|wows_data| wows_data.read().file_tree.clone()) else {
+            return;
+        };
+
+        let conversions = self.tab_state.conversion_options();
+        let output_dir = output_dir.to_owned();
+        let items_to_unpack = items_to_unpack.to_vec();
+
+        let job = UnpackerJob::spawn(format!("Extracting to {}", output_dir.display()), move |progress| {
+            let files_to_extract = archive_output::collect_files(&items_to_unpack);
+
+            let manifest = ExtractionManifest::load(&output_dir).unwrap_or_else(|e| {
+                error!("failed to load extraction manifest, extracting everything: {:?}", e);
+                Default::default()
+            });
+            let mut report = ExtractionReport::new(ExtractionReportKind::Extracted, &output_dir);
+
+            let bytes_total = files_to_extract
+                .iter()
+                .map(|file| file.file_info().map(|info| info.unpacked_size as u64).unwrap_or_default())
+                .sum();
+            progress.set_totals(files_to_extract.len(), bytes_total);
+
+            let results: Vec<Result<ExtractResult, ToolkitError>> = files_to_extract
+                .par_iter()
+                .map(|file| {
+                    if progress.is_cancelled() {
+                        return Ok(ExtractResult::Cancelled);
                     }
-                    let file_count = files_to_extract.len();
 
-                    for (files_written, file) in files_to_extract.iter().enumerate() {
-                        if UNPACKER_STOP.load(Ordering::Relaxed) {
-                            break;
-                        }
+                    let unpacked_size = file.file_info().map(|info| info.unpacked_size as u64).unwrap_or_default();
+                    let source_path = file.path()?;
+                    let parent_path = file.parent().map(|parent| parent.path()).transpose()?.unwrap_or_default();
+                    let path = output_dir.join(&parent_path);
+                    progress.file_started(path.join(file.filename()).to_string_lossy().into_owned());
 
-                        let path = output_dir.join(file.parent().unwrap().path().unwrap());
-                        let file_path = path.join(file.filename());
-                        tx.send(UnpackerProgress {
-                            file_name: file_path.to_string_lossy().into(),
-                            progress: (files_written as f32) / (file_count as f32),
-                        })
-                        .unwrap();
-                        if !folders_created.contains(&path) {
-                            fs::create_dir_all(&path).expect("failed to create folder");
-                            folders_created.insert(path.clone());
-                        }
+                    if manifest.is_unchanged(&output_dir, &source_path, file, conversions) {
+                        progress.file_finished(unpacked_size);
+                        return Ok(ExtractResult::Unchanged);
+                    }
+
+                    fs::create_dir_all(&path)?;
+
+                    let extracted = extraction_manifest::extract_to_dir(file, &pkg_loader, conversions, &path)?;
+
+                    progress.file_finished(unpacked_size);
 
-                        let mut out_file = File::create(file_path).expect("failed to create output file");
+                    Ok(ExtractResult::Written(
+                        source_path,
+                        ManifestEntry::new(file, parent_path.join(&extracted.file_name), &extracted, conversions),
+                    ))
+                })
+                .collect();
 
-                        file.read_file(&pkg_loader, &mut out_file).expect("Failed to read file");
+            let mut written = Vec::new();
+            let mut first_error = None;
+            for result in results {
+                match result {
+                    Ok(ExtractResult::Written(source_path, entry)) => {
+                        written.push((source_path, entry));
+                        report.ok += 1;
                     }
-                }));
+                    Ok(ExtractResult::Unchanged) => report.skipped += 1,
+                    Ok(ExtractResult::Cancelled) => {}
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
             }
+
+            // Save whatever was written before any failure so that it can be skipped next time
+            match ExtractionManifest::update(&output_dir, written) {
+                Ok(manifest) => report.stale = manifest.stale_entries(&file_tree),
+                Err(e) => {
+                    error!("failed to save extraction manifest: {:?}", e);
+                    report.stale = manifest.stale_entries(&file_tree);
+                }
+            }
+
+            match first_error {
+                Some(e) => Err(e),
+                None => Ok(JobOutcome::Report(report)),
+            }
+        });
+
+        self.tab_state.unpacker_jobs.push(job);
+    }
+
+    /// Re-checks a previous extraction to `output_dir` against the packed resources
+    fn verify_extraction(&mut self, output_dir: &Path) {
+        let Some(pkg_loader) = self.pkg_loader() else {
+            return;
+        };
+        let Some(file_tree) = self.tab_state.world_of_warships_data.as_ref().map(|wows_data| wows_data.read().file_tree.clone()) else {
+            return;
+        };
+
+        let output_dir = output_dir.to_owned();
+        let job = UnpackerJob::spawn(format!("Verifying {}", output_dir.display()), move |progress| {
+            extraction_manifest::verify_extraction(&output_dir, &file_tree, &pkg_loader, progress).map(JobOutcome::Report)
+        });
+
+        self.tab_state.unpacker_jobs.push(job);
+    }
+
+    /// Writes the selected files into a single archive rather than loose files
+    fn extract_to_archive(&mut self, archive_path: PathBuf, format: ArchiveFormat) {
+        let items_to_unpack = self.tab_state.items_to_extract.lock().clone();
+        if items_to_unpack.is_empty() {
+            return;
         }
+        let Some(pkg_loader) = self.pkg_loader() else {
+            return;
+        };
+
+        let conversions = self.tab_state.conversion_options();
+        let job = UnpackerJob::spawn(format!("Archiving to {}", archive_path.display()), move |progress| {
+            let files = archive_output::collect_files(&items_to_unpack);
+            let written = archive_output::write_archive(&archive_path, format, files, &pkg_loader, conversions, progress)?;
+
+            if progress.is_cancelled() {
+                Ok(JobOutcome::Message(format!("Cancelled writing {}", archive_path.display())))
+            } else {
+                Ok(JobOutcome::Message(format!("Wrote {} files to {}", written, archive_path.display())))
+            }
+        });
+
+        self.tab_state.unpacker_jobs.push(job);
     }
 
     fn extract_files_clicked(&mut self, _ui: &mut Ui) {
@@ -196,61 +362,59 @@ impl ToolkitTabViewer<'_> {
     }
 
     fn dump_game_params(&mut self, file_path: PathBuf, format: GameParamsFormat) {
-        if let Some(pkg_loader) = self.pkg_loader() {
-            let (tx, rx) = mpsc::channel();
-
-            self.tab_state.unpacker_progress = Some(rx);
-            UNPACKER_STOP.store(false, Ordering::Relaxed);
-
-            // Find GameParams.json
-            if let Some(wows_data) = self.tab_state.world_of_warships_data.as_ref() {
-                let wows_data = wows_data.read();
-                let metadata_provider = { wows_data.game_metadata.clone() };
-
-                if let Ok(game_params_file) = wows_data.file_tree.find("content/GameParams.data") {
-                    let mut game_params_data: Vec<u8> = Vec::with_capacity(game_params_file.file_info().unwrap().unpacked_size as usize);
-
-                    game_params_file.read_file(&pkg_loader, &mut game_params_data).expect("failed to read GameParams");
-                    let _unpacker_thread = Some(std::thread::spawn(move || {
-                        tx.send(UnpackerProgress {
-                            file_name: file_path.to_string_lossy().into(),
-                            progress: 0.0,
-                        })
-                        .unwrap();
-
-                        let pickle = game_params_to_pickle(game_params_data).expect("failed to deserialize GameParams");
-
-                        let mut file = File::create(&file_path).expect("failed to create GameParams.json file");
-                        match format {
-                            GameParamsFormat::Json => {
-                                let json = pickle_to_json(pickle);
-                                serde_json::to_writer_pretty(&mut file, &json).expect("failed to write JSON data");
-                            }
-                            GameParamsFormat::Cbor => {
-                                let cbor = pickle_to_cbor(pickle);
-                                serde_cbor::to_writer(file, &cbor).expect("failed to write CBOR data");
-                            }
-                            GameParamsFormat::MinimalJson => {
-                                if let Some(metadata_provider) = metadata_provider {
-                                    serde_json::to_writer(file, &metadata_provider.params()).expect("failed to write CBOR data");
-                                }
-                            }
-                            GameParamsFormat::MinimalCbor => {
-                                if let Some(metadata_provider) = metadata_provider {
-                                    serde_cbor::to_writer(file, &metadata_provider.params()).expect("failed to write CBOR data");
-                                }
-                            }
-                        }
-
-                        tx.send(UnpackerProgress {
-                            file_name: file_path.to_string_lossy().into(),
-                            progress: 1.0,
-                        })
-                        .unwrap();
-                    }));
+        let Some(pkg_loader) = self.pkg_loader() else {
+            return;
+        };
+        let Some(wows_data) = self.tab_state.world_of_warships_data.as_ref() else {
+            return;
+        };
+
+        let (game_params_file, metadata_provider) = {
+            let wows_data = wows_data.read();
+            (wows_data.file_tree.find("content/GameParams.data"), wows_data.game_metadata.clone())
+        };
+        let Ok(game_params_file) = game_params_file else {
+            return;
+        };
+
+        let job = UnpackerJob::spawn(format!("Writing {}", file_path.display()), move |progress| {
+            let unpacked_size = game_params_file.file_info().map(|info| info.unpacked_size as u64).unwrap_or_default();
+            progress.set_totals(1, unpacked_size);
+            progress.file_started(file_path.to_string_lossy().into_owned());
+
+            let mut game_params_data: Vec<u8> = Vec::with_capacity(unpacked_size as usize);
+            game_params_file.read_file(&pkg_loader, &mut game_params_data)?;
+
+            let pickle = game_params_to_pickle(game_params_data)?;
+
+            let mut file = File::create(&file_path)?;
+            match format {
+                GameParamsFormat::Json => {
+                    let json = pickle_to_json(pickle);
+                    serde_json::to_writer_pretty(&mut file, &json).map_err(std::io::Error::from)?;
+                }
+                GameParamsFormat::Cbor => {
+                    let cbor = pickle_to_cbor(pickle);
+                    serde_cbor::to_writer(file, &cbor).map_err(std::io::Error::other)?;
+                }
+                GameParamsFormat::MinimalJson => {
+                    if let Some(metadata_provider) = metadata_provider {
+                        serde_json::to_writer(file, &metadata_provider.params()).map_err(std::io::Error::from)?;
+                    }
+                }
+                GameParamsFormat::MinimalCbor => {
+                    if let Some(metadata_provider) = metadata_provider {
+                        serde_cbor::to_writer(file, &metadata_provider.params()).map_err(std::io::Error::other)?;
+                    }
                 }
             }
-        }
+
+            progress.file_finished(unpacked_size);
+
+            Ok(JobOutcome::Message(format!("Wrote {}", file_path.display())))
+        });
+
+        self.tab_state.unpacker_jobs.push(job);
     }
 
     /// Builds the file unpacker tab
@@ -287,42 +451,82 @@ impl ToolkitTabViewer<'_> {
 
                     self.tab_state.filtered_file_list = filter_list.map(Arc::new);
                     self.tab_state.used_filter = Some(self.tab_state.filter.clone());
+                    self.tab_state.asset_gallery.filter_changed();
                 }
 
                 let filter_list = self.tab_state.filtered_file_list.clone();
 
-                StripBuilder::new(ui).size(Size::exact(25.0)).size(Size::remainder()).vertical(|mut strip| {
-                    strip.strip(|builder| {
-                        builder.size(Size::remainder()).size(Size::exact(50.0)).horizontal(|mut strip| {
-                            strip.cell(|ui| {
-                                ui.add(egui::TextEdit::singleline(&mut self.tab_state.filter).hint_text("Filter"));
-                            });
-                            strip.cell(|ui| {
-                                if let Some(filter_list) = &filter_list {
-                                    if ui.button("Add All").clicked() {
-                                        let mut items_to_extract = self.tab_state.items_to_extract.lock();
-                                        for file in filter_list.iter() {
-                                            items_to_extract.push(file.1.clone());
+                StripBuilder::new(ui)
+                    .size(Size::exact(25.0))
+                    .size(Size::exact(25.0))
+                    .size(Size::remainder())
+                    .vertical(|mut strip| {
+                        strip.strip(|builder| {
+                            builder
+                                .size(Size::remainder())
+                                .size(Size::exact(30.0))
+                                .size(Size::exact(30.0))
+                                .size(Size::exact(50.0))
+                                .horizontal(|mut strip| {
+                                    strip.cell(|ui| {
+                                        ui.add(egui::TextEdit::singleline(&mut self.tab_state.filter).hint_text("Filter"));
+                                    });
+                                    strip.cell(|ui| {
+                                        self.build_saved_filters_menu(ui);
+                                    });
+                                    strip.cell(|ui| {
+                                        let has_images = filter_list
+                                            .as_ref()
+                                            .map(|filter_list| filter_list.iter().any(|(path, _)| asset_gallery::is_gallery_file(path)))
+                                            .unwrap_or(false);
+                                        if ui
+                                            .add_enabled(has_images, egui::SelectableLabel::new(self.tab_state.asset_gallery.enabled, icons::IMAGES))
+                                            .on_hover_text("Show matching images as thumbnails")
+                                            .clicked()
+                                        {
+                                            self.tab_state.asset_gallery.enabled = !self.tab_state.asset_gallery.enabled;
                                         }
-                                    }
-                                }
-                            });
+                                    });
+                                    strip.cell(|ui| {
+                                        if let Some(filter_list) = &filter_list {
+                                            if ui.button("Add All").clicked() {
+                                                let mut items_to_extract = self.tab_state.items_to_extract.lock();
+                                                for file in filter_list.iter() {
+                                                    items_to_extract.push(file.1.clone());
+                                                }
+                                            }
+                                        }
+                                    });
+                                });
                         });
-                    });
-                    strip.cell(|ui| {
-                        egui::ScrollArea::both().id_source("file_tree_scroll_area").show(ui, |ui| {
-                            if let Some(wows_data) = self.tab_state.world_of_warships_data.as_ref() {
-                                let wows_data = wows_data.read();
-                                let file_tree = &wows_data.file_tree;
-                                if let Some(filtered_files) = &filter_list {
-                                    self.build_file_list_from_array(ui, filtered_files.iter());
-                                } else {
-                                    self.build_resource_tree_node(ui, file_tree);
+                        strip.cell(|ui| {
+                            self.build_content_search_bar(ui);
+                        });
+                        strip.cell(|ui| {
+                            if self.build_content_search_results(ui) {
+                                return;
+                            }
+
+                            if let Some(filtered_files) = filter_list.as_ref().filter(|_| self.tab_state.asset_gallery.enabled) {
+                                if filtered_files.iter().any(|(path, _)| asset_gallery::is_gallery_file(path)) {
+                                    self.build_asset_gallery(ui, filtered_files);
+                                    return;
                                 }
                             }
+
+                            egui::ScrollArea::both().id_source("file_tree_scroll_area").show(ui, |ui| {
+                                if let Some(wows_data) = self.tab_state.world_of_warships_data.as_ref() {
+                                    let wows_data = wows_data.read();
+                                    let file_tree = &wows_data.file_tree;
+                                    if let Some(filtered_files) = &filter_list {
+                                        self.build_file_list_from_array(ui, filtered_files.iter());
+                                    } else {
+                                        self.build_resource_tree_node(ui, file_tree);
+                                    }
+                                }
+                            });
                         });
                     });
-                });
             });
         });
         egui::CentralPanel::default().show_inside(ui, |ui| {
@@ -350,6 +554,23 @@ impl ToolkitTabViewer<'_> {
                             if let Some(remove_idx) = remove_idx {
                                 items.remove(remove_idx);
                             }
+                            drop(items);
+
+                            if let Some(report) = self.tab_state.last_extraction_report.as_ref().filter(|report| report.has_problems()) {
+                                ui.separator();
+                                CollapsingHeader::new(format!("{} {}", icons::WARNING, report.summary()))
+                                    .id_source("extraction_report")
+                                    .show(ui, |ui| {
+                                        for (label, paths) in [("Stale", &report.stale), ("Modified", &report.modified), ("Missing", &report.missing)] {
+                                            for path in paths {
+                                                ui.label(format!("{}: {}", label, report.output_dir.join(path).to_string_lossy()));
+                                            }
+                                        }
+                                        for (path, reason) in &report.failed {
+                                            ui.label(format!("Could not check {}: {}", report.output_dir.join(path).to_string_lossy(), reason));
+                                        }
+                                    });
+                            }
                         });
                     });
                 });
@@ -359,6 +580,9 @@ impl ToolkitTabViewer<'_> {
                         .size(Size::remainder())
                         .size(Size::exact(60.0))
                         .size(Size::exact(60.0))
+                        .size(Size::exact(80.0))
+                        .size(Size::exact(60.0))
+                        .size(Size::exact(90.0))
                         .size(Size::exact(150.0))
                         .size(Size::exact(150.0))
                         .horizontal(|mut strip| {
@@ -381,6 +605,39 @@ impl ToolkitTabViewer<'_> {
                                     self.extract_files_clicked(ui);
                                 }
                             });
+                            strip.cell(|ui| {
+                                ui.menu_button(format!("{} Archive", icons::FILE_ZIP), |ui| {
+                                    for format in [ArchiveFormat::Zip, ArchiveFormat::TarGz] {
+                                        if ui.small_button(format!("As .{}", format.extension())).clicked() {
+                                            let dialog = rfd::FileDialog::new().set_file_name(format.default_file_name());
+                                            if let Some(path) = dialog.save_file() {
+                                                self.extract_to_archive(path, format);
+                                            }
+                                            ui.close_menu();
+                                        }
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Write the selected files into a single archive, keeping their res/ paths");
+                            });
+                            strip.cell(|ui| {
+                                if ui
+                                    .button("Verify")
+                                    .on_hover_text("Check a previous extraction to the output path against the game files")
+                                    .clicked()
+                                {
+                                    let output_dir = Path::new(self.tab_state.output_dir.as_str()).join("res");
+                                    self.verify_extraction(&output_dir);
+                                }
+                            });
+                            strip.cell(|ui| {
+                                ui.vertical(|ui| {
+                                    ui.checkbox(&mut self.tab_state.convert_dds_to_png, "DDS to PNG")
+                                        .on_hover_text("Convert BC1/BC3/BC5/BC7 .dds textures to .png while extracting");
+                                    ui.checkbox(&mut self.tab_state.decode_packed_xml, "Decode XML")
+                                        .on_hover_text("Convert packed binary XML (.xml, .visual, .model, ...) to readable XML while extracting");
+                                });
+                            });
                             strip.cell(|ui| {
                                 ui.menu_button(format!("{} Dump GameParams", icons::FLOPPY_DISK), |ui| {
                                     if ui.small_button("As JSON").clicked() {
@@ -415,3 +672,26 @@ impl ToolkitTabViewer<'_> {
         });
     }
 }
+
+/// Copies a file too large to keep in memory to a temporary file on a background thread
+/// and replaces the viewer's loading placeholder with a hex view of it
+fn read_large_binary(node: FileNode, pkg_loader: Arc<PkgFileLoader>, file_info: Arc<Mutex<FileType>>) {
+    let _read_thread = std::thread::spawn(move || {
+        let result = tempfile::Builder::new()
+            .prefix("wows_toolkit_")
+            .tempfile()
+            .map_err(ToolkitError::from)
+            .and_then(|mut temp_file| {
+                node.read_file(&pkg_loader, &mut temp_file)?;
+                Ok(BinarySource::temp_file(temp_file)?)
+            });
+
+        *file_info.lock() = match result {
+            Ok(source) => FileType::Binary(HexViewer::new(source)),
+            Err(e) => {
+                error!("failed to read {}: {:?}", node.filename(), e);
+                FileType::Failed(format!("{} Failed to read {}: {}", icons::WARNING, node.filename(), e))
+            }
+        };
+    });
+}
@@ -1,13 +1,21 @@
+//! Caching of parsed GameParams, keyed by game build and toolkit version.
+//!
+//! Each cache entry is a bincode-serialized [CachedGameParams] stored in
+//! [game_params_cache_dir]. Entries are written to a temporary file and renamed
+//! into place so an interrupted write never leaves a truncated entry behind.
+
 use std::{
+    fs,
+    io::{self, BufReader, BufWriter, Write},
     path::{Path, PathBuf},
     sync::Arc,
-    time::Instant,
+    time::{Instant, SystemTime},
 };
 
 use itertools::Itertools;
 
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, warn};
 use wowsunpack::{
     data::{idx::FileNode, pkg::PkgFileLoader},
     game_params::{
@@ -18,13 +26,18 @@ use wowsunpack::{
 
 use crate::error::ToolkitError;
 
+const CACHE_DIR_NAME: &str = "game_params_cache";
+const CACHE_EXTENSION: &str = "bin";
+
 #[derive(Debug, Serialize, Deserialize)]
-struct CachedGameParams {
+pub(crate) struct CachedGameParams {
     app_version: String,
     game_version: usize,
-    params: Vec<Param>,
+    pub(crate) params: Vec<Param>,
 }
 
+/// Path of the single-entry cache used by versions before the cache directory.
+/// It is removed by a settings migration.
 pub fn game_params_bin_path() -> PathBuf {
     let old_cache_path = Path::new("game_params.bin");
     if let Some(storage_dir) = eframe::storage_dir(crate::APP_NAME) {
@@ -34,51 +47,142 @@ pub fn game_params_bin_path() -> PathBuf {
     }
 }
 
-pub fn load_game_params(file_tree: &FileNode, pkg_loader: &PkgFileLoader, game_version: usize) -> Result<GameMetadataProvider, ToolkitError> {
-    debug!("loading game params");
-    let old_cache_path = Path::new("game_params.bin");
+pub fn game_params_cache_dir() -> PathBuf {
+    if let Some(storage_dir) = eframe::storage_dir(crate::APP_NAME) {
+        storage_dir.join(CACHE_DIR_NAME)
+    } else {
+        PathBuf::from(CACHE_DIR_NAME)
+    }
+}
 
-    let cache_path = if let Some(storage_dir) = eframe::storage_dir(crate::APP_NAME) {
-        let new_cache_path = storage_dir.join(old_cache_path);
-        if !new_cache_path.exists() && old_cache_path.exists() {
-            // Doesn't matter if this fails, we want to only use the new cache path.
-            // The implication of failure here is that the user re-generates
-            // the cache.
-            let _ = std::fs::rename(old_cache_path, &new_cache_path);
-        }
+fn cache_entry_path(cache_dir: &Path, game_version: usize, app_version: &str) -> PathBuf {
+    cache_dir.join(format!("game_params_{}_{}.{}", game_version, app_version, CACHE_EXTENSION))
+}
 
-        new_cache_path
-    } else {
-        old_cache_path.to_path_buf()
+/// Reads a cache entry. Returns `None` if the entry doesn't exist, and an error
+/// if it exists but can't be used.
+fn read_cache_entry(path: &Path, game_version: usize, app_version: &str) -> Result<Option<Vec<Param>>, ToolkitError> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
     };
 
-    let start = Instant::now();
-    let params = cache_path
-        .exists()
-        .then(|| {
-            let cache_data = std::fs::File::open(&cache_path).ok()?;
-            let cached_params: CachedGameParams = bincode::deserialize_from(cache_data).ok()?;
-            if cached_params.game_version == game_version {
-                Some(cached_params.params)
-            } else {
-                None
-            }
+    let cached_params: CachedGameParams =
+        bincode::deserialize_from(BufReader::new(file)).map_err(|e| ToolkitError::GameParamsCache(format!("{}: {}", path.display(), e)))?;
+    if cached_params.game_version != game_version || cached_params.app_version != app_version {
+        return Err(ToolkitError::GameParamsCache(format!(
+            "{} is for build {} (toolkit {})",
+            path.display(),
+            cached_params.game_version,
+            cached_params.app_version
+        )));
+    }
+
+    Ok(Some(cached_params.params))
+}
+
+/// Writes a cache entry to a temporary file in the same directory and then moves it into place
+fn write_cache_entry(path: &Path, cached_params: &CachedGameParams) -> Result<(), ToolkitError> {
+    let temp_path = path.with_extension(format!("{}.{}.tmp", CACHE_EXTENSION, std::process::id()));
+
+    let result = (|| -> Result<(), ToolkitError> {
+        let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+        bincode::serialize_into(&mut writer, cached_params).map_err(|e| ToolkitError::GameParamsCache(format!("{}: {}", path.display(), e)))?;
+        let file = writer.into_inner().map_err(io::Error::from)?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+/// Removes the oldest cache entries until the cache fits in `max_size` bytes.
+/// The entry at `keep` is never removed.
+fn prune_cache(cache_dir: &Path, keep: &Path, max_size: u64) -> Result<(), ToolkitError> {
+    let entries: Vec<(PathBuf, u64, SystemTime)> = fs::read_dir(cache_dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path();
+            let metadata = entry.metadata().ok()?;
+            // Temporary files may belong to a write which is still in progress
+            let is_cache_file = path.extension().map(|ext| ext == CACHE_EXTENSION).unwrap_or(false);
+            (metadata.is_file() && is_cache_file).then(|| (path, metadata.len(), metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
         })
-        .flatten();
+        .sorted_by_key(|(_, _, modified)| std::cmp::Reverse(*modified))
+        .collect();
 
-    let metadata_provider = if let Some(params) = params {
-        GameMetadataProvider::from_params(params, file_tree, pkg_loader)?
+    let mut total_size = entries.iter().filter(|(path, _, _)| path == keep).map(|(_, size, _)| *size).sum::<u64>();
+    for (path, size, _) in entries.into_iter().filter(|(path, _, _)| path != keep) {
+        if total_size + size > max_size {
+            debug!("pruning game params cache entry {:?}", path);
+            // The entry may be in use by another instance, so keep pruning the rest
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("failed to prune game params cache entry {:?}: {}", path, e);
+            }
+        } else {
+            total_size += size;
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads GameParams from the cache for this build and toolkit version, or from
+/// the game files if there is no usable cache entry. Freshly built params are
+/// cached and older entries pruned so the cache stays under `max_cache_size` bytes.
+pub fn load_game_params(file_tree: &FileNode, pkg_loader: &PkgFileLoader, game_version: usize, max_cache_size: u64) -> Result<GameMetadataProvider, ToolkitError> {
+    debug!("loading game params");
+    let app_version = env!("CARGO_PKG_VERSION");
+
+    let cache_dir = game_params_cache_dir();
+    let cache_path = cache_entry_path(&cache_dir, game_version, app_version);
+
+    let start = Instant::now();
+    let params = match read_cache_entry(&cache_path, game_version, app_version) {
+        Ok(params) => params,
+        Err(e) => {
+            warn!("discarding unusable game params cache: {}", e);
+            let _ = fs::remove_file(&cache_path);
+            None
+        }
+    };
+
+    let cached_metadata_provider = params.and_then(|params| match GameMetadataProvider::from_params(params, file_tree, pkg_loader) {
+        Ok(metadata_provider) => Some(metadata_provider),
+        Err(e) => {
+            warn!("failed to load cached game params, rebuilding: {:?}", e);
+            let _ = fs::remove_file(&cache_path);
+            None
+        }
+    });
+
+    let metadata_provider = if let Some(metadata_provider) = cached_metadata_provider {
+        metadata_provider
     } else {
         let metadata_provider = GameMetadataProvider::from_pkg(file_tree, pkg_loader)?;
         let cached_params = CachedGameParams {
-            app_version: env!("CARGO_PKG_VERSION").to_owned(),
+            app_version: app_version.to_owned(),
             game_version,
             // TODO: kind of unnecessarily expensive to round-trip from Arc to Owned here.
             params: metadata_provider.params().iter().map(|param| Arc::unwrap_or_clone(Arc::clone(param))).collect(),
         };
 
-        let file = std::fs::File::create(cache_path).unwrap();
-        bincode::serialize_into(file, &cached_params).expect("failed to serialize cached game params");
+        // Failing to cache only costs time on the next launch, so don't fail the load
+        let cache_result = fs::create_dir_all(&cache_dir)
+            .map_err(ToolkitError::from)
+            .and_then(|_| write_cache_entry(&cache_path, &cached_params))
+            .and_then(|_| prune_cache(&cache_dir, &cache_path, max_cache_size));
+        if let Err(e) = cache_result {
+            warn!("failed to write game params cache: {}", e);
+        }
 
         metadata_provider
     };
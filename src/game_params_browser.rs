@@ -0,0 +1,259 @@
+//! A browser over the loaded GameParams.
+//!
+//! Each param is shown as a tree built from its serialized form. Any string in
+//! the tree which is the index of another param, or any integer which is the
+//! id of another param, is shown as a link so that e.g. a ship's modules or a
+//! module's guns can be followed without leaving the tab.
+
+use std::{collections::HashMap, sync::Arc};
+
+use egui::{CollapsingHeader, Color32, RichText};
+use serde_json::Value;
+use wowsunpack::game_params::{provider::GameMetadataProvider, types::Param};
+
+use crate::{app::ToolkitTabViewer, icons};
+
+/// Upper bound on the number of search results shown at once
+const MAX_SEARCH_RESULTS: usize = 500;
+
+/// Param ids are large numbers. Small integers are far more likely to be
+/// counts or flags than references, so they are never shown as links.
+const MIN_LINKABLE_PARAM_ID: u64 = 100_000;
+
+struct ParamEntry {
+    param: Arc<Param>,
+    localized_name: Option<String>,
+}
+
+/// Lookup tables built once per loaded [GameMetadataProvider]
+struct ParamIndex {
+    metadata_provider: Arc<GameMetadataProvider>,
+    entries: Vec<ParamEntry>,
+    by_index: HashMap<String, usize>,
+    by_id: HashMap<u64, usize>,
+}
+
+impl ParamIndex {
+    fn new(metadata_provider: Arc<GameMetadataProvider>) -> Self {
+        let entries: Vec<ParamEntry> = metadata_provider
+            .params()
+            .iter()
+            .map(|param| ParamEntry {
+                localized_name: metadata_provider.localized_name_from_param(param).map(|name| name.to_string()),
+                param: Arc::clone(param),
+            })
+            .collect();
+
+        let by_index = entries.iter().enumerate().map(|(i, entry)| (entry.param.index().to_owned(), i)).collect();
+        let by_id = entries.iter().enumerate().map(|(i, entry)| (entry.param.id() as u64, i)).collect();
+
+        ParamIndex {
+            metadata_provider,
+            entries,
+            by_index,
+            by_id,
+        }
+    }
+
+    fn search(&self, query: &str) -> Vec<usize> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        if let Some(i) = query.parse::<u64>().ok().and_then(|id| self.by_id.get(&id)) {
+            return vec![*i];
+        }
+
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_i, entry)| {
+                entry.param.index().to_lowercase().contains(&query) || entry.localized_name.as_ref().map(|name| name.to_lowercase().contains(&query)).unwrap_or(false)
+            })
+            .map(|(i, _entry)| i)
+            .take(MAX_SEARCH_RESULTS)
+            .collect()
+    }
+
+    fn link_target(&self, value: &Value) -> Option<usize> {
+        match value {
+            Value::String(index) => self.by_index.get(index).copied(),
+            Value::Number(number) => number.as_u64().filter(|id| *id >= MIN_LINKABLE_PARAM_ID).and_then(|id| self.by_id.get(&id).copied()),
+            _ => None,
+        }
+    }
+
+    fn display_name(&self, i: usize) -> String {
+        let entry = &self.entries[i];
+        match &entry.localized_name {
+            Some(name) => format!("{} ({})", name, entry.param.index()),
+            None => entry.param.index().to_owned(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct GameParamsBrowserTabState {
+    pub search: String,
+    searched: Option<String>,
+    results: Vec<usize>,
+    /// Previously viewed params, used for back navigation
+    history: Vec<usize>,
+    selected: Option<(usize, Value)>,
+    index: Option<ParamIndex>,
+}
+
+impl GameParamsBrowserTabState {
+    fn select(&mut self, i: usize, push_history: bool) {
+        let Some(index) = self.index.as_ref() else {
+            return;
+        };
+
+        if push_history {
+            if let Some((previous, _)) = self.selected.take() {
+                self.history.push(previous);
+            }
+        }
+
+        let value = serde_json::to_value(index.entries[i].param.as_ref()).unwrap_or(Value::Null);
+        self.selected = Some((i, value));
+    }
+}
+
+fn value_text(value: &Value) -> RichText {
+    match value {
+        Value::Null => RichText::new("null").color(Color32::GRAY),
+        Value::Bool(value) => RichText::new(value.to_string()).color(Color32::LIGHT_BLUE),
+        Value::Number(value) => RichText::new(value.to_string()).color(Color32::LIGHT_GREEN),
+        Value::String(value) => RichText::new(format!("{:?}", value)).color(Color32::KHAKI),
+        Value::Array(_) | Value::Object(_) => RichText::new(""),
+    }
+}
+
+fn build_value_tree(ui: &mut egui::Ui, index: &ParamIndex, key: &str, value: &Value, id_path: &str, navigate_to: &mut Option<usize>) {
+    let children: Vec<(String, &Value)> = match value {
+        Value::Object(map) => map.iter().map(|(key, value)| (key.clone(), value)).collect(),
+        Value::Array(values) => values.iter().enumerate().map(|(i, value)| (i.to_string(), value)).collect(),
+        leaf => {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}:", key));
+                ui.label(value_text(leaf));
+                if let Some(target) = index.link_target(leaf) {
+                    if ui.link(format!("{} {}", icons::ARROW_SQUARE_OUT, index.display_name(target))).clicked() {
+                        *navigate_to = Some(target);
+                    }
+                }
+            });
+            return;
+        }
+    };
+
+    let summary = if value.is_array() {
+        format!("{} [{}]", key, children.len())
+    } else {
+        format!("{} {{{}}}", key, children.len())
+    };
+    CollapsingHeader::new(summary).id_source(id_path).show(ui, |ui| {
+        for (child_key, child) in children {
+            build_value_tree(ui, index, &child_key, child, &format!("{}/{}", id_path, child_key), navigate_to);
+        }
+    });
+}
+
+impl ToolkitTabViewer<'_> {
+    /// Builds the GameParams browser tab
+    pub fn build_game_params_browser_tab(&mut self, ui: &mut egui::Ui) {
+        let Some(metadata_provider) = self.metadata_provider() else {
+            ui.label("GameParams have not been loaded. Set your World of Warships directory in the settings tab.");
+            return;
+        };
+
+        let state = &mut self.tab_state.game_params_browser_tab;
+        let is_current = state
+            .index
+            .as_ref()
+            .map(|index| Arc::ptr_eq(&index.metadata_provider, &metadata_provider))
+            .unwrap_or(false);
+        if !is_current {
+            // Game data was (re)loaded, so any previous indices are stale
+            *state = GameParamsBrowserTabState {
+                search: std::mem::take(&mut state.search),
+                index: Some(ParamIndex::new(metadata_provider)),
+                ..Default::default()
+            };
+        }
+
+        if state.searched.as_deref() != Some(state.search.as_str()) {
+            state.results = state.index.as_ref().map(|index| index.search(&state.search)).unwrap_or_default();
+            state.searched = Some(state.search.clone());
+        }
+
+        let mut navigate_to = None;
+        let mut go_back = false;
+
+        egui::SidePanel::left("game_params_browser_left").show_inside(ui, |ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.search).hint_text("Index, id or name"));
+            if state.results.len() == MAX_SEARCH_RESULTS {
+                ui.label(format!("Showing the first {} results", MAX_SEARCH_RESULTS));
+            }
+            ui.separator();
+
+            let Some(index) = state.index.as_ref() else {
+                return;
+            };
+            let selected = state.selected.as_ref().map(|(i, _)| *i);
+            egui::ScrollArea::vertical()
+                .id_source("game_params_search_results")
+                .show_rows(ui, 18.0, state.results.len(), |ui, range| {
+                    for &i in &state.results[range] {
+                        if ui.selectable_label(selected == Some(i), index.display_name(i)).clicked() {
+                            navigate_to = Some(i);
+                        }
+                    }
+                });
+        });
+
+        egui::CentralPanel::default().show_inside(ui, |ui| {
+            let (Some(index), Some((selected, value))) = (state.index.as_ref(), state.selected.as_ref()) else {
+                ui.label("Search for a param to view it");
+                return;
+            };
+
+            let param = &index.entries[*selected].param;
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!state.history.is_empty(), egui::Button::new(icons::ARROW_LEFT))
+                    .on_hover_text("Back")
+                    .clicked()
+                {
+                    go_back = true;
+                }
+                ui.heading(index.display_name(*selected));
+            });
+            ui.label(format!(
+                "Index: {}    Id: {}    Nation: {}    Species: {}",
+                param.index(),
+                param.id(),
+                param.nation(),
+                param
+                    .species()
+                    .map(|species| <&'static str>::from(species).to_owned())
+                    .unwrap_or_else(|| "None".to_owned())
+            ));
+            ui.separator();
+
+            egui::ScrollArea::both().id_source("game_params_value_tree").show(ui, |ui| {
+                build_value_tree(ui, index, param.index(), value, param.index(), &mut navigate_to);
+            });
+        });
+
+        if go_back {
+            if let Some(previous) = state.history.pop() {
+                state.select(previous, false);
+            }
+        } else if let Some(target) = navigate_to {
+            state.select(target, true);
+        }
+    }
+}
@@ -0,0 +1,498 @@
+//! Comparison of GameParams between two game versions.
+//!
+//! Params are compared by their index (e.g. `PASB001`) using their serialized
+//! JSON form, so any field the [Param] type exposes is covered without having to
+//! know each param type's layout.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+};
+
+use egui::CollapsingHeader;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::debug;
+use wowsunpack::{
+    data::pkg::PkgFileLoader,
+    game_params::{provider::GameMetadataProvider, types::Param},
+};
+
+use crate::{
+    app::{TimedMessage, ToolkitTabViewer},
+    build_diff,
+    error::ToolkitError,
+    game_params::CachedGameParams,
+    icons,
+    task::{BackgroundTask, BackgroundTaskCompletion, BackgroundTaskKind},
+};
+
+/// Where one side of a comparison gets its params from
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum GameParamsSource {
+    /// The currently loaded game data
+    #[default]
+    Loaded,
+    /// A `game_params.bin` cache or a minimal JSON/CBOR GameParams dump
+    Snapshot(PathBuf),
+    /// The latest build of another game install
+    Install(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ParamChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParamChange {
+    pub index: String,
+    pub category: String,
+    pub kind: ParamChangeKind,
+    /// Changed numeric fields. Only populated for [ParamChangeKind::Changed].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GameParamsDiff {
+    pub old_label: String,
+    pub new_label: String,
+    /// Changes grouped by species, then by nation
+    pub groups: BTreeMap<String, BTreeMap<String, Vec<ParamChange>>>,
+}
+
+#[derive(Default)]
+pub struct GameParamsDiffTabState {
+    pub old_source: GameParamsSource,
+    pub new_source: GameParamsSource,
+    pub filter: String,
+    pub category_filter: Option<String>,
+    pub diff: Option<Arc<GameParamsDiff>>,
+}
+
+/// Params reduced to what we need for the comparison
+struct ComparableParam {
+    species: String,
+    nation: String,
+    category: String,
+    fields: BTreeMap<String, Value>,
+}
+
+/// Flattens a JSON value into dotted field paths
+fn flatten(value: &Value, prefix: &str, out: &mut BTreeMap<String, Value>) {
+    let join = |key: &str| if prefix.is_empty() { key.to_owned() } else { format!("{}.{}", prefix, key) };
+
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(value, &join(key), out);
+            }
+        }
+        Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                flatten(value, &join(&i.to_string()), out);
+            }
+        }
+        other => {
+            out.insert(prefix.to_owned(), other.clone());
+        }
+    }
+}
+
+/// The param type is the name of the single variant in the serialized `data` field
+fn param_category(value: &Value) -> String {
+    value
+        .get("data")
+        .and_then(Value::as_object)
+        .filter(|data| data.len() == 1)
+        .and_then(|data| data.keys().next().cloned())
+        .unwrap_or_else(|| "Other".to_owned())
+}
+
+fn comparable_params<'a>(params: impl IntoIterator<Item = &'a Param>) -> Result<BTreeMap<String, ComparableParam>, ToolkitError> {
+    params
+        .into_iter()
+        .map(|param| {
+            let value = serde_json::to_value(param).map_err(|e| ToolkitError::GameParamsSnapshot(e.to_string()))?;
+            let mut fields = BTreeMap::new();
+            flatten(&value, "", &mut fields);
+
+            Ok((
+                param.index().to_owned(),
+                ComparableParam {
+                    species: param
+                        .species()
+                        .map(|species| <&'static str>::from(species).to_owned())
+                        .unwrap_or_else(|| "None".to_owned()),
+                    nation: param.nation().to_owned(),
+                    category: param_category(&value),
+                    fields,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn read_snapshot(path: &Path) -> Result<Vec<Param>, ToolkitError> {
+    let file = File::open(path)?;
+    let snapshot_error = |e: &dyn std::fmt::Display| ToolkitError::GameParamsSnapshot(format!("{}: {}", path.display(), e));
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_reader(file).map_err(|e| snapshot_error(&e)),
+        Some("cbor") => serde_cbor::from_reader(file).map_err(|e| snapshot_error(&e)),
+        _ => bincode::deserialize_from::<_, CachedGameParams>(file)
+            .map(|cached| cached.params)
+            .map_err(|e| snapshot_error(&e)),
+    }
+}
+
+fn load_source(source: &GameParamsSource, loaded: Option<&Arc<GameMetadataProvider>>) -> Result<(String, BTreeMap<String, ComparableParam>), ToolkitError> {
+    match source {
+        GameParamsSource::Loaded => {
+            let metadata_provider = loaded.ok_or(ToolkitError::InvalidGameParams)?;
+            let params = comparable_params(metadata_provider.params().iter().map(|param| param.as_ref()))?;
+            Ok(("Loaded game data".to_owned(), params))
+        }
+        GameParamsSource::Snapshot(path) => {
+            let params = read_snapshot(path)?;
+            let label = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            Ok((label, comparable_params(params.iter())?))
+        }
+        GameParamsSource::Install(wows_directory) => {
+            let build = build_diff::available_builds(wows_directory)?
+                .first()
+                .copied()
+                .ok_or_else(|| ToolkitError::InvalidWowsDirectory(wows_directory.clone()))?;
+            let file_tree = build_diff::load_build_file_tree(wows_directory, build)?;
+            let pkg_loader = PkgFileLoader::new(wows_directory.join("res_packages"));
+            let metadata_provider = GameMetadataProvider::from_pkg(&file_tree, &pkg_loader)?;
+
+            let params = comparable_params(metadata_provider.params().iter().map(|param| param.as_ref()))?;
+            Ok((format!("Build {}", build), params))
+        }
+    }
+}
+
+fn changed_numeric_fields(old: &BTreeMap<String, Value>, new: &BTreeMap<String, Value>) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut record = |field: &String, old_value: Option<&Value>, new_value: Option<&Value>| {
+        let is_numeric = old_value.map(Value::is_number).unwrap_or(false) || new_value.map(Value::is_number).unwrap_or(false);
+        if is_numeric && old_value != new_value {
+            changes.push(FieldChange {
+                field: field.clone(),
+                old: old_value.cloned().unwrap_or(Value::Null),
+                new: new_value.cloned().unwrap_or(Value::Null),
+            });
+        }
+    };
+
+    for (field, old_value) in old {
+        record(field, Some(old_value), new.get(field));
+    }
+    for (field, new_value) in new {
+        if !old.contains_key(field) {
+            record(field, None, Some(new_value));
+        }
+    }
+
+    changes
+}
+
+fn diff_game_params(
+    old_source: GameParamsSource,
+    new_source: GameParamsSource,
+    loaded: Option<Arc<GameMetadataProvider>>,
+) -> Result<BackgroundTaskCompletion, ToolkitError> {
+    debug!("Comparing GameParams {:?} to {:?}", old_source, new_source);
+
+    let (old_label, old_params) = load_source(&old_source, loaded.as_ref())?;
+    let (new_label, mut new_params) = load_source(&new_source, loaded.as_ref())?;
+
+    let mut groups: BTreeMap<String, BTreeMap<String, Vec<ParamChange>>> = BTreeMap::new();
+    let mut push = |param: &ComparableParam, change: ParamChange| {
+        groups.entry(param.species.clone()).or_default().entry(param.nation.clone()).or_default().push(change);
+    };
+
+    for (index, old_param) in old_params {
+        match new_params.remove(&index) {
+            Some(new_param) => {
+                let fields = changed_numeric_fields(&old_param.fields, &new_param.fields);
+                if !fields.is_empty() {
+                    push(
+                        &new_param,
+                        ParamChange {
+                            index,
+                            category: new_param.category.clone(),
+                            kind: ParamChangeKind::Changed,
+                            fields,
+                        },
+                    );
+                }
+            }
+            None => push(
+                &old_param,
+                ParamChange {
+                    index,
+                    category: old_param.category.clone(),
+                    kind: ParamChangeKind::Removed,
+                    fields: Vec::new(),
+                },
+            ),
+        }
+    }
+
+    for (index, new_param) in new_params {
+        push(
+            &new_param,
+            ParamChange {
+                index,
+                category: new_param.category.clone(),
+                kind: ParamChangeKind::Added,
+                fields: Vec::new(),
+            },
+        );
+    }
+
+    Ok(BackgroundTaskCompletion::GameParamsCompared(Arc::new(GameParamsDiff {
+        old_label,
+        new_label,
+        groups,
+    })))
+}
+
+pub fn start_comparing_game_params(old_source: GameParamsSource, new_source: GameParamsSource, loaded: Option<Arc<GameMetadataProvider>>) -> BackgroundTask {
+    let (tx, rx) = mpsc::channel();
+
+    let _join_handle = std::thread::spawn(move || {
+        let _ = tx.send(diff_game_params(old_source, new_source, loaded));
+    });
+
+    BackgroundTask::new(rx, BackgroundTaskKind::ComparingGameParams)
+}
+
+impl GameParamsDiff {
+    pub fn change_count(&self) -> usize {
+        self.groups.values().flat_map(|nations| nations.values()).map(Vec::len).sum()
+    }
+
+    pub fn categories(&self) -> Vec<String> {
+        let mut categories: Vec<String> = self
+            .groups
+            .values()
+            .flat_map(|nations| nations.values())
+            .flatten()
+            .map(|change| change.category.clone())
+            .collect();
+        categories.sort();
+        categories.dedup();
+
+        categories
+    }
+
+    /// Renders the diff as a Markdown summary suitable for patch notes
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# GameParams changes: {} → {}", self.old_label, self.new_label);
+
+        for (species, nations) in &self.groups {
+            let _ = writeln!(out, "\n## {}", species);
+            for (nation, changes) in nations {
+                let _ = writeln!(out, "\n### {}\n", nation);
+                for change in changes {
+                    let _ = writeln!(out, "- **{:?}** `{}` ({})", change.kind, change.index, change.category);
+                    for field in &change.fields {
+                        let _ = writeln!(out, "  - `{}`: {} → {}", field.field, field.old, field.new);
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+fn source_selector(ui: &mut egui::Ui, label: &str, source: &mut GameParamsSource) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        if ui.radio(matches!(source, GameParamsSource::Loaded), "Loaded game data").clicked() {
+            *source = GameParamsSource::Loaded;
+        }
+        if ui
+            .button("Snapshot...")
+            .on_hover_text("A game_params.bin cache or a minimal JSON/CBOR GameParams dump")
+            .clicked()
+        {
+            if let Some(path) = rfd::FileDialog::new().add_filter("GameParams snapshot", &["bin", "json", "cbor"]).pick_file() {
+                *source = GameParamsSource::Snapshot(path);
+            }
+        }
+        if ui
+            .button("Install...")
+            .on_hover_text("Load the latest build of another World of Warships install")
+            .clicked()
+        {
+            if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                *source = GameParamsSource::Install(path);
+            }
+        }
+        match source {
+            GameParamsSource::Loaded => {}
+            GameParamsSource::Snapshot(path) | GameParamsSource::Install(path) => {
+                ui.label(path.to_string_lossy());
+            }
+        }
+    });
+}
+
+impl ToolkitTabViewer<'_> {
+    fn export_game_params_diff(&self, diff: &GameParamsDiff, markdown: bool) {
+        let (file_name, extension) = if markdown {
+            ("GameParamsChanges.md", "md")
+        } else {
+            ("GameParamsChanges.json", "json")
+        };
+        let Some(path) = rfd::FileDialog::new().set_file_name(file_name).add_filter(extension, &[extension]).save_file() else {
+            return;
+        };
+
+        let result = if markdown {
+            std::fs::write(&path, diff.to_markdown()).map_err(ToolkitError::from)
+        } else {
+            File::create(&path)
+                .map_err(ToolkitError::from)
+                .and_then(|file| serde_json::to_writer_pretty(file, diff).map_err(|e| ToolkitError::GameParamsSnapshot(e.to_string())))
+        };
+
+        let message = match result {
+            Ok(_) => format!("{} Exported GameParams changes to {}", icons::CHECK_CIRCLE, path.display()),
+            Err(e) => format!("{} Failed to export GameParams changes: {}", icons::WARNING, e),
+        };
+        *self.tab_state.timed_message.write() = Some(TimedMessage::new(message));
+    }
+
+    /// Builds the GameParams comparison tab
+    pub fn build_game_params_diff_tab(&mut self, ui: &mut egui::Ui) {
+        let loaded = self
+            .tab_state
+            .world_of_warships_data
+            .as_ref()
+            .and_then(|wows_data| wows_data.read().game_metadata.clone());
+
+        let state = &mut self.tab_state.game_params_diff_tab;
+        source_selector(ui, "Old:", &mut state.old_source);
+        source_selector(ui, "New:", &mut state.new_source);
+
+        let can_compare = !self
+            .tab_state
+            .background_tasks
+            .is_running(|kind| matches!(kind, BackgroundTaskKind::ComparingGameParams))
+            && state.old_source != state.new_source;
+        if ui.add_enabled(can_compare, egui::Button::new(format!("{} Compare", icons::GIT_DIFF))).clicked() {
+            self.tab_state
+                .background_tasks
+                .start(start_comparing_game_params(state.old_source.clone(), state.new_source.clone(), loaded));
+        }
+
+        let Some(diff) = state.diff.clone() else {
+            return;
+        };
+
+        ui.separator();
+
+        let mut export = None;
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{} {} {}: {} changes",
+                diff.old_label,
+                icons::ARROW_RIGHT,
+                diff.new_label,
+                diff.change_count()
+            ));
+            if ui.button(format!("{} Export Markdown", icons::FLOPPY_DISK)).clicked() {
+                export = Some(true);
+            }
+            if ui.button(format!("{} Export JSON", icons::FLOPPY_DISK)).clicked() {
+                export = Some(false);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.filter).hint_text("Filter by param index"));
+            egui::ComboBox::new("game_params_diff_category", "Category")
+                .selected_text(state.category_filter.as_deref().unwrap_or("All"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut state.category_filter, None, "All");
+                    for category in diff.categories() {
+                        ui.selectable_value(&mut state.category_filter, Some(category.clone()), category);
+                    }
+                });
+        });
+
+        let filter = state.filter.to_lowercase();
+        let category_filter = state.category_filter.clone();
+        let is_shown = |change: &ParamChange| {
+            (filter.is_empty() || change.index.to_lowercase().contains(&filter)) && category_filter.as_ref().map(|category| *category == change.category).unwrap_or(true)
+        };
+
+        egui::ScrollArea::vertical().id_source("game_params_diff_scroll_area").show(ui, |ui| {
+            for (species, nations) in &diff.groups {
+                let species_count: usize = nations.values().flatten().filter(|change| is_shown(change)).count();
+                if species_count == 0 {
+                    continue;
+                }
+
+                CollapsingHeader::new(format!("{} ({})", species, species_count)).id_source(species).show(ui, |ui| {
+                    for (nation, changes) in nations {
+                        let changes: Vec<&ParamChange> = changes.iter().filter(|change| is_shown(change)).collect();
+                        if changes.is_empty() {
+                            continue;
+                        }
+
+                        CollapsingHeader::new(format!("{} ({})", nation, changes.len()))
+                            .id_source((species, nation))
+                            .show(ui, |ui| {
+                                for change in changes {
+                                    let title = format!("{:?}: {} ({})", change.kind, change.index, change.category);
+                                    if change.fields.is_empty() {
+                                        ui.label(title);
+                                        continue;
+                                    }
+
+                                    CollapsingHeader::new(title).id_source((species, nation, &change.index)).show(ui, |ui| {
+                                        egui::Grid::new((species, nation, &change.index, "fields"))
+                                            .num_columns(3)
+                                            .striped(true)
+                                            .show(ui, |ui| {
+                                                for field in &change.fields {
+                                                    ui.label(field.field.as_str());
+                                                    ui.label(field.old.to_string());
+                                                    ui.label(field.new.to_string());
+                                                    ui.end_row();
+                                                }
+                                            });
+                                    });
+                                }
+                            });
+                    }
+                });
+            }
+        });
+
+        if let Some(markdown) = export {
+            self.export_game_params_diff(&diff, markdown);
+        }
+    }
+}
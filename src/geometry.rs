@@ -0,0 +1,469 @@
+//! Decoding of the game's `.geometry` model files and export to Wavefront OBJ.
+//!
+//! A `.geometry` file contains a set of merged vertex and index buffers plus
+//! mappings which describe which range of those buffers belongs to each
+//! render set. The matching `.visual` file describes the materials used by the
+//! render sets.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::error::ToolkitError;
+
+/// Buffers starting with this magic are meshoptimizer-encoded
+const ENCODED_BUFFER_MAGIC: &[u8; 4] = b"ENCD";
+
+/// Adds two offsets read from the file, failing instead of overflowing
+fn add(a: usize, b: usize) -> Result<usize, ToolkitError> {
+    a.checked_add(b).ok_or(ToolkitError::InvalidGeometry("offset out of range"))
+}
+
+/// Multiplies two sizes read from the file, failing instead of overflowing
+fn mul(a: usize, b: usize) -> Result<usize, ToolkitError> {
+    a.checked_mul(b).ok_or(ToolkitError::InvalidGeometry("offset out of range"))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], ToolkitError> {
+        self.data
+            .get(offset..add(offset, len)?)
+            .ok_or(ToolkitError::InvalidGeometry("unexpected end of file"))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, ToolkitError> {
+        Ok(u16::from_le_bytes(self.bytes(offset, 2)?.try_into().unwrap()))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, ToolkitError> {
+        Ok(u32::from_le_bytes(self.bytes(offset, 4)?.try_into().unwrap()))
+    }
+
+    fn u64(&self, offset: usize) -> Result<usize, ToolkitError> {
+        Ok(u64::from_le_bytes(self.bytes(offset, 8)?.try_into().unwrap()) as usize)
+    }
+
+    fn f32(&self, offset: usize) -> Result<f32, ToolkitError> {
+        Ok(f32::from_le_bytes(self.bytes(offset, 4)?.try_into().unwrap()))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BufferMapping {
+    mapping_id: u32,
+    buffer_index: usize,
+    items_offset: usize,
+    items_count: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: Option<[f32; 3]>,
+    pub uv: Option<[f32; 2]>,
+}
+
+/// A single render set in the model
+#[derive(Debug)]
+pub struct Mesh {
+    pub mapping_id: u32,
+    pub vertices: Vec<Vertex>,
+    /// Triangle list indices into [Mesh::vertices]
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Default)]
+pub struct Geometry {
+    pub meshes: Vec<Mesh>,
+}
+
+/// Material information pulled out of a `.visual` file
+#[derive(Debug, Default, Clone)]
+pub struct Material {
+    pub name: String,
+    /// Texture property name (e.g. `diffuseMap`) to texture path
+    pub textures: BTreeMap<String, String>,
+}
+
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f => {
+            if mantissa == 0.0 {
+                sign * f32::INFINITY
+            } else {
+                f32::NAN
+            }
+        }
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Normals are packed as 11:11:10 signed integers
+fn unpack_normal(packed: u32) -> [f32; 3] {
+    fn signed(value: u32, bits: u32) -> f32 {
+        let max = (1 << (bits - 1)) - 1;
+        let value = if value > max { value as i32 - (1 << bits) } else { value as i32 };
+        value as f32 / max as f32
+    }
+
+    [signed(packed & 0x7ff, 11), signed((packed >> 11) & 0x7ff, 11), signed((packed >> 22) & 0x3ff, 10)]
+}
+
+/// Describes where each attribute lives in a vertex based on the vertex type name, e.g. `set3/xyznuvtbpc`
+#[derive(Debug, Default)]
+struct VertexLayout {
+    normal_offset: Option<usize>,
+    uv_offset: Option<usize>,
+}
+
+impl VertexLayout {
+    fn from_type_name(name: &str) -> Self {
+        let format = name.rsplit('/').next().unwrap_or(name);
+        let mut layout = VertexLayout::default();
+
+        // Position is always 3 floats at the start of the vertex
+        let mut offset = 12;
+        let mut rest = format.strip_prefix("xyz").unwrap_or(format);
+        if let Some(remaining) = rest.strip_prefix('n') {
+            layout.normal_offset = Some(offset);
+            offset += 4;
+            rest = remaining;
+        }
+        if rest.starts_with("uv") {
+            layout.uv_offset = Some(offset);
+        }
+
+        layout
+    }
+}
+
+struct VertexBuffer<'a> {
+    data: &'a [u8],
+    stride: usize,
+    layout: VertexLayout,
+}
+
+struct IndexBuffer<'a> {
+    data: &'a [u8],
+    index_size: usize,
+}
+
+fn check_not_encoded(data: &[u8]) -> Result<(), ToolkitError> {
+    if data.starts_with(ENCODED_BUFFER_MAGIC) {
+        Err(ToolkitError::UnsupportedGeometryEncoding)
+    } else {
+        Ok(())
+    }
+}
+
+fn read_mappings(reader: &Reader<'_>, offset: usize, count: usize) -> Result<Vec<BufferMapping>, ToolkitError> {
+    const MAPPING_SIZE: usize = 16;
+
+    (0..count)
+        .map(|i| {
+            let entry = add(offset, mul(i, MAPPING_SIZE)?)?;
+            Ok(BufferMapping {
+                mapping_id: reader.u32(entry)?,
+                buffer_index: reader.u16(add(entry, 4)?)? as usize,
+                items_offset: reader.u32(add(entry, 8)?)? as usize,
+                items_count: reader.u32(add(entry, 12)?)? as usize,
+            })
+        })
+        .collect()
+}
+
+fn read_vertex_buffers<'a>(reader: &Reader<'a>, offset: usize, count: usize) -> Result<Vec<VertexBuffer<'a>>, ToolkitError> {
+    const PROTOTYPE_SIZE: usize = 32;
+
+    (0..count)
+        .map(|i| {
+            let entry = add(offset, mul(i, PROTOTYPE_SIZE)?)?;
+            // Offsets in the prototypes are relative to the field they're read from
+            let data_offset = add(entry, reader.u64(entry)?)?;
+            let type_name_field = add(entry, 8)?;
+            let type_name_offset = add(type_name_field, reader.u64(type_name_field)?)?;
+            let data_size = reader.u32(add(entry, 16)?)? as usize;
+            let stride = reader.u16(add(entry, 20)?)? as usize;
+
+            let type_name_len = reader.u32(type_name_offset)? as usize;
+            let type_name_data_field = add(type_name_offset, 8)?;
+            let type_name_data_offset = add(type_name_data_field, reader.u64(type_name_data_field)?)?;
+            let type_name = String::from_utf8_lossy(reader.bytes(type_name_data_offset, type_name_len)?.split(|b| *b == 0).next().unwrap_or_default()).into_owned();
+
+            let data = reader.bytes(data_offset, data_size)?;
+            check_not_encoded(data)?;
+
+            if stride < 12 {
+                return Err(ToolkitError::InvalidGeometry("vertex stride is too small"));
+            }
+
+            Ok(VertexBuffer {
+                data,
+                stride,
+                layout: VertexLayout::from_type_name(&type_name),
+            })
+        })
+        .collect()
+}
+
+fn read_index_buffers<'a>(reader: &Reader<'a>, offset: usize, count: usize) -> Result<Vec<IndexBuffer<'a>>, ToolkitError> {
+    const PROTOTYPE_SIZE: usize = 16;
+
+    (0..count)
+        .map(|i| {
+            let entry = add(offset, mul(i, PROTOTYPE_SIZE)?)?;
+            let data_offset = add(entry, reader.u64(entry)?)?;
+            let data_size = reader.u32(add(entry, 8)?)? as usize;
+            let index_size = reader.u16(add(entry, 12)?)? as usize;
+
+            let data = reader.bytes(data_offset, data_size)?;
+            check_not_encoded(data)?;
+
+            if index_size != 2 && index_size != 4 {
+                return Err(ToolkitError::InvalidGeometry("unsupported index size"));
+            }
+
+            Ok(IndexBuffer { data, index_size })
+        })
+        .collect()
+}
+
+impl Geometry {
+    pub fn parse(data: &[u8]) -> Result<Geometry, ToolkitError> {
+        let reader = Reader { data };
+
+        let vertices_mapping_count = reader.u32(0)? as usize;
+        let indices_mapping_count = reader.u32(4)? as usize;
+        let vertex_buffer_count = reader.u32(8)? as usize;
+        let index_buffer_count = reader.u32(12)? as usize;
+        // 16: collision model count, 20: armor model count
+        let vertices_mapping_offset = reader.u64(24)?;
+        let indices_mapping_offset = reader.u64(32)?;
+        let vertex_buffers_offset = reader.u64(40)?;
+        let index_buffers_offset = reader.u64(48)?;
+
+        let vertex_mappings = read_mappings(&reader, vertices_mapping_offset, vertices_mapping_count)?;
+        let index_mappings = read_mappings(&reader, indices_mapping_offset, indices_mapping_count)?;
+        let vertex_buffers = read_vertex_buffers(&reader, vertex_buffers_offset, vertex_buffer_count)?;
+        let index_buffers = read_index_buffers(&reader, index_buffers_offset, index_buffer_count)?;
+
+        let mut meshes = Vec::with_capacity(vertex_mappings.len());
+        for vertex_mapping in &vertex_mappings {
+            let Some(index_mapping) = index_mappings.iter().find(|mapping| mapping.mapping_id == vertex_mapping.mapping_id) else {
+                continue;
+            };
+
+            let vertex_buffer = vertex_buffers
+                .get(vertex_mapping.buffer_index)
+                .ok_or(ToolkitError::InvalidGeometry("vertex buffer index out of range"))?;
+            let index_buffer = index_buffers
+                .get(index_mapping.buffer_index)
+                .ok_or(ToolkitError::InvalidGeometry("index buffer index out of range"))?;
+
+            let vertex_reader = Reader { data: vertex_buffer.data };
+            let vertices = (vertex_mapping.items_offset..add(vertex_mapping.items_offset, vertex_mapping.items_count)?)
+                .map(|i| {
+                    let base = mul(i, vertex_buffer.stride)?;
+                    let position = [vertex_reader.f32(base)?, vertex_reader.f32(add(base, 4)?)?, vertex_reader.f32(add(base, 8)?)?];
+                    let normal = match vertex_buffer.layout.normal_offset {
+                        Some(offset) => Some(unpack_normal(vertex_reader.u32(add(base, offset)?)?)),
+                        None => None,
+                    };
+                    let uv = match vertex_buffer.layout.uv_offset {
+                        Some(offset) => {
+                            let offset = add(base, offset)?;
+                            Some([half_to_f32(vertex_reader.u16(offset)?), half_to_f32(vertex_reader.u16(add(offset, 2)?)?)])
+                        }
+                        None => None,
+                    };
+
+                    Ok(Vertex { position, normal, uv })
+                })
+                .collect::<Result<Vec<_>, ToolkitError>>()?;
+
+            let index_reader = Reader { data: index_buffer.data };
+            let indices = (index_mapping.items_offset..add(index_mapping.items_offset, index_mapping.items_count)?)
+                .map(|i| match index_buffer.index_size {
+                    2 => index_reader.u16(mul(i, 2)?).map(u32::from),
+                    _ => index_reader.u32(mul(i, 4)?),
+                })
+                .collect::<Result<Vec<_>, ToolkitError>>()?;
+
+            if indices.iter().any(|index| *index as usize >= vertices.len()) {
+                return Err(ToolkitError::InvalidGeometry("index out of range of its vertex mapping"));
+            }
+
+            meshes.push(Mesh {
+                mapping_id: vertex_mapping.mapping_id,
+                vertices,
+                indices,
+            });
+        }
+
+        Ok(Geometry { meshes })
+    }
+
+    /// Writes the model as OBJ. If `materials` is provided, render sets are assigned
+    /// materials in the order they appear in the `.visual` file.
+    pub fn write_obj<W: Write>(&self, out: &mut W, mtl_file_name: Option<&str>, materials: &[Material]) -> Result<(), ToolkitError> {
+        let mut obj = String::new();
+        if let Some(mtl_file_name) = mtl_file_name {
+            let _ = writeln!(obj, "mtllib {}", mtl_file_name);
+        }
+
+        // OBJ indices are 1-based and global across the file
+        let mut vertex_base = 1;
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            let _ = writeln!(obj, "o mesh_{:08x}", mesh.mapping_id);
+            if let Some(material) = materials.get(i) {
+                let _ = writeln!(obj, "usemtl {}", material.name);
+            }
+
+            for vertex in &mesh.vertices {
+                let [x, y, z] = vertex.position;
+                let _ = writeln!(obj, "v {} {} {}", x, y, z);
+            }
+            let has_uvs = mesh.vertices.first().map(|vertex| vertex.uv.is_some()).unwrap_or(false);
+            let has_normals = mesh.vertices.first().map(|vertex| vertex.normal.is_some()).unwrap_or(false);
+            if has_uvs {
+                for vertex in &mesh.vertices {
+                    let [u, v] = vertex.uv.unwrap_or_default();
+                    // OBJ texture coordinates have their origin in the bottom left
+                    let _ = writeln!(obj, "vt {} {}", u, 1.0 - v);
+                }
+            }
+            if has_normals {
+                for vertex in &mesh.vertices {
+                    let [x, y, z] = vertex.normal.unwrap_or_default();
+                    let _ = writeln!(obj, "vn {} {} {}", x, y, z);
+                }
+            }
+
+            for triangle in mesh.indices.chunks_exact(3) {
+                let _ = write!(obj, "f");
+                for index in triangle {
+                    let index = u64::from(*index) + vertex_base;
+                    match (has_uvs, has_normals) {
+                        (true, true) => write!(obj, " {0}/{0}/{0}", index),
+                        (true, false) => write!(obj, " {0}/{0}", index),
+                        (false, true) => write!(obj, " {0}//{0}", index),
+                        (false, false) => write!(obj, " {}", index),
+                    }
+                    .expect("writing to a String cannot fail");
+                }
+                let _ = writeln!(obj);
+            }
+
+            vertex_base += mesh.vertices.len() as u64;
+        }
+
+        out.write_all(obj.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// Returns the text between `<tag>` and `</tag>` for every occurrence of `tag`
+fn tag_contents<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+
+    let mut results = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after_open = &rest[start + open.len()..];
+        let Some(end) = after_open.find(&close) else {
+            break;
+        };
+        results.push(&after_open[..end]);
+        rest = &after_open[end + close.len()..];
+    }
+
+    results
+}
+
+/// Pulls the render set materials out of a `.visual` file
+pub fn parse_visual_materials(visual_xml: &str) -> Vec<Material> {
+    tag_contents(visual_xml, "renderSet")
+        .into_iter()
+        .enumerate()
+        .map(|(i, render_set)| {
+            let name = tag_contents(render_set, "identifier")
+                .first()
+                .map(|identifier| identifier.trim().to_owned())
+                .unwrap_or_else(|| format!("material_{}", i));
+
+            let textures = tag_contents(render_set, "property")
+                .into_iter()
+                .filter_map(|property| {
+                    let texture = tag_contents(property, "Texture").first()?.trim().to_owned();
+                    let property_name = property[..property.find('<').unwrap_or(property.len())].trim().to_owned();
+                    Some((property_name, texture))
+                })
+                .collect();
+
+            Material { name, textures }
+        })
+        .collect()
+}
+
+/// Writes an MTL file referencing the textures used by each material
+pub fn write_mtl<W: Write>(out: &mut W, materials: &[Material]) -> Result<(), ToolkitError> {
+    let mut mtl = String::new();
+    for material in materials {
+        let _ = writeln!(mtl, "newmtl {}", material.name);
+        for (property, texture) in &material.textures {
+            // Textures are referenced relative to the `res` directory
+            let texture = Path::new("res").join(texture).to_string_lossy().replace('\\', "/");
+            let statement = match property.as_str() {
+                "diffuseMap" => "map_Kd",
+                "normalMap" => "norm",
+                "metallicGlossMap" => "map_Pm",
+                "ambientOcclusionMap" => "map_Ka",
+                _ => {
+                    let _ = writeln!(mtl, "# {} {}", property, texture);
+                    continue;
+                }
+            };
+            let _ = writeln!(mtl, "{} {}", statement, texture);
+        }
+        let _ = writeln!(mtl);
+    }
+
+    out.write_all(mtl.as_bytes())?;
+
+    Ok(())
+}
+
+/// Exports a model to `obj_path`, writing a `.mtl` next to it if materials are available
+pub fn export_obj(geometry_data: &[u8], visual_xml: Option<&str>, obj_path: &Path) -> Result<PathBuf, ToolkitError> {
+    let geometry = Geometry::parse(geometry_data)?;
+    let materials = visual_xml.map(parse_visual_materials).unwrap_or_default();
+
+    let mtl_path = obj_path.with_extension("mtl");
+    let mtl_file_name = if materials.is_empty() {
+        None
+    } else {
+        let mut mtl_file = std::fs::File::create(&mtl_path)?;
+        write_mtl(&mut mtl_file, &materials)?;
+        mtl_path.file_name().map(|name| name.to_string_lossy().into_owned())
+    };
+
+    let mut obj_file = std::io::BufWriter::new(std::fs::File::create(obj_path)?);
+    geometry.write_obj(&mut obj_file, mtl_file_name.as_deref(), &materials)?;
+    obj_file.flush()?;
+
+    Ok(obj_path.to_path_buf())
+}
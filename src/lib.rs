@@ -1,17 +1,40 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![allow(clippy::blocks_in_if_conditions)]
 mod app;
+mod archive_output;
+mod asset_gallery;
+mod build_diff;
 mod build_tracker;
+mod content_search;
+mod dds;
 mod error;
+mod extraction_manifest;
 mod file_unpacker;
 mod game_params;
+mod game_params_browser;
+mod game_params_diff;
+mod geometry;
+mod localization;
+mod logging;
+mod packed_xml;
 mod plaintext_viewer;
+mod player_store;
 mod player_tracker;
+mod replay_archive;
 mod replay_parser;
+mod saved_filters;
+mod settings_migrations;
+mod ship_stats;
+mod sound_banks;
 mod task;
+mod task_manager;
 mod twitch;
+mod unpacker_jobs;
+mod user_data;
 mod util;
 mod wows_data;
+mod wwise;
 pub use app::WowsToolkitApp;
+pub use logging::{log_dir, MemoryLogLayer};
 pub const APP_NAME: &str = "WoWs Toolkit";
 pub(crate) use egui_phosphor::regular as icons;
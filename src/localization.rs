@@ -0,0 +1,424 @@
+//! A browser over the game's localization catalogs.
+//!
+//! [gettext::Catalog] only supports lookups, so the `global.mo` files are parsed
+//! again here to get at every `IDS_` key. Plural forms are collapsed to their
+//! first form, which is what the game displays for most strings.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use egui_extras::{Column, TableBuilder};
+use serde_json::{Map, Value};
+
+use crate::{
+    app::{TimedMessage, ToolkitTabViewer},
+    error::ToolkitError,
+    icons,
+};
+
+const MO_MAGIC_LE: u32 = 0x950412de;
+const MO_MAGIC_BE: u32 = 0xde120495;
+
+/// Strings for a single locale, sorted by key
+pub struct LocaleCatalog {
+    pub locale: String,
+    entries: Vec<(String, String)>,
+}
+
+fn read_u32(data: &[u8], offset: usize, big_endian: bool) -> Result<u32, ToolkitError> {
+    let bytes: [u8; 4] = data
+        .get(offset..offset + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(ToolkitError::InvalidCatalog("unexpected end of file"))?;
+
+    Ok(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+}
+
+fn read_string(data: &[u8], table: usize, i: usize, big_endian: bool) -> Result<&[u8], ToolkitError> {
+    let len = read_u32(data, table + i * 8, big_endian)? as usize;
+    let offset = read_u32(data, table + i * 8 + 4, big_endian)? as usize;
+
+    data.get(offset..offset + len).ok_or(ToolkitError::InvalidCatalog("string out of bounds"))
+}
+
+/// Takes the text before the first NUL, i.e. the singular form of a plural entry
+fn first_form(data: &[u8]) -> String {
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+impl LocaleCatalog {
+    pub fn parse(locale: String, data: &[u8]) -> Result<Self, ToolkitError> {
+        let big_endian = match read_u32(data, 0, false)? {
+            MO_MAGIC_LE => false,
+            MO_MAGIC_BE => true,
+            _ => return Err(ToolkitError::InvalidCatalog("bad magic")),
+        };
+
+        let count = read_u32(data, 8, big_endian)? as usize;
+        let originals = read_u32(data, 12, big_endian)? as usize;
+        let translations = read_u32(data, 16, big_endian)? as usize;
+
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let key = first_form(read_string(data, originals, i, big_endian)?);
+            // The empty key holds the catalog's metadata
+            if key.is_empty() {
+                continue;
+            }
+            let text = first_form(read_string(data, translations, i, big_endian)?);
+            entries.push((key, text));
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(LocaleCatalog { locale, entries })
+    }
+
+    pub fn load(wows_dir: &Path, build: usize, locale: &str) -> Result<Self, ToolkitError> {
+        let data = fs::read(catalog_path(wows_dir, build, locale))?;
+
+        Self::parse(locale.to_owned(), &data)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .binary_search_by(|(entry_key, _)| entry_key.as_str().cmp(key))
+            .ok()
+            .map(|i| self.entries[i].1.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn catalog_path(wows_dir: &Path, build: usize, locale: &str) -> PathBuf {
+    wows_dir.join(format!("bin/{}/res/texts/{}/LC_MESSAGES/global.mo", build, locale))
+}
+
+/// Locales which have a `global.mo` for the given build
+pub fn available_locales(wows_dir: &Path, build: usize) -> Vec<String> {
+    let Ok(dirs) = fs::read_dir(wows_dir.join(format!("bin/{}/res/texts", build))) else {
+        return Vec::new();
+    };
+
+    let mut locales: Vec<String> = dirs
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_owned))
+        .filter(|locale| catalog_path(wows_dir, build, locale).exists())
+        .collect();
+    locales.sort();
+
+    locales
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalogExportFormat {
+    Json,
+    Csv,
+    Po,
+}
+
+impl CatalogExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            CatalogExportFormat::Json => "json",
+            CatalogExportFormat::Csv => "csv",
+            CatalogExportFormat::Po => "po",
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn po_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n");
+
+    format!("\"{}\"", escaped)
+}
+
+/// Writes `keys` from `primary` (and `secondary`, if comparing) to `path`. PO
+/// files can only hold one translation per key, so a compared locale is written
+/// as a translator comment.
+pub fn export_catalog(path: &Path, format: CatalogExportFormat, keys: &[&str], primary: &LocaleCatalog, secondary: Option<&LocaleCatalog>) -> Result<(), ToolkitError> {
+    let mut file = File::create(path)?;
+
+    match format {
+        CatalogExportFormat::Json => {
+            let mut json = Map::new();
+            for key in keys {
+                let text = primary.get(key).unwrap_or_default();
+                let value = match secondary {
+                    Some(secondary) => {
+                        let mut locales = Map::new();
+                        locales.insert(primary.locale.clone(), Value::String(text.to_owned()));
+                        locales.insert(
+                            secondary.locale.clone(),
+                            secondary.get(key).map(|text| Value::String(text.to_owned())).unwrap_or(Value::Null),
+                        );
+                        Value::Object(locales)
+                    }
+                    None => Value::String(text.to_owned()),
+                };
+                json.insert((*key).to_owned(), value);
+            }
+            serde_json::to_writer_pretty(&mut file, &json).map_err(std::io::Error::from)?;
+        }
+        CatalogExportFormat::Csv => {
+            match secondary {
+                Some(secondary) => writeln!(file, "key,{},{}", csv_field(&primary.locale), csv_field(&secondary.locale))?,
+                None => writeln!(file, "key,{}", csv_field(&primary.locale))?,
+            }
+            for key in keys {
+                let text = csv_field(primary.get(key).unwrap_or_default());
+                match secondary {
+                    Some(secondary) => writeln!(file, "{},{},{}", csv_field(key), text, csv_field(secondary.get(key).unwrap_or_default()))?,
+                    None => writeln!(file, "{},{}", csv_field(key), text)?,
+                }
+            }
+        }
+        CatalogExportFormat::Po => {
+            writeln!(file, "msgid \"\"")?;
+            writeln!(file, "msgstr \"\"")?;
+            writeln!(file, "{}", po_string(&format!("Language: {}\n", primary.locale)))?;
+            writeln!(file, "{}", po_string("Content-Type: text/plain; charset=UTF-8\n"))?;
+            for key in keys {
+                writeln!(file)?;
+                if let Some(text) = secondary.and_then(|secondary| secondary.get(key)) {
+                    for line in text.lines() {
+                        writeln!(file, "# {}", line)?;
+                    }
+                }
+                writeln!(file, "msgid {}", po_string(key))?;
+                writeln!(file, "msgstr {}", po_string(primary.get(key).unwrap_or_default()))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct LocalizationTabState {
+    pub search: String,
+    pub primary_locale: Option<String>,
+    pub compare_locale: Option<String>,
+    /// Build and available locales for the currently loaded game data
+    locales: Option<(usize, Vec<String>)>,
+    catalogs: HashMap<String, Arc<LocaleCatalog>>,
+    /// Search, locales and matching keys from the last time the filter was applied
+    filtered: Option<(String, Option<String>, Option<String>, Vec<usize>)>,
+}
+
+impl LocalizationTabState {
+    fn catalog(&mut self, wows_dir: &Path, build: usize, locale: &str) -> Result<Arc<LocaleCatalog>, ToolkitError> {
+        if let Some(catalog) = self.catalogs.get(locale) {
+            return Ok(Arc::clone(catalog));
+        }
+
+        let catalog = Arc::new(LocaleCatalog::load(wows_dir, build, locale)?);
+        self.catalogs.insert(locale.to_owned(), Arc::clone(&catalog));
+
+        Ok(catalog)
+    }
+
+    fn filter(&mut self, primary: &LocaleCatalog, secondary: Option<&LocaleCatalog>) -> &[usize] {
+        let is_current = self
+            .filtered
+            .as_ref()
+            .map(|(search, primary_locale, compare_locale, _)| search == &self.search && primary_locale == &self.primary_locale && compare_locale == &self.compare_locale)
+            .unwrap_or(false);
+
+        if !is_current {
+            let query = self.search.to_lowercase();
+            let matches = primary
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(_, (key, text))| {
+                    query.is_empty()
+                        || key.to_lowercase().contains(&query)
+                        || text.to_lowercase().contains(&query)
+                        || secondary
+                            .and_then(|secondary| secondary.get(key))
+                            .map(|text| text.to_lowercase().contains(&query))
+                            .unwrap_or(false)
+                })
+                .map(|(i, _)| i)
+                .collect();
+
+            self.filtered = Some((self.search.clone(), self.primary_locale.clone(), self.compare_locale.clone(), matches));
+        }
+
+        self.filtered.as_ref().map(|(_, _, _, matches)| matches.as_slice()).unwrap_or_default()
+    }
+}
+
+fn locale_combo(ui: &mut egui::Ui, id: &str, selected: &mut Option<String>, locales: &[String], allow_none: bool) {
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(selected.as_deref().unwrap_or("None"))
+        .show_ui(ui, |ui| {
+            if allow_none {
+                ui.selectable_value(selected, None, "None");
+            }
+            for locale in locales {
+                ui.selectable_value(selected, Some(locale.clone()), locale.as_str());
+            }
+        });
+}
+
+impl ToolkitTabViewer<'_> {
+    /// Builds the localization browser tab
+    pub fn build_localization_tab(&mut self, ui: &mut egui::Ui) {
+        let Some(build) = self.tab_state.world_of_warships_data.as_ref().map(|wows_data| wows_data.read().game_version) else {
+            ui.label("Game data has not been loaded. Set your World of Warships directory in the settings tab.");
+            return;
+        };
+        let wows_dir = PathBuf::from(&self.tab_state.settings.wows_dir);
+        let default_locale = self.tab_state.settings.locale.clone();
+
+        let state = &mut self.tab_state.localization_tab;
+        if state.locales.as_ref().map(|(locales_build, _)| *locales_build != build).unwrap_or(true) {
+            // A different build was loaded, so any parsed catalogs are stale
+            *state = LocalizationTabState {
+                search: std::mem::take(&mut state.search),
+                locales: Some((build, available_locales(&wows_dir, build))),
+                ..Default::default()
+            };
+        }
+        let locales = state.locales.as_ref().map(|(_, locales)| locales.clone()).unwrap_or_default();
+        if state.primary_locale.is_none() {
+            state.primary_locale = default_locale.filter(|locale| locales.contains(locale)).or_else(|| locales.first().cloned());
+        }
+
+        let mut export = None;
+        ui.horizontal(|ui| {
+            ui.label("Locale");
+            locale_combo(ui, "localization_primary_locale", &mut state.primary_locale, &locales, false);
+            ui.label("Compare with");
+            locale_combo(ui, "localization_compare_locale", &mut state.compare_locale, &locales, true);
+            ui.add(egui::TextEdit::singleline(&mut state.search).hint_text("Search keys or text"));
+            ui.menu_button(format!("{} Export", icons::EXPORT), |ui| {
+                for (format, label) in [
+                    (CatalogExportFormat::Json, "As JSON"),
+                    (CatalogExportFormat::Csv, "As CSV"),
+                    (CatalogExportFormat::Po, "As PO"),
+                ] {
+                    if ui.small_button(label).clicked() {
+                        export = Some(format);
+                        ui.close_menu();
+                    }
+                }
+            });
+        });
+        ui.separator();
+
+        let Some(primary_locale) = state.primary_locale.clone() else {
+            ui.label("No localization catalogs were found for this build");
+            return;
+        };
+        let primary = match state.catalog(&wows_dir, build, &primary_locale) {
+            Ok(catalog) => catalog,
+            Err(e) => {
+                ui.label(format!("Could not load the {} catalog: {}", primary_locale, e));
+                return;
+            }
+        };
+        let secondary = match state.compare_locale.clone().map(|locale| state.catalog(&wows_dir, build, &locale)).transpose() {
+            Ok(catalog) => catalog,
+            Err(e) => {
+                ui.label(format!("Could not load the compared catalog: {}", e));
+                None
+            }
+        };
+
+        let matches = state.filter(&primary, secondary.as_deref());
+        ui.label(format!("{} of {} strings", matches.len(), primary.len()));
+
+        if let Some(format) = export {
+            let file_name = format!("global_{}.{}", primary.locale, format.extension());
+            if let Some(path) = rfd::FileDialog::new().set_file_name(file_name).save_file() {
+                let keys: Vec<&str> = matches.iter().map(|i| primary.entries[*i].0.as_str()).collect();
+                let message = match export_catalog(&path, format, &keys, &primary, secondary.as_deref()) {
+                    Ok(()) => format!("{} Exported {} strings to {}", icons::CHECK_CIRCLE, keys.len(), path.display()),
+                    Err(e) => format!("{} Failed to export strings: {}", icons::WARNING, e),
+                };
+                *self.tab_state.timed_message.write() = Some(TimedMessage::new(message));
+            }
+        }
+
+        let mut table = TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::initial(250.0).clip(true));
+        table = match secondary {
+            Some(_) => table.column(Column::initial(400.0).clip(true)).column(Column::remainder().clip(true)),
+            None => table.column(Column::remainder().clip(true)),
+        };
+
+        table
+            .min_scrolled_height(0.0)
+            .id_salt("localization_table")
+            .header(20.0, |mut header| {
+                header.col(|ui| {
+                    ui.strong("Key");
+                });
+                header.col(|ui| {
+                    ui.strong(primary.locale.as_str());
+                });
+                if let Some(secondary) = secondary.as_ref() {
+                    header.col(|ui| {
+                        ui.strong(secondary.locale.as_str());
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(20.0, matches.len(), |mut row| {
+                    let (key, text) = &primary.entries[matches[row.index()]];
+                    row.col(|ui| {
+                        if ui
+                            .add(egui::Label::new(key.as_str()).sense(egui::Sense::click()))
+                            .on_hover_text("Click to copy")
+                            .clicked()
+                        {
+                            ui.output_mut(|output| output.copied_text = key.clone());
+                        }
+                    });
+                    row.col(|ui| {
+                        ui.label(text.as_str()).on_hover_text(text.as_str());
+                    });
+                    if let Some(secondary) = secondary.as_ref() {
+                        row.col(|ui| match secondary.get(key) {
+                            Some(text) => {
+                                ui.label(text).on_hover_text(text);
+                            }
+                            None => {
+                                ui.weak("Missing");
+                            }
+                        });
+                    }
+                });
+            });
+    }
+}
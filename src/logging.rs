@@ -0,0 +1,233 @@
+//! Log files, the in-memory log shown in the Log tab, and diagnostics bundles for bug reports.
+//!
+//! `main` installs a rolling file appender writing to [log_dir] alongside a
+//! [MemoryLogLayer], which keeps the most recent records for the Log tab.
+
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Write as _},
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+use egui::{Color32, RichText};
+use parking_lot::Mutex;
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    app::{Settings, TimedMessage, ToolkitTabViewer},
+    error::ToolkitError,
+    icons,
+};
+
+/// How many records the Log tab keeps around
+const MAX_LOG_RECORDS: usize = 5000;
+
+static LOG_RECORDS: Mutex<VecDeque<LogRecord>> = parking_lot::const_mutex(VecDeque::new());
+
+/// Settings which are secret and never belong in a bug report
+const REDACTED_SETTINGS: [&str; 1] = ["twitch_token"];
+
+#[derive(Clone)]
+pub struct LogRecord {
+    pub timestamp: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+pub fn log_dir() -> PathBuf {
+    if let Some(storage_dir) = eframe::storage_dir(crate::APP_NAME) {
+        storage_dir.join("logs")
+    } else {
+        PathBuf::from("logs")
+    }
+}
+
+/// Collects an event's message followed by its other fields as `name=value`
+struct MessageVisitor<'a> {
+    message: &'a mut String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// A [Layer] which keeps the most recent log records in memory for the Log tab
+pub struct MemoryLogLayer;
+
+impl<S: Subscriber> Layer<S> for MemoryLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut message = String::new();
+        let mut visitor = MessageVisitor {
+            message: &mut message,
+            fields: String::new(),
+        };
+        event.record(&mut visitor);
+        let fields = visitor.fields;
+        message.push_str(&fields);
+
+        let record = LogRecord {
+            timestamp: Local::now(),
+            level: *metadata.level(),
+            target: metadata.target().to_owned(),
+            message,
+        };
+
+        let mut records = LOG_RECORDS.lock();
+        if records.len() == MAX_LOG_RECORDS {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+}
+
+/// Returns the in-memory records at or above `level` whose target or message contains `search`
+fn matching_records(level: Level, search: &str) -> Vec<LogRecord> {
+    let search = search.to_lowercase();
+    // Cloned so that nothing logged while the tab is drawn has to wait on the lock
+    LOG_RECORDS
+        .lock()
+        .iter()
+        .filter(|record| record.level <= level)
+        .filter(|record| search.is_empty() || record.message.to_lowercase().contains(&search) || record.target.to_lowercase().contains(&search))
+        .cloned()
+        .collect()
+}
+
+fn level_color(level: Level) -> Option<Color32> {
+    match level {
+        Level::ERROR => Some(Color32::LIGHT_RED),
+        Level::WARN => Some(Color32::GOLD),
+        Level::DEBUG | Level::TRACE => Some(Color32::GRAY),
+        Level::INFO => None,
+    }
+}
+
+/// Writes a zip containing the toolkit version, `settings` without [REDACTED_SETTINGS], and every file in [log_dir]
+pub fn write_diagnostics_bundle(path: &Path, settings: &Settings) -> Result<(), ToolkitError> {
+    let mut settings = serde_json::to_value(settings).map_err(ToolkitError::SettingsSerialization)?;
+    if let Some(settings) = settings.as_object_mut() {
+        for key in REDACTED_SETTINGS {
+            settings.remove(key);
+        }
+    }
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("version.txt", options)?;
+    writeln!(zip, "{} v{}", crate::APP_NAME, env!("CARGO_PKG_VERSION"))?;
+    writeln!(zip, "{} {}", std::env::consts::OS, std::env::consts::ARCH)?;
+
+    zip.start_file("settings.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, &settings).map_err(ToolkitError::SettingsSerialization)?;
+
+    if let Ok(entries) = std::fs::read_dir(log_dir()) {
+        for entry in entries.flatten().filter(|entry| entry.file_type().map(|ty| ty.is_file()).unwrap_or(false)) {
+            zip.start_file(format!("logs/{}", entry.file_name().to_string_lossy()), options)?;
+            io::copy(&mut File::open(entry.path())?, &mut zip)?;
+        }
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+pub struct LogTabState {
+    pub level: Level,
+    pub search: String,
+    pub follow: bool,
+}
+
+impl Default for LogTabState {
+    fn default() -> Self {
+        LogTabState {
+            level: Level::INFO,
+            search: String::new(),
+            follow: true,
+        }
+    }
+}
+
+impl ToolkitTabViewer<'_> {
+    /// Builds the log viewer tab
+    pub fn build_log_tab(&mut self, ui: &mut egui::Ui) {
+        let state = &mut self.tab_state.log_tab;
+        let mut save_bundle = false;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("log_level").selected_text(state.level.as_str()).show_ui(ui, |ui| {
+                for level in [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG, Level::TRACE] {
+                    ui.selectable_value(&mut state.level, level, level.as_str());
+                }
+            });
+            ui.add(egui::TextEdit::singleline(&mut state.search).hint_text("Search"));
+            ui.checkbox(&mut state.follow, "Follow").on_hover_text("Keep the newest messages in view");
+            if ui.button(format!("{} Open Log Folder", icons::FOLDER_OPEN)).clicked() {
+                ui.ctx().open_url(egui::OpenUrl::new_tab(format!("file://{}", log_dir().display())));
+            }
+            if ui
+                .button(format!("{} Save Diagnostics Bundle...", icons::FILE_ZIP))
+                .on_hover_text("Packages the logs, toolkit version and settings for a bug report. Your Twitch token is not included.")
+                .clicked()
+            {
+                save_bundle = true;
+            }
+        });
+        ui.separator();
+
+        // Pick up records logged since the last frame
+        ui.ctx().request_repaint_after(Duration::from_millis(500));
+
+        let records = matching_records(state.level, &state.search);
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::both()
+            .id_source("log_scroll_area")
+            .auto_shrink([false, false])
+            .stick_to_bottom(state.follow)
+            .show_rows(ui, row_height, records.len(), |ui, range| {
+                for record in &records[range] {
+                    let text = format!(
+                        "{} {:>5} {}: {}",
+                        record.timestamp.format("%H:%M:%S%.3f"),
+                        record.level.as_str(),
+                        record.target,
+                        record.message
+                    );
+                    let mut text = RichText::new(text).monospace();
+                    if let Some(color) = level_color(record.level) {
+                        text = text.color(color);
+                    }
+                    ui.add(egui::Label::new(text).extend());
+                }
+            });
+
+        if save_bundle {
+            let file_name = format!("wows_toolkit_diagnostics_{}.zip", Local::now().format("%Y%m%d_%H%M%S"));
+            if let Some(path) = rfd::FileDialog::new().set_file_name(file_name).add_filter("Zip Archive", &["zip"]).save_file() {
+                let message = match write_diagnostics_bundle(&path, &self.tab_state.settings) {
+                    Ok(()) => format!("{} Saved diagnostics to {}", icons::CHECK_CIRCLE, path.display()),
+                    Err(e) => format!("{} Could not save diagnostics: {}", icons::WARNING, e),
+                };
+                *self.tab_state.timed_message.write() = Some(TimedMessage::new(message));
+            }
+        }
+    }
+}
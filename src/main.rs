@@ -25,39 +25,35 @@ fn main() -> eframe::Result<()> {
 
     use egui::{Style, Visuals};
 
-    // use tracing::level_filters::LevelFilter;
-    // use tracing_appender::rolling::Rotation;
-    // use tracing_subscriber::{
-    //     fmt::{self, time::LocalTime},
-    //     layer::SubscriberExt,
-    //     Layer,
-    // };
+    use tracing_appender::rolling::Rotation;
+    use tracing_subscriber::{
+        fmt::{self, time::LocalTime},
+        layer::SubscriberExt,
+        EnvFilter,
+    };
+
+    // Logging to a file is best-effort; the in-memory log still works without it
+    let file_appender = tracing_appender::rolling::Builder::new()
+        .rotation(Rotation::DAILY)
+        .max_log_files(7)
+        .filename_prefix("wows_toolkit")
+        .filename_suffix("log")
+        .build(wows_toolkit::log_dir())
+        .ok();
+    let (file_writer, _log_guard) = file_appender.map(tracing_appender::non_blocking).unzip();
 
-    // let file_appender = tracing_appender::rolling::Builder::new()
-    //     .rotation(Rotation::HOURLY)
-    //     .max_log_files(1)
-    //     .filename_prefix("wows_toolkit.log")
-    //     .build(".")
-    //     .expect("failed to build file appender");
-    // let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-    // let subscriber = tracing_subscriber::registry()
-    //     .with(
-    //         fmt::Layer::new()
-    //             .pretty()
-    //             // .with_writer(std::io::stdout)
-    //             // .with_timer(LocalTime::rfc_3339())
-    //             .fmt_fields(NewType(Pretty::default()))
-    //             .with_ansi(true)
-    //             .with_filter(LevelFilter::DEBUG),
-    //     )
-    //     .with(
-    //         fmt::Layer::new()
-    //             .with_writer(non_blocking)
-    //             .with_timer(LocalTime::rfc_3339())
-    //             .with_ansi(false)
-    //             .with_filter(LevelFilter::DEBUG),
-    //     );
-    // tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+    let subscriber = tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info,wows_toolkit=debug")))
+        .with(cfg!(debug_assertions).then(|| {
+            fmt::Layer::new()
+                .pretty()
+                .with_writer(std::io::stdout)
+                .fmt_fields(NewType(Pretty::default()))
+                .with_ansi(true)
+        }))
+        .with(file_writer.map(|writer| fmt::Layer::new().with_writer(writer).with_timer(LocalTime::rfc_3339()).with_ansi(false)))
+        .with(wows_toolkit::MemoryLogLayer);
+    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
     let icon_data: &[u8] = &include_bytes!("../assets/wows_toolkit.png")[..];
 
@@ -0,0 +1,203 @@
+//! Decoder for BigWorld "packed section" binary XML.
+//!
+//! A packed file starts with [PACKED_XML_MAGIC] and a version byte, followed by
+//! a dictionary of NUL-terminated element names which ends with an empty name.
+//! The root element follows. Each element is:
+//!
+//! - a `u16` child count,
+//! - a data descriptor for the element's own value,
+//! - a `(u16 name index, data descriptor)` pair per child,
+//! - the data of the element followed by that of each child.
+//!
+//! A data descriptor is a `u32` whose low 28 bits are the end offset of the data
+//! (relative to the start of the element's data) and whose high 4 bits are its type.
+
+use crate::error::ToolkitError;
+
+pub const PACKED_XML_MAGIC: u32 = 0x62A14E45;
+
+const TYPE_ELEMENT: u32 = 0;
+const TYPE_STRING: u32 = 1;
+const TYPE_INT: u32 = 2;
+const TYPE_FLOATS: u32 = 3;
+const TYPE_BOOL: u32 = 4;
+const TYPE_BLOB: u32 = 5;
+
+/// Packed sections nest deeply for spaces, but never anywhere near this
+const MAX_DEPTH: usize = 256;
+
+pub fn is_packed_xml(data: &[u8]) -> bool {
+    data.get(..4)
+        .map(|magic| u32::from_le_bytes(magic.try_into().unwrap()) == PACKED_XML_MAGIC)
+        .unwrap_or(false)
+}
+
+struct DataDescriptor {
+    end: usize,
+    ty: u32,
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    names: Vec<&'a str>,
+    out: String,
+}
+
+fn invalid(reason: &'static str) -> ToolkitError {
+    ToolkitError::InvalidPackedXml(reason)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl<'a> Decoder<'a> {
+    fn bytes(&self, start: usize, len: usize) -> Result<&'a [u8], ToolkitError> {
+        let data: &'a [u8] = self.data;
+        data.get(start..start + len).ok_or_else(|| invalid("unexpected end of data"))
+    }
+
+    fn u16(&self, pos: usize) -> Result<u16, ToolkitError> {
+        Ok(u16::from_le_bytes(self.bytes(pos, 2)?.try_into().unwrap()))
+    }
+
+    fn u32(&self, pos: usize) -> Result<u32, ToolkitError> {
+        Ok(u32::from_le_bytes(self.bytes(pos, 4)?.try_into().unwrap()))
+    }
+
+    fn descriptor(&self, pos: usize) -> Result<DataDescriptor, ToolkitError> {
+        let raw = self.u32(pos)?;
+        Ok(DataDescriptor {
+            end: (raw & 0x0FFF_FFFF) as usize,
+            ty: raw >> 28,
+        })
+    }
+
+    /// Reads the name dictionary starting at `pos`, returning the position after it
+    fn read_dictionary(&mut self, mut pos: usize) -> Result<usize, ToolkitError> {
+        loop {
+            let remaining = self.data.get(pos..).ok_or_else(|| invalid("unterminated dictionary"))?;
+            let len = remaining.iter().position(|b| *b == 0).ok_or_else(|| invalid("unterminated dictionary"))?;
+            let name = std::str::from_utf8(&self.data[pos..pos + len]).map_err(|_| invalid("element name is not UTF-8"))?;
+            pos += len + 1;
+            if name.is_empty() {
+                return Ok(pos);
+            }
+            self.names.push(name);
+        }
+    }
+
+    fn value_text(&self, data: &[u8], ty: u32) -> Result<String, ToolkitError> {
+        Ok(match ty {
+            TYPE_STRING => escape(&String::from_utf8_lossy(data)),
+            TYPE_INT => match data.len() {
+                0 => "0".to_owned(),
+                1 => (data[0] as i8).to_string(),
+                2 => i16::from_le_bytes(data.try_into().unwrap()).to_string(),
+                4 => i32::from_le_bytes(data.try_into().unwrap()).to_string(),
+                8 => i64::from_le_bytes(data.try_into().unwrap()).to_string(),
+                _ => return Err(invalid("unexpected integer size")),
+            },
+            TYPE_FLOATS => {
+                if data.len() % 4 != 0 {
+                    return Err(invalid("unexpected float array size"));
+                }
+                data.chunks_exact(4)
+                    .map(|float| f32::from_le_bytes(float.try_into().unwrap()).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            TYPE_BOOL => (data.first() == Some(&1)).to_string(),
+            TYPE_BLOB => data_encoding::BASE64.encode(data),
+            _ => return Err(invalid("unknown data type")),
+        })
+    }
+
+    /// Decodes the element at `pos` and writes it as `name`
+    fn write_element(&mut self, pos: usize, name: &str, depth: usize) -> Result<(), ToolkitError> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("elements are nested too deeply"));
+        }
+
+        let child_count = self.u16(pos)? as usize;
+        let own = self.descriptor(pos + 2)?;
+        let mut children = Vec::with_capacity(child_count);
+        for i in 0..child_count {
+            let child_pos = pos + 6 + i * 6;
+            let name_index = self.u16(child_pos)? as usize;
+            let child_name = *self.names.get(name_index).ok_or_else(|| invalid("element name out of range"))?;
+            children.push((child_name, self.descriptor(child_pos + 2)?));
+        }
+        let data_start = pos + 6 + child_count * 6;
+
+        let indent = "\t".repeat(depth);
+        self.out.push_str(&indent);
+        self.out.push_str(&format!("<{}>", name));
+
+        if own.ty != TYPE_ELEMENT {
+            let text = self.value_text(self.bytes(data_start, own.end)?, own.ty)?;
+            self.out.push_str(&text);
+        }
+
+        if !children.is_empty() {
+            self.out.push('\n');
+            let mut offset = own.end;
+            for (child_name, descriptor) in children {
+                let end = descriptor.end;
+                if end < offset {
+                    return Err(invalid("child data overlaps its sibling"));
+                }
+                self.write_value(data_start + offset, end - offset, child_name, &descriptor, depth + 1)?;
+                offset = end;
+            }
+            self.out.push_str(&indent);
+        }
+
+        self.out.push_str(&format!("</{}>\n", name));
+
+        Ok(())
+    }
+
+    fn write_value(&mut self, pos: usize, len: usize, name: &str, descriptor: &DataDescriptor, depth: usize) -> Result<(), ToolkitError> {
+        if descriptor.ty == TYPE_ELEMENT {
+            return self.write_element(pos, name, depth);
+        }
+
+        let data = self.bytes(pos, len)?;
+        let indent = "\t".repeat(depth);
+        // 12 floats are a transform matrix, which reads better as one row per line
+        if descriptor.ty == TYPE_FLOATS && len == 48 {
+            self.out.push_str(&format!("{}<{}>\n", indent, name));
+            for (i, row) in data.chunks_exact(12).enumerate() {
+                let row = self.value_text(row, TYPE_FLOATS)?;
+                self.out.push_str(&format!("{}\t<row{}>{}</row{}>\n", indent, i, row, i));
+            }
+            self.out.push_str(&format!("{}</{}>\n", indent, name));
+        } else {
+            let text = self.value_text(data, descriptor.ty)?;
+            self.out.push_str(&format!("{}<{}>{}</{}>\n", indent, name, text, name));
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes a packed section into XML text. The format doesn't store a name for
+/// the root element, so `root_name` (usually the file name) is used instead.
+pub fn decode(data: &[u8], root_name: &str) -> Result<String, ToolkitError> {
+    if !is_packed_xml(data) {
+        return Err(invalid("missing packed section header"));
+    }
+
+    let mut decoder = Decoder {
+        data,
+        names: Vec::new(),
+        out: String::with_capacity(data.len() * 2),
+    };
+
+    // Skip the magic and the version byte
+    let root_pos = decoder.read_dictionary(5)?;
+    decoder.write_element(root_pos, root_name, 0)?;
+
+    Ok(decoder.out)
+}
@@ -1,13 +1,360 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+use std::{
+    io::{Read, Seek, SeekFrom},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-use egui::{mutex::Mutex, Image, ImageSource, TextEdit, ViewportBuilder};
+use egui::{mutex::Mutex, text::LayoutJob, Color32, FontId, Image, ImageSource, TextEdit, TextFormat, TextStyle, ViewportBuilder};
+use tempfile::NamedTempFile;
+
+/// Files larger than this are viewed from a temporary file on disk instead of memory
+pub const LARGE_BINARY_FILE_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+const HEX_BYTES_PER_ROW: usize = 16;
+const MAX_SEARCH_RESULTS: usize = 10_000;
+const SEARCH_CHUNK_SIZE: usize = 1024 * 1024;
 
 pub enum FileType {
-    PlainTextFile { ext: String, contents: String },
-    Image { ext: String, contents: Vec<u8> },
+    PlainTextFile {
+        ext: String,
+        contents: String,
+    },
+    Image {
+        ext: String,
+        contents: Vec<u8>,
+    },
+    Binary(HexViewer),
+    /// The file is still being read on a background thread
+    Loading,
+    Failed(String),
+}
+
+/// Backing storage for the hex viewer
+pub enum BinarySource {
+    Memory(Vec<u8>),
+    /// The file was too large to comfortably keep in memory and was written
+    /// to a temporary file which is read a page at a time. The file is deleted
+    /// when the source is dropped.
+    TempFile {
+        file: Mutex<NamedTempFile>,
+        len: u64,
+    },
+}
+
+impl BinarySource {
+    pub fn temp_file(file: NamedTempFile) -> std::io::Result<Self> {
+        let len = file.as_file().metadata()?.len();
+
+        Ok(BinarySource::TempFile { file: Mutex::new(file), len })
+    }
+
+    pub fn len(&self) -> u64 {
+        match self {
+            BinarySource::Memory(data) => data.len() as u64,
+            BinarySource::TempFile { len, .. } => *len,
+        }
+    }
+
+    /// Reads up to `buf.len()` bytes at `offset`, returning the number of bytes read
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+        match self {
+            BinarySource::Memory(data) => {
+                let start = (offset as usize).min(data.len());
+                let end = (start + buf.len()).min(data.len());
+                buf[..end - start].copy_from_slice(&data[start..end]);
+                end - start
+            }
+            BinarySource::TempFile { file, .. } => {
+                let mut file = file.lock();
+                if file.seek(SeekFrom::Start(offset)).is_err() {
+                    return 0;
+                }
+
+                let mut read = 0;
+                while read < buf.len() {
+                    match file.read(&mut buf[read..]) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => read += n,
+                    }
+                }
+                read
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct SearchResults {
+    matches: Vec<u64>,
+    running: bool,
+    truncated: bool,
+}
+
+pub struct HexViewer {
+    source: Arc<BinarySource>,
+    goto_offset: String,
+    scroll_to_offset: Option<u64>,
+    search_pattern: String,
+    search_error: Option<String>,
+    search_results: Arc<Mutex<SearchResults>>,
+    search_cancel: Arc<AtomicBool>,
+    current_match: Option<usize>,
+    highlight: Option<(u64, usize)>,
+}
+
+/// Parses a search pattern. Patterns wrapped in quotes are searched for as ASCII text,
+/// anything else is treated as hex bytes where `??` matches any byte.
+fn parse_search_pattern(pattern: &str) -> Result<Vec<Option<u8>>, String> {
+    let pattern = pattern.trim();
+    if pattern.len() >= 2 && pattern.starts_with('"') && pattern.ends_with('"') {
+        return Ok(pattern[1..pattern.len() - 1].bytes().map(Some).collect());
+    }
+
+    let digits: String = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return Err("Hex patterns must contain an even number of digits".to_owned());
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            let byte = &digits[i..i + 2];
+            if byte == "??" {
+                Ok(None)
+            } else {
+                u8::from_str_radix(byte, 16).map(Some).map_err(|_| format!("Invalid hex byte {:?}", byte))
+            }
+        })
+        .collect()
+}
+
+fn parse_offset(offset: &str) -> Option<u64> {
+    let offset = offset.trim();
+    if let Some(hex) = offset.strip_prefix("0x").or_else(|| offset.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        offset.parse().ok()
+    }
+}
+
+fn search_source(source: &BinarySource, pattern: &[Option<u8>], cancel: &AtomicBool, results: &Mutex<SearchResults>) {
+    let len = source.len();
+    let overlap = pattern.len().saturating_sub(1);
+    let mut chunk = vec![0u8; SEARCH_CHUNK_SIZE + overlap];
+    let mut offset = 0u64;
+
+    while offset < len && !cancel.load(Ordering::Relaxed) {
+        let read = source.read_at(offset, &mut chunk);
+        if read < pattern.len() {
+            break;
+        }
+
+        for start in 0..=(read - pattern.len()) {
+            let is_match = pattern
+                .iter()
+                .zip(&chunk[start..])
+                .all(|(expected, actual)| expected.map(|expected| expected == *actual).unwrap_or(true));
+            if is_match {
+                let mut results = results.lock();
+                if results.matches.len() >= MAX_SEARCH_RESULTS {
+                    results.truncated = true;
+                    return;
+                }
+                results.matches.push(offset + start as u64);
+            }
+        }
+
+        offset += SEARCH_CHUNK_SIZE as u64;
+    }
+}
+
+impl HexViewer {
+    pub fn new(source: BinarySource) -> Self {
+        HexViewer {
+            source: Arc::new(source),
+            goto_offset: String::new(),
+            scroll_to_offset: None,
+            search_pattern: String::new(),
+            search_error: None,
+            search_results: Default::default(),
+            search_cancel: Arc::new(AtomicBool::new(false)),
+            current_match: None,
+            highlight: None,
+        }
+    }
+
+    fn start_search(&mut self) {
+        // Stop any search which is already running
+        self.search_cancel.store(true, Ordering::Relaxed);
+        self.current_match = None;
+        self.highlight = None;
+
+        let pattern = match parse_search_pattern(&self.search_pattern) {
+            Ok(pattern) if !pattern.is_empty() => pattern,
+            Ok(_) => return,
+            Err(e) => {
+                self.search_error = Some(e);
+                return;
+            }
+        };
+        self.search_error = None;
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let results = Arc::new(Mutex::new(SearchResults {
+            running: true,
+            ..Default::default()
+        }));
+        self.search_cancel = Arc::clone(&cancel);
+        self.search_results = Arc::clone(&results);
+
+        let source = Arc::clone(&self.source);
+        std::thread::spawn(move || {
+            search_source(&source, &pattern, &cancel, &results);
+            results.lock().running = false;
+        });
+    }
+
+    fn select_match(&mut self, idx: usize) {
+        let pattern_len = parse_search_pattern(&self.search_pattern).map(|pattern| pattern.len()).unwrap_or(1);
+        if let Some(offset) = self.search_results.lock().matches.get(idx).copied() {
+            self.current_match = Some(idx);
+            self.highlight = Some((offset, pattern_len));
+            self.scroll_to_offset = Some(offset);
+        }
+    }
+
+    fn build_toolbar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("{} bytes", self.source.len()));
+            ui.separator();
+
+            let response = ui.add(TextEdit::singleline(&mut self.goto_offset).hint_text("Offset (0x...)").desired_width(120.0));
+            if (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))) || ui.button("Go").clicked() {
+                if let Some(offset) = parse_offset(&self.goto_offset) {
+                    let offset = offset.min(self.source.len().saturating_sub(1));
+                    self.highlight = Some((offset, 1));
+                    self.scroll_to_offset = Some(offset);
+                }
+            }
+            ui.separator();
+
+            let response = ui.add(
+                TextEdit::singleline(&mut self.search_pattern)
+                    .hint_text("DE AD ?? EF or \"text\"")
+                    .desired_width(200.0),
+            );
+            if (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))) || ui.button("Search").clicked() {
+                self.start_search();
+            }
+
+            let (match_count, running, truncated) = {
+                let results = self.search_results.lock();
+                (results.matches.len(), results.running, results.truncated)
+            };
+
+            if running {
+                ui.spinner();
+                if ui.button("Stop").clicked() {
+                    self.search_cancel.store(true, Ordering::Relaxed);
+                }
+            }
+
+            if match_count > 0 {
+                if ui.button("Previous").clicked() {
+                    let idx = self
+                        .current_match
+                        .map(|idx| if idx == 0 { match_count - 1 } else { idx - 1 })
+                        .unwrap_or(match_count - 1);
+                    self.select_match(idx);
+                }
+                if ui.button("Next").clicked() {
+                    let idx = self.current_match.map(|idx| (idx + 1) % match_count).unwrap_or(0);
+                    self.select_match(idx);
+                }
+                let current = self.current_match.map(|idx| (idx + 1).to_string()).unwrap_or_else(|| "-".to_owned());
+                ui.label(format!("{}/{}{}", current, match_count, if truncated { "+" } else { "" }));
+            } else if !running && !self.search_pattern.is_empty() && self.search_error.is_none() {
+                ui.label("No matches");
+            }
+
+            if let Some(error) = &self.search_error {
+                ui.colored_label(Color32::LIGHT_RED, error);
+            }
+        });
+    }
+
+    fn build_row(&self, offset: u64, bytes: &[u8], font_id: &FontId, text_color: Color32) -> LayoutJob {
+        let normal = TextFormat {
+            font_id: font_id.clone(),
+            color: text_color,
+            ..Default::default()
+        };
+        let highlighted = TextFormat {
+            background: Color32::from_rgb(0x80, 0x60, 0x00),
+            ..normal.clone()
+        };
+        let is_highlighted = |byte_offset: u64| {
+            self.highlight
+                .map(|(start, len)| byte_offset >= start && byte_offset < start + len as u64)
+                .unwrap_or(false)
+        };
+
+        let mut job = LayoutJob::default();
+        job.append(&format!("{:08X}  ", offset), 0.0, normal.clone());
+
+        for i in 0..HEX_BYTES_PER_ROW {
+            let text = bytes.get(i).map(|byte| format!("{:02X}", byte)).unwrap_or_else(|| "  ".to_owned());
+            let format = if is_highlighted(offset + i as u64) { &highlighted } else { &normal };
+            job.append(&text, 0.0, format.clone());
+            job.append(if i == 7 { "  " } else { " " }, 0.0, normal.clone());
+        }
+
+        job.append(" ", 0.0, normal.clone());
+        for (i, byte) in bytes.iter().enumerate() {
+            let c = if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' };
+            let format = if is_highlighted(offset + i as u64) { &highlighted } else { &normal };
+            job.append(&c.to_string(), 0.0, format.clone());
+        }
+
+        job
+    }
+
+    pub fn draw(&mut self, ui: &mut egui::Ui) {
+        self.build_toolbar(ui);
+        ui.separator();
+
+        let font_id = TextStyle::Monospace.resolve(ui.style());
+        let text_color = ui.visuals().text_color();
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        let total_rows = self.source.len().div_ceil(HEX_BYTES_PER_ROW as u64) as usize;
+
+        let mut scroll_area = egui::ScrollArea::vertical().auto_shrink([false, false]);
+        if let Some(offset) = self.scroll_to_offset.take() {
+            let row = offset / HEX_BYTES_PER_ROW as u64;
+            scroll_area = scroll_area.vertical_scroll_offset(row as f32 * (row_height + ui.spacing().item_spacing.y));
+        }
+
+        scroll_area.show_rows(ui, row_height, total_rows, |ui, row_range| {
+            // Only read the rows which are currently visible
+            let start = (row_range.start * HEX_BYTES_PER_ROW) as u64;
+            let mut page = vec![0u8; row_range.len() * HEX_BYTES_PER_ROW];
+            let read = self.source.read_at(start, &mut page);
+            page.truncate(read);
+
+            for (i, row_bytes) in page.chunks(HEX_BYTES_PER_ROW).enumerate() {
+                let offset = start + (i * HEX_BYTES_PER_ROW) as u64;
+                ui.label(self.build_row(offset, row_bytes, &font_id, text_color));
+            }
+        });
+    }
+}
+
+impl Drop for HexViewer {
+    fn drop(&mut self) {
+        self.search_cancel.store(true, Ordering::Relaxed);
+    }
 }
 
 pub struct PlaintextFileViewer {
@@ -48,6 +395,15 @@ impl PlaintextFileViewer {
                         });
                         ui.add(image);
                     }
+                    FileType::Binary(hex_viewer) => {
+                        hex_viewer.draw(ui);
+                    }
+                    FileType::Loading => {
+                        ui.spinner();
+                    }
+                    FileType::Failed(message) => {
+                        ui.label(message.as_str());
+                    }
                 });
                 if ctx.input(|i| i.viewport().close_requested()) {
                     // Tell parent to close us.
@@ -0,0 +1,478 @@
+//! On-disk store for tracked players and the replays which have already been
+//! sent to shipbuilds.com.
+//!
+//! This data used to live in the eframe [Settings](crate::app::Settings) blob,
+//! which is rewritten in full on every save. It's now kept in an SQLite database
+//! next to the settings so that each replay only writes the rows it touches, the
+//! player tracker can query by time range instead of loading every player, and a
+//! crash mid-write rolls back instead of truncating the file.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use chrono::{DateTime, Local};
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::ToolkitError,
+    replay_parser::{replay_timestamp, Replay},
+};
+
+const STORE_FILE_NAME: &str = "player_data.sqlite3";
+
+/// Stored in the database's `user_version`. Bump this and migrate in
+/// [PlayerStore::from_connection] when the schema changes.
+const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS players (
+    db_id INTEGER PRIMARY KEY,
+    last_name TEXT NOT NULL,
+    clan TEXT NOT NULL,
+    clan_id INTEGER NOT NULL,
+    notes TEXT NOT NULL DEFAULT ''
+);
+CREATE TABLE IF NOT EXISTS player_aliases (
+    db_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (db_id, name)
+);
+CREATE TABLE IF NOT EXISTS encounters (
+    db_id INTEGER NOT NULL,
+    arena_id INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    PRIMARY KEY (db_id, arena_id)
+);
+CREATE INDEX IF NOT EXISTS encounters_by_time ON encounters (timestamp, db_id);
+CREATE TABLE IF NOT EXISTS sent_replays (
+    path TEXT PRIMARY KEY
+);
+";
+
+pub fn player_store_path() -> PathBuf {
+    if let Some(storage_dir) = eframe::storage_dir(crate::APP_NAME) {
+        storage_dir.join(STORE_FILE_NAME)
+    } else {
+        PathBuf::from(STORE_FILE_NAME)
+    }
+}
+
+/// A single match a player was seen in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Encounter {
+    pub arena_id: i64,
+    pub timestamp: DateTime<Local>,
+}
+
+/// Everything stored about one player. This is also the layout used when
+/// exporting players.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoredPlayer {
+    pub db_id: i64,
+    pub last_name: String,
+    pub clan: String,
+    pub clan_id: i64,
+    #[serde(default)]
+    pub notes: String,
+    /// Names this player was previously seen with
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub encounters: Vec<Encounter>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlayerOrder {
+    Name,
+    Clan,
+    LastEncountered,
+    Encounters,
+    EncountersInRange,
+}
+
+impl PlayerOrder {
+    fn column(&self) -> &'static str {
+        match self {
+            PlayerOrder::Name => "p.last_name COLLATE NOCASE",
+            PlayerOrder::Clan => "p.clan COLLATE NOCASE",
+            PlayerOrder::LastEncountered => "last_encountered",
+            PlayerOrder::Encounters => "encounters",
+            PlayerOrder::EncountersInRange => "encounters_in_range",
+        }
+    }
+}
+
+pub struct PlayerQuery<'a> {
+    /// Only players encountered after this time are returned
+    pub since: Option<DateTime<Local>>,
+    /// Matched against the player's name, clan and aliases
+    pub filter: &'a str,
+    pub order: PlayerOrder,
+    pub descending: bool,
+}
+
+/// A row in the player tracker table
+#[derive(Debug, Clone)]
+pub struct PlayerSummary {
+    pub db_id: i64,
+    pub last_name: String,
+    pub clan: String,
+    pub aliases: String,
+    pub notes: String,
+    pub encounters: u64,
+    pub encounters_in_range: u64,
+    pub last_encountered: DateTime<Local>,
+}
+
+fn to_timestamp(time: &DateTime<Local>) -> i64 {
+    time.timestamp()
+}
+
+fn from_timestamp(timestamp: i64) -> DateTime<Local> {
+    DateTime::from_timestamp(timestamp, 0).unwrap_or_default().with_timezone(&Local)
+}
+
+fn like_pattern(filter: &str) -> String {
+    let mut pattern = String::with_capacity(filter.len() + 2);
+    pattern.push('%');
+    for c in filter.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Merges `player` into the store. Encounters are combined, and whichever side
+/// saw the player most recently decides their current name and clan.
+fn merge_player(tx: &Transaction<'_>, player: &StoredPlayer) -> Result<(), ToolkitError> {
+    let existing: Option<(String, String, Option<i64>)> = tx
+        .query_row(
+            "SELECT p.last_name, p.notes, (SELECT MAX(e.timestamp) FROM encounters e WHERE e.db_id = p.db_id) FROM players p WHERE p.db_id = ?1",
+            params![player.db_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    let newest_encounter = player.encounters.iter().map(|encounter| to_timestamp(&encounter.timestamp)).max();
+
+    let add_alias = |name: &str| -> Result<(), ToolkitError> {
+        if !name.is_empty() {
+            tx.execute("INSERT OR IGNORE INTO player_aliases (db_id, name) VALUES (?1, ?2)", params![player.db_id, name])?;
+        }
+        Ok(())
+    };
+
+    match existing {
+        None => {
+            tx.execute(
+                "INSERT INTO players (db_id, last_name, clan, clan_id, notes) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![player.db_id, player.last_name, player.clan, player.clan_id, player.notes],
+            )?;
+        }
+        Some((last_name, notes, last_seen)) => {
+            if newest_encounter > last_seen {
+                if last_name != player.last_name {
+                    add_alias(&last_name)?;
+                }
+                tx.execute(
+                    "UPDATE players SET last_name = ?2, clan = ?3, clan_id = ?4 WHERE db_id = ?1",
+                    params![player.db_id, player.last_name, player.clan, player.clan_id],
+                )?;
+            } else if last_name != player.last_name {
+                add_alias(&player.last_name)?;
+            }
+
+            if !player.notes.is_empty() && !notes.contains(player.notes.as_str()) {
+                let notes = if notes.is_empty() {
+                    player.notes.clone()
+                } else {
+                    format!("{}\n\n{}", notes, player.notes)
+                };
+                tx.execute("UPDATE players SET notes = ?2 WHERE db_id = ?1", params![player.db_id, notes])?;
+            }
+        }
+    }
+
+    for alias in &player.aliases {
+        add_alias(alias)?;
+    }
+    // The current name is never an alias
+    tx.execute(
+        "DELETE FROM player_aliases WHERE db_id = ?1 AND name = (SELECT last_name FROM players WHERE db_id = ?1)",
+        params![player.db_id],
+    )?;
+
+    let mut insert_encounter = tx.prepare_cached("INSERT OR IGNORE INTO encounters (db_id, arena_id, timestamp) VALUES (?1, ?2, ?3)")?;
+    for encounter in &player.encounters {
+        insert_encounter.execute(params![player.db_id, encounter.arena_id, to_timestamp(&encounter.timestamp)])?;
+    }
+
+    Ok(())
+}
+
+pub struct PlayerStore {
+    conn: Mutex<Connection>,
+    /// Bumped on every change to the players so that views know to query again
+    revision: AtomicU64,
+}
+
+impl PlayerStore {
+    pub fn open(path: &Path) -> Result<PlayerStore, ToolkitError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// A store which is thrown away on exit, used when the database can't be opened
+    pub fn open_in_memory() -> Result<PlayerStore, ToolkitError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<PlayerStore, ToolkitError> {
+        // WAL keeps readers from blocking on writes, and with it NORMAL sync can
+        // lose the last transactions on power loss but never corrupts the database
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+
+        // A new database reports version 0
+        let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(ToolkitError::UnsupportedPlayerStoreVersion(version));
+        }
+        conn.execute_batch(SCHEMA)?;
+        if version < SCHEMA_VERSION {
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+
+        Ok(PlayerStore {
+            conn: Mutex::new(conn),
+            revision: AtomicU64::new(0),
+        })
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::Relaxed)
+    }
+
+    fn changed(&self) {
+        self.revision.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records everyone in a random or ranked battle except the replay's owner and their division
+    pub fn record_replay(&self, replay: &Replay) -> Result<(), ToolkitError> {
+        if !matches!(replay.replay_file.meta.gameType.as_str(), "RandomBattle" | "RankedBattle") {
+            // Only update from randoms / ranked
+            return Ok(());
+        }
+
+        let Some(report) = replay.battle_report.as_ref() else {
+            return Ok(());
+        };
+
+        let timestamp = replay_timestamp(&replay.replay_file.meta.dateTime)?;
+
+        let self_player = report.players().iter().find(|player| {
+            if let Some(meta_player) = replay.replay_file.meta.vehicles.iter().find(|metadata_player| metadata_player.name == player.name()) {
+                meta_player.relation == 0
+            } else {
+                false
+            }
+        });
+
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        for player in report.players() {
+            if let Some(self_player) = self_player {
+                // Ignore ourselves and people in our division
+                if Arc::ptr_eq(self_player, player) || (self_player.division_id() > 0 && player.division_id() == self_player.division_id()) {
+                    continue;
+                }
+            }
+
+            merge_player(
+                &tx,
+                &StoredPlayer {
+                    db_id: player.db_id(),
+                    last_name: player.name().to_string(),
+                    clan: player.clan().to_string(),
+                    clan_id: player.clan_id(),
+                    notes: String::new(),
+                    aliases: Vec::new(),
+                    encounters: vec![Encounter {
+                        arena_id: report.arena_id(),
+                        timestamp,
+                    }],
+                },
+            )?;
+        }
+        tx.commit()?;
+        self.changed();
+
+        Ok(())
+    }
+
+    /// Adds `players` and `sent_replays` to the store, first removing everything
+    /// already stored if `replace` is set. Either all of it is written or none of it is.
+    pub fn import(&self, players: &[StoredPlayer], sent_replays: &[String], replace: bool) -> Result<(), ToolkitError> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        if replace {
+            tx.execute_batch("DELETE FROM encounters; DELETE FROM player_aliases; DELETE FROM players; DELETE FROM sent_replays;")?;
+        }
+        for player in players {
+            merge_player(&tx, player)?;
+        }
+        {
+            let mut insert = tx.prepare_cached("INSERT OR IGNORE INTO sent_replays (path) VALUES (?1)")?;
+            for path in sent_replays {
+                insert.execute(params![path])?;
+            }
+        }
+        tx.commit()?;
+        self.changed();
+
+        Ok(())
+    }
+
+    /// Removes all tracked players. Sent replays are kept.
+    pub fn clear_players(&self) -> Result<(), ToolkitError> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        tx.execute_batch("DELETE FROM encounters; DELETE FROM player_aliases; DELETE FROM players;")?;
+        tx.commit()?;
+        self.changed();
+
+        Ok(())
+    }
+
+    pub fn set_notes(&self, db_id: i64, notes: &str) -> Result<(), ToolkitError> {
+        self.conn.lock().execute("UPDATE players SET notes = ?2 WHERE db_id = ?1", params![db_id, notes])?;
+        self.changed();
+
+        Ok(())
+    }
+
+    /// Returns the players encountered within the query's time range which match its filter
+    pub fn query_players(&self, query: &PlayerQuery<'_>) -> Result<Vec<PlayerSummary>, ToolkitError> {
+        let since = query.since.as_ref().map(to_timestamp).unwrap_or(i64::MIN);
+        let filter = if query.filter.is_empty() { String::new() } else { like_pattern(query.filter) };
+        let sql = format!(
+            "SELECT p.db_id, p.last_name, p.clan, p.notes,
+                COUNT(*) AS encounters,
+                SUM(e.timestamp > ?1) AS encounters_in_range,
+                MAX(e.timestamp) AS last_encountered,
+                (SELECT group_concat(a.name, ', ') FROM player_aliases a WHERE a.db_id = p.db_id) AS aliases
+            FROM players p
+            JOIN encounters e ON e.db_id = p.db_id
+            WHERE p.db_id IN (SELECT db_id FROM encounters WHERE timestamp > ?1)
+                AND (?2 = ''
+                    OR p.last_name LIKE ?2 ESCAPE '\\'
+                    OR p.clan LIKE ?2 ESCAPE '\\'
+                    OR EXISTS (SELECT 1 FROM player_aliases a WHERE a.db_id = p.db_id AND a.name LIKE ?2 ESCAPE '\\'))
+            GROUP BY p.db_id
+            ORDER BY {} {}, p.db_id",
+            query.order.column(),
+            if query.descending { "DESC" } else { "ASC" }
+        );
+
+        let conn = self.conn.lock();
+        let mut statement = conn.prepare_cached(&sql)?;
+        let rows = statement.query_map(params![since, filter], |row| {
+            Ok(PlayerSummary {
+                db_id: row.get(0)?,
+                last_name: row.get(1)?,
+                clan: row.get(2)?,
+                notes: row.get(3)?,
+                encounters: row.get(4)?,
+                encounters_in_range: row.get(5)?,
+                last_encountered: from_timestamp(row.get(6)?),
+                aliases: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
+            })
+        })?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Every stored player with all of their encounters, for exporting
+    pub fn players(&self) -> Result<Vec<StoredPlayer>, ToolkitError> {
+        let conn = self.conn.lock();
+        let mut players: Vec<StoredPlayer> = conn
+            .prepare("SELECT db_id, last_name, clan, clan_id, notes FROM players ORDER BY db_id")?
+            .query_map([], |row| {
+                Ok(StoredPlayer {
+                    db_id: row.get(0)?,
+                    last_name: row.get(1)?,
+                    clan: row.get(2)?,
+                    clan_id: row.get(3)?,
+                    notes: row.get(4)?,
+                    aliases: Vec::new(),
+                    encounters: Vec::new(),
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        let mut aliases = conn.prepare("SELECT name FROM player_aliases WHERE db_id = ?1 ORDER BY name")?;
+        let mut encounters = conn.prepare("SELECT arena_id, timestamp FROM encounters WHERE db_id = ?1 ORDER BY timestamp")?;
+        for player in &mut players {
+            player.aliases = aliases.query_map(params![player.db_id], |row| row.get(0))?.collect::<Result<_, _>>()?;
+            player.encounters = encounters
+                .query_map(params![player.db_id], |row| {
+                    Ok(Encounter {
+                        arena_id: row.get(0)?,
+                        timestamp: from_timestamp(row.get(1)?),
+                    })
+                })?
+                .collect::<Result<_, _>>()?;
+        }
+
+        Ok(players)
+    }
+
+    pub fn sent_replays(&self) -> Result<Vec<String>, ToolkitError> {
+        let conn = self.conn.lock();
+        let mut statement = conn.prepare("SELECT path FROM sent_replays ORDER BY path")?;
+        let paths = statement.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+
+        Ok(paths)
+    }
+
+    pub fn is_replay_sent(&self, path: &str) -> Result<bool, ToolkitError> {
+        let conn = self.conn.lock();
+        let sent = conn.prepare_cached("SELECT 1 FROM sent_replays WHERE path = ?1")?.exists(params![path])?;
+
+        Ok(sent)
+    }
+
+    pub fn mark_replay_sent(&self, path: &str) -> Result<(), ToolkitError> {
+        self.conn.lock().execute("INSERT OR IGNORE INTO sent_replays (path) VALUES (?1)", params![path])?;
+
+        Ok(())
+    }
+
+    /// Forgets sent replays which no longer exist on disk
+    pub fn prune_sent_replays(&self) -> Result<(), ToolkitError> {
+        let missing: Vec<String> = self.sent_replays()?.into_iter().filter(|path| !Path::new(path).exists()).collect();
+
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        {
+            let mut delete = tx.prepare_cached("DELETE FROM sent_replays WHERE path = ?1")?;
+            for path in &missing {
+                delete.execute(params![path])?;
+            }
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+}
@@ -1,21 +1,42 @@
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     sync::Arc,
+    time::Instant,
 };
 
-use crate::{icons, task, twitch};
-use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone};
+use crate::{
+    icons,
+    task::{self, BackgroundTaskKind},
+    twitch,
+};
+use chrono::{DateTime, Duration, Local};
 use egui::{Color32, RichText};
 use egui_extras::{Column, TableBuilder};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use tracing::error;
 use wows_replays::ReplayMeta;
 
-use crate::{app::ToolkitTabViewer, replay_parser::Replay};
+use crate::{
+    app::ToolkitTabViewer,
+    error::ToolkitError,
+    player_store::{Encounter, PlayerOrder, PlayerQuery, PlayerStore, PlayerSummary, StoredPlayer},
+    replay_parser::replay_timestamp,
+};
+
+/// How often the table picks up players recorded in the background
+const MIN_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// How often the table is queried regardless, since the time range moves with the clock
+const MAX_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+/// How long after the last keystroke edited notes are written to the store
+const NOTES_SAVE_DELAY: std::time::Duration = std::time::Duration::from_millis(750);
 
+/// Player tracker view settings. The players themselves live in the [PlayerStore].
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct PlayerTracker {
-    tracked_players_by_time: BTreeMap<chrono::DateTime<Local>, Vec<i64>>,
+    /// Players tracked by versions which kept them in the settings. These are
+    /// only read so that they can be moved into the [PlayerStore].
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     tracked_players: HashMap<i64, TrackedPlayer>,
     filter_time_period: TimePeriod,
     sort_order: SortedBy,
@@ -26,81 +47,29 @@ pub struct PlayerTracker {
 }
 
 impl PlayerTracker {
-    pub fn update_from_live_arena_info(&mut self, meta: &ReplayMeta) {
+    pub fn update_from_live_arena_info(&mut self, meta: &ReplayMeta) -> Result<(), ToolkitError> {
         // Clear the data from the last game
         self.live_game_players = None;
 
-        let timestamp = NaiveDateTime::parse_from_str(&meta.dateTime, "%d.%m.%Y %H:%M:%S").expect("parsing replay date failed");
-        let timestamp = Local.from_local_datetime(&timestamp).single().expect("failed to convert to local time");
+        let timestamp = replay_timestamp(&meta.dateTime)?;
         let players = meta.vehicles.iter().map(|player| player.name.clone()).collect();
 
-        self.live_game_players = Some((timestamp, players))
-    }
-    pub fn update_from_replay(&mut self, replay: &Replay) {
-        if !matches!(replay.replay_file.meta.gameType.as_str(), "RandomBattle" | "RankedBattle") {
-            // Only update from randoms / ranked
-            return;
-        }
-
-        if let Some(report) = replay.battle_report.as_ref() {
-            let tracked_players = &mut self.tracked_players;
-            let tracked_players_by_ts = &mut self.tracked_players_by_time;
-
-            let timestamp = NaiveDateTime::parse_from_str(&replay.replay_file.meta.dateTime, "%d.%m.%Y %H:%M:%S").expect("parsing replay date failed");
-            let timestamp = Local.from_local_datetime(&timestamp).single().expect("failed to convert to local time");
-
-            let self_player = report.players().iter().find(|player| {
-                if let Some(meta_player) = replay.replay_file.meta.vehicles.iter().find(|metadata_player| metadata_player.name == player.name()) {
-                    meta_player.relation == 0
-                } else {
-                    false
-                }
-            });
-
-            for player in report.players() {
-                if let Some(self_player) = self_player {
-                    // Ignore ourselves and people in our division
-                    if Arc::ptr_eq(self_player, player) || (self_player.division_id() > 0 && player.division_id() == self_player.division_id()) {
-                        continue;
-                    }
-                }
+        self.live_game_players = Some((timestamp, players));
 
-                let tracked_player = tracked_players.entry(player.db_id()).or_default();
-                if tracked_player.arena_ids.contains(&report.arena_id()) {
-                    continue;
-                }
-
-                let mut update_metadata = false;
-
-                if let Some(last_seen) = tracked_player.timestamps.first() {
-                    if *last_seen < timestamp {
-                        update_metadata = true;
-                    }
-                }
-
-                if update_metadata || tracked_player.timestamps.is_empty() {
-                    if update_metadata
-                        && !tracked_player.names.contains(&tracked_player.last_name)
-                        && tracked_player.last_name != player.name()
-                        && !tracked_player.last_name.is_empty()
-                    {
-                        // If we need to update the name, let's add the name to the alias list
-                        tracked_player.names.insert(tracked_player.last_name.clone());
-                    }
-
-                    tracked_player.last_name = player.name().to_string();
+        Ok(())
+    }
 
-                    tracked_player.clan = player.clan().to_string();
-                }
+    pub fn has_legacy_players(&self) -> bool {
+        !self.tracked_players.is_empty()
+    }
 
-                tracked_player.db_id = player.db_id();
-                tracked_player.clan_id = player.clan_id();
-                tracked_player.timestamps.insert(timestamp);
-                tracked_player.arena_ids.insert(report.arena_id());
+    /// Returns the players stored by older versions, in the layout of the [PlayerStore]
+    pub fn legacy_players(&self) -> Vec<StoredPlayer> {
+        self.tracked_players.values().map(StoredPlayer::from).collect()
+    }
 
-                tracked_players_by_ts.entry(timestamp.clone()).or_default().push(player.db_id());
-            }
-        }
+    pub fn clear_legacy_players(&mut self) {
+        self.tracked_players.clear();
     }
 }
 
@@ -117,6 +86,31 @@ pub struct TrackedPlayer {
     notes: String,
 }
 
+impl From<&TrackedPlayer> for StoredPlayer {
+    fn from(player: &TrackedPlayer) -> Self {
+        // Which arena each timestamp belongs to was never recorded. Arena IDs grow
+        // over time, so pairing both in sorted order recovers it. Any arena left
+        // over is given the most recent timestamp.
+        let last_seen = player.timestamps.last().copied().unwrap_or_else(Local::now);
+        let encounters = player
+            .arena_ids
+            .iter()
+            .zip(player.timestamps.iter().copied().chain(std::iter::repeat(last_seen)))
+            .map(|(arena_id, timestamp)| Encounter { arena_id: *arena_id, timestamp })
+            .collect();
+
+        StoredPlayer {
+            db_id: player.db_id,
+            last_name: player.last_name.clone(),
+            clan: player.clan.clone(),
+            clan_id: player.clan_id,
+            notes: player.notes.clone(),
+            aliases: player.names.iter().cloned().collect(),
+            encounters,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 enum TimePeriod {
     LastHour,
@@ -190,6 +184,18 @@ impl SortedBy {
         }
     }
 
+    fn query_order(&self) -> (PlayerOrder, bool) {
+        let order = match self {
+            SortedBy::Name(_) => PlayerOrder::Name,
+            SortedBy::Clan(_) => PlayerOrder::Clan,
+            SortedBy::LastEncountered(_) => PlayerOrder::LastEncountered,
+            SortedBy::TimesEncountered(_) => PlayerOrder::Encounters,
+            SortedBy::TimesEncounteredInTimeRange(_) => PlayerOrder::EncountersInRange,
+        };
+
+        (order, self.order() == SortOrder::Desc)
+    }
+
     fn order(&self) -> SortOrder {
         match self {
             SortedBy::Name(sort_order)
@@ -237,17 +243,94 @@ impl Default for TimePeriod {
     }
 }
 
+/// The players shown in the tracker table, queried from the [PlayerStore]
+#[derive(Default)]
+pub struct PlayerTrackerTabState {
+    rows: Vec<PlayerSummary>,
+    query: Option<(TimePeriod, SortedBy, String)>,
+    revision: u64,
+    refreshed_at: Option<Instant>,
+    error: Option<String>,
+    /// Notes which have been edited but not yet saved, and when they were last edited
+    pending_notes: Option<(i64, String, Instant)>,
+}
+
+impl PlayerTrackerTabState {
+    /// Queries the store again if the filters changed, the store changed, or the results are getting old
+    fn refresh(&mut self, store: &PlayerStore, tracker: &PlayerTracker) {
+        let query = (tracker.filter_time_period, tracker.sort_order, tracker.player_filter.clone());
+        let revision = store.revision();
+        let stale = match self.refreshed_at.map(|refreshed_at| refreshed_at.elapsed()) {
+            Some(elapsed) => self.query.as_ref() != Some(&query) || elapsed > MAX_REFRESH_INTERVAL || (revision != self.revision && elapsed > MIN_REFRESH_INTERVAL),
+            None => true,
+        };
+        if !stale {
+            return;
+        }
+
+        let (order, descending) = tracker.sort_order.query_order();
+        let result = store.query_players(&PlayerQuery {
+            since: tracker.filter_time_period.to_date(),
+            filter: &tracker.player_filter,
+            order,
+            descending,
+        });
+        match result {
+            Ok(rows) => {
+                self.rows = rows;
+                self.error = None;
+                // Keep showing what's being typed rather than what was last saved
+                if let Some((db_id, notes, _)) = self.pending_notes.as_ref() {
+                    if let Some(row) = self.rows.iter_mut().find(|row| row.db_id == *db_id) {
+                        row.notes = notes.clone();
+                    }
+                }
+            }
+            Err(e) => {
+                error!("failed to query tracked players: {}", e);
+                self.error = Some(e.to_string());
+            }
+        }
+
+        self.query = Some(query);
+        self.revision = revision;
+        self.refreshed_at = Some(Instant::now());
+    }
+}
+
+/// Writes edited notes once typing has paused, or straight away if `force` is set
+fn save_notes(pending_notes: &mut Option<(i64, String, Instant)>, store: &PlayerStore, force: bool) {
+    let due = pending_notes
+        .as_ref()
+        .map(|(_, _, edited_at)| force || edited_at.elapsed() >= NOTES_SAVE_DELAY)
+        .unwrap_or(false);
+    if !due {
+        return;
+    }
+
+    if let Some((db_id, notes, _)) = pending_notes.take() {
+        if let Err(e) = store.set_notes(db_id, &notes) {
+            error!("failed to save notes for player {}: {}", db_id, e);
+        }
+    }
+}
+
 impl ToolkitTabViewer<'_> {
     pub fn build_player_tracker_tab(&mut self, ui: &mut egui::Ui) {
+        let Some(player_store) = self.tab_state.player_store.as_ref() else {
+            ui.label(format!("{} The player data store could not be opened, so players are not tracked", icons::WARNING));
+            return;
+        };
         let mut player_tracker_settings = self.tab_state.settings.player_tracker.write();
         let player_tracker_settings = &mut *player_tracker_settings;
-        let filter_lower = player_tracker_settings.player_filter.to_ascii_lowercase();
+        let tab_state = &mut self.tab_state.player_tracker_tab;
         let now = chrono::offset::Local::now();
         ui.vertical(|ui| {
             ui.horizontal(|ui| {
                 if ui.button("Clear Stats").clicked() {
-                    player_tracker_settings.tracked_players.clear();
-                    player_tracker_settings.tracked_players_by_time.clear();
+                    if let Err(e) = player_store.clear_players() {
+                        error!("failed to clear tracked players: {}", e);
+                    }
                 }
 
                 let selected = &mut player_tracker_settings.filter_time_period;
@@ -265,13 +348,17 @@ impl ToolkitTabViewer<'_> {
                 ui.text_edit_singleline(&mut player_tracker_settings.player_filter);
                 if let Some(replay_files) = self.tab_state.replay_files.as_ref() {
                     if let Some(wows_data) = self.tab_state.world_of_warships_data.as_ref() {
-                        if ui.button("Populate Data From Replays").clicked() {
-                            crate::update_background_task!(
-                                self.tab_state.background_task,
+                        let populating = self
+                            .tab_state
+                            .background_tasks
+                            .is_running(|kind| matches!(kind, BackgroundTaskKind::PopulatePlayerInspectorFromReplays));
+                        if ui.add_enabled(!populating, egui::Button::new("Populate Data From Replays")).clicked() {
+                            crate::start_background_task!(
+                                self.tab_state.background_tasks,
                                 Some(task::start_populating_player_inspector(
                                     replay_files.keys().cloned().collect(),
                                     Arc::clone(wows_data),
-                                    Arc::clone(&self.tab_state.settings.player_tracker)
+                                    Arc::clone(player_store)
                                 ))
                             );
                         }
@@ -343,6 +430,18 @@ impl ToolkitTabViewer<'_> {
 
             egui::CentralPanel::default().show_inside(ui, |ui| {
                 ui.heading("Historical Matches");
+                save_notes(&mut tab_state.pending_notes, player_store, false);
+                if tab_state.pending_notes.is_some() {
+                    ui.ctx().request_repaint_after(NOTES_SAVE_DELAY);
+                }
+                tab_state.refresh(player_store, player_tracker_settings);
+                if tab_state.revision != player_store.revision() {
+                    // Pick up players which are still being recorded
+                    ui.ctx().request_repaint_after(MIN_REFRESH_INTERVAL);
+                }
+                if let Some(error) = tab_state.error.as_ref() {
+                    ui.colored_label(Color32::LIGHT_RED, format!("{} Could not load tracked players: {}", icons::WARNING, error));
+                }
                 egui::ScrollArea::horizontal().id_salt("player_tracker_central").show(ui, |ui| {
                     let table = TableBuilder::new(ui)
                         .striped(true)
@@ -433,157 +532,73 @@ impl ToolkitTabViewer<'_> {
                                 ui.strong("Notes");
                             });
                         })
-                        .body(|mut body| {
-                            let tracked_players_by_ts = &player_tracker_settings.tracked_players_by_time;
-                            // Filter by the date range
-                            let player_range: BTreeSet<_> = if let Some(filter_range) = player_tracker_settings.filter_time_period.to_date() {
-                                tracked_players_by_ts
-                                    .iter()
-                                    .filter_map(|(ts, ids)| if *ts > filter_range { Some(ids) } else { None })
-                                    .flatten()
-                                    .cloned()
-                                    .collect()
-                            } else {
-                                tracked_players_by_ts.iter().map(|(_ts, ids)| ids).flatten().cloned().collect()
-                            };
-
-                            let tracked_players = &mut player_tracker_settings.tracked_players;
-                            let players = tracked_players
-                                .iter_mut()
-                                .filter(|(id, player)| {
-                                    if !player_tracker_settings.player_filter.is_empty() {
-                                        player_range.contains(id)
-                                            && (player.clan.to_ascii_lowercase().contains(&filter_lower)
-                                                || player.last_name.contains(&filter_lower)
-                                                || player.names.iter().position(|name| name.to_ascii_lowercase().contains(&filter_lower)).is_some())
-                                    } else {
-                                        player_range.contains(id)
-                                    }
-                                })
-                                .sorted_by(|(_ida, playera), (_idb, playerb)| match sorted_by {
-                                    SortedBy::Name(sort_order) => {
-                                        let playera_name = &playera.last_name;
-                                        let playerb_name = &playerb.last_name;
-
-                                        if sort_order == SortOrder::Asc {
-                                            playera_name.cmp(playerb_name)
-                                        } else {
-                                            playerb_name.cmp(playera_name)
-                                        }
-                                    }
-                                    SortedBy::Clan(sort_order) => {
-                                        let playera_clan = &playera.clan;
-                                        let playerb_clan = &playerb.clan;
-
-                                        if sort_order == SortOrder::Asc {
-                                            playera_clan.cmp(&playerb_clan)
-                                        } else {
-                                            playerb_clan.cmp(playera_clan)
-                                        }
-                                    }
-                                    SortedBy::LastEncountered(sort_order) => {
-                                        let playera_last = playera.timestamps.last().unwrap();
-                                        let playerb_last = playerb.timestamps.last().unwrap();
-
-                                        if sort_order == SortOrder::Asc {
-                                            playera_last.cmp(&playerb_last)
-                                        } else {
-                                            playerb_last.cmp(&playera_last)
-                                        }
-                                    }
-                                    SortedBy::TimesEncountered(sort_order) => {
-                                        let playera_count = playera.timestamps.len();
-                                        let playerb_count = playerb.timestamps.len();
-
-                                        if sort_order == SortOrder::Asc {
-                                            playera_count.cmp(&playerb_count)
-                                        } else {
-                                            playerb_count.cmp(&playera_count)
-                                        }
-                                    }
-                                    SortedBy::TimesEncounteredInTimeRange(sort_order) => {
-                                        let (playera_count, playerb_count) = if let Some(filter_range) = player_tracker_settings.filter_time_period.to_date() {
-                                            let playera_count = playera.timestamps.iter().filter(|ts| **ts > filter_range).count();
-                                            let playerb_count = playerb.timestamps.iter().filter(|ts| **ts > filter_range).count();
-
-                                            (playera_count, playerb_count)
-                                        } else {
-                                            let playera_count = playera.timestamps.len();
-                                            let playerb_count = playerb.timestamps.len();
-
-                                            (playera_count, playerb_count)
-                                        };
+                        .body(|body| {
+                            body.rows(30.0, tab_state.rows.len(), |mut row| {
+                                let player = &mut tab_state.rows[row.index()];
+                                let times_encountered = player.encounters;
+                                let times_encountered_in_range = player.encounters_in_range;
+
+                                let encounters_color = match times_encountered_in_range {
+                                    0..=1 => None,
+                                    2..=3 => Some(Color32::YELLOW),
+                                    4..=5 => Some(Color32::ORANGE),
+                                    _ => Some(Color32::LIGHT_RED),
+                                };
 
-                                        if sort_order == SortOrder::Asc {
-                                            playera_count.cmp(&playerb_count)
-                                        } else {
-                                            playerb_count.cmp(&playera_count)
-                                        }
-                                    }
+                                row.col(|ui| {
+                                    ui.label(&player.clan);
                                 });
+                                row.col(|ui| {
+                                    let text = RichText::new(&player.last_name);
+                                    let text = if let Some(color) = encounters_color { text.color(color) } else { text };
 
-                            for (player_id, player) in players {
-                                body.row(30.0, |mut row| {
-                                    let times_encountered = player.arena_ids.len();
-                                    let times_encountered_in_range = if let Some(filter_range) = player_tracker_settings.filter_time_period.to_date() {
-                                        player.timestamps.iter().filter(|ts| **ts > filter_range).count()
+                                    ui.label(text);
+                                });
+                                row.col(|ui| {
+                                    ui.label(player.db_id.to_string());
+                                });
+                                row.col(|ui| {
+                                    let text = RichText::new(times_encountered.to_string());
+                                    let text = if let Some(color) = encounters_color { text.color(color) } else { text };
+                                    ui.label(text);
+                                });
+                                row.col(|ui| {
+                                    let text = RichText::new(times_encountered_in_range.to_string());
+                                    let text = if let Some(color) = encounters_color { text.color(color) } else { text };
+                                    ui.label(text);
+                                });
+                                row.col(|ui| {
+                                    let timestamp = player.last_encountered;
+                                    let encounter_date = format!("{}", timestamp.format("%Y-%m-%d %H:%M:%S"));
+                                    let delta = now - timestamp;
+
+                                    let delta_text = if delta.num_weeks() > 0 {
+                                        format!("{} weeks ago", delta.num_weeks())
+                                    } else if delta.num_days() > 0 {
+                                        format!("{} days ago", delta.num_days())
+                                    } else if delta.num_hours() > 0 {
+                                        format!("{} hours ago", delta.num_hours())
                                     } else {
-                                        times_encountered
-                                    };
-
-                                    let encounters_color = match times_encountered_in_range {
-                                        0..=1 => None,
-                                        2..=3 => Some(Color32::YELLOW),
-                                        4..=5 => Some(Color32::ORANGE),
-                                        _ => Some(Color32::LIGHT_RED),
+                                        format!("{} minutes ago", delta.num_minutes())
                                     };
-
-                                    row.col(|ui| {
-                                        ui.label(&player.clan);
-                                    });
-                                    row.col(|ui| {
-                                        let text = RichText::new(&player.last_name);
-                                        let text = if let Some(color) = encounters_color { text.color(color) } else { text };
-
-                                        ui.label(text);
-                                    });
-                                    row.col(|ui| {
-                                        ui.label(player_id.to_string());
-                                    });
-                                    row.col(|ui| {
-                                        let text = RichText::new(times_encountered.to_string());
-                                        let text = if let Some(color) = encounters_color { text.color(color) } else { text };
-                                        ui.label(text);
-                                    });
-                                    row.col(|ui| {
-                                        let text = RichText::new(times_encountered_in_range.to_string());
-                                        let text = if let Some(color) = encounters_color { text.color(color) } else { text };
-                                        ui.label(text);
-                                    });
-                                    row.col(|ui| {
-                                        let timestamp = player.timestamps.last().unwrap();
-                                        let encounter_date = format!("{}", timestamp.format("%Y-%m-%d %H:%M:%S"));
-                                        let delta = now - timestamp;
-
-                                        let delta_text = if delta.num_weeks() > 0 {
-                                            format!("{} weeks ago", delta.num_weeks())
-                                        } else if delta.num_days() > 0 {
-                                            format!("{} days ago", delta.num_days())
-                                        } else if delta.num_hours() > 0 {
-                                            format!("{} hours ago", delta.num_hours())
-                                        } else {
-                                            format!("{} minutes ago", delta.num_minutes())
-                                        };
-                                        ui.label(delta_text).on_hover_text(encounter_date);
-                                    });
-                                    row.col(|ui| {
-                                        ui.label(player.names.iter().join(", "));
-                                    });
-                                    row.col(|ui| {
-                                        ui.text_edit_singleline(&mut player.notes);
-                                    });
+                                    ui.label(delta_text).on_hover_text(encounter_date);
                                 });
-                            }
+                                row.col(|ui| {
+                                    ui.label(&player.aliases);
+                                });
+                                row.col(|ui| {
+                                    let response = ui.text_edit_singleline(&mut player.notes);
+                                    if response.changed() {
+                                        if tab_state.pending_notes.as_ref().is_some_and(|(db_id, _, _)| *db_id != player.db_id) {
+                                            save_notes(&mut tab_state.pending_notes, player_store, true);
+                                        }
+                                        tab_state.pending_notes = Some((player.db_id, player.notes.clone(), Instant::now()));
+                                    }
+                                    if response.lost_focus() {
+                                        save_notes(&mut tab_state.pending_notes, player_store, true);
+                                    }
+                                });
+                            });
                         });
                 });
             });
@@ -0,0 +1,288 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    time::{Duration, SystemTime},
+};
+
+use chrono::Local;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+use wows_replays::ReplayFile;
+use wowsunpack::game_params::provider::GameMetadataProvider;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    error::ToolkitError,
+    replay_parser::Replay,
+    task::{BackgroundTask, BackgroundTaskCompletion, BackgroundTaskKind, TaskProgress},
+};
+
+const ARCHIVE_DIR_NAME: &str = "archive";
+const INDEX_FILE_NAME: &str = "index.json";
+
+/// A replay which has been moved out of the replays directory and into a
+/// compressed archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedReplay {
+    /// File name of the zip (relative to the archive directory) containing this replay
+    pub archive: String,
+    /// Original file name of the replay. This is also the name of the entry in the zip.
+    pub file_name: String,
+    /// Uncompressed size of the replay
+    pub size: u64,
+    /// Replay metadata JSON, kept in the index so that the replay library can be
+    /// populated without decompressing every replay.
+    pub raw_meta: String,
+    pub archived_at: chrono::DateTime<Local>,
+}
+
+/// Index of all replays archived for a replays directory. The index lives
+/// alongside the zips in `<replays dir>/archive/index.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReplayArchive {
+    #[serde(skip)]
+    archive_dir: PathBuf,
+    replays: BTreeMap<String, ArchivedReplay>,
+}
+
+impl ReplayArchive {
+    pub fn load(replays_dir: &Path) -> Result<ReplayArchive, ToolkitError> {
+        let archive_dir = replays_dir.join(ARCHIVE_DIR_NAME);
+        let index_path = archive_dir.join(INDEX_FILE_NAME);
+
+        let mut archive: ReplayArchive = if index_path.exists() {
+            let index_data = fs::read(&index_path)?;
+            serde_json::from_slice(&index_data).map_err(ToolkitError::ReplayArchiveIndex)?
+        } else {
+            Default::default()
+        };
+
+        archive.archive_dir = archive_dir;
+
+        Ok(archive)
+    }
+
+    /// Writes the index to a temporary file before moving it over the old index
+    /// so that a crash never leaves us with a truncated index.
+    fn save(&self) -> Result<(), ToolkitError> {
+        fs::create_dir_all(&self.archive_dir)?;
+
+        let index_path = self.archive_dir.join(INDEX_FILE_NAME);
+        let tmp_path = index_path.with_extension("json.tmp");
+        {
+            let mut file = File::create(&tmp_path)?;
+            serde_json::to_writer(&mut file, self).map_err(ToolkitError::ReplayArchiveIndex)?;
+            file.sync_all()?;
+        }
+        fs::rename(tmp_path, index_path)?;
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.replays.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replays.is_empty()
+    }
+
+    /// Total uncompressed size of all archived replays
+    pub fn total_size(&self) -> u64 {
+        self.replays.values().map(|replay| replay.size).sum()
+    }
+
+    /// Path used to represent an archived replay in the replay library. This
+    /// path does not exist on disk.
+    pub fn virtual_path(&self, replay: &ArchivedReplay) -> PathBuf {
+        self.archive_dir.join(&replay.file_name)
+    }
+
+    pub fn archive_path(&self, replay: &ArchivedReplay) -> PathBuf {
+        self.archive_dir.join(&replay.archive)
+    }
+
+    /// Looks up an archived replay by the path returned from [ReplayArchive::virtual_path]
+    pub fn entry_for_path(&self, path: &Path) -> Option<&ArchivedReplay> {
+        if path.parent() != Some(self.archive_dir.as_path()) {
+            return None;
+        }
+
+        path.file_name().and_then(|name| name.to_str()).and_then(|name| self.replays.get(name))
+    }
+
+    /// Builds the replays which should be shown in the replay library for all
+    /// archived replays. Only the metadata is loaded.
+    pub fn library_replays(&self, metadata_provider: &Arc<GameMetadataProvider>) -> Vec<(PathBuf, Arc<RwLock<Replay>>)> {
+        self.replays
+            .values()
+            .filter_map(|entry| {
+                let replay = match Replay::from_archive_entry(entry.clone(), Arc::clone(metadata_provider)) {
+                    Ok(replay) => replay,
+                    Err(e) => {
+                        error!("failed to load archived replay {}: {:?}", entry.file_name, e);
+                        return None;
+                    }
+                };
+
+                Some((self.virtual_path(entry), Arc::new(RwLock::new(replay))))
+            })
+            .collect()
+    }
+}
+
+/// Decompresses an archived replay and parses it. The replay parser only accepts
+/// files on disk, so the replay is decompressed into a temporary file first which
+/// is unique to this read.
+pub fn read_archived_replay(archive_path: &Path, entry: &ArchivedReplay) -> Result<ReplayFile, ToolkitError> {
+    let mut zip = ZipArchive::new(File::open(archive_path)?)?;
+    let mut zip_file = zip.by_name(&entry.file_name)?;
+
+    let mut temp_file = tempfile::Builder::new().prefix("wows_toolkit_").suffix(".wowsreplay").tempfile()?;
+    io::copy(&mut zip_file, &mut temp_file)?;
+    temp_file.flush()?;
+
+    ReplayFile::from_file(temp_file.path()).map_err(|_| ToolkitError::InvalidArchivedReplay(entry.file_name.clone()))
+}
+
+fn replays_older_than(replays_dir: &Path, cutoff: SystemTime) -> Result<Vec<PathBuf>, ToolkitError> {
+    let mut replays = Vec::new();
+    for file in fs::read_dir(replays_dir)?.flatten() {
+        let path = file.path();
+        if path.extension().map(|ext| ext != "wowsreplay").unwrap_or(true) || path.file_name().map(|name| name == "temp.wowsreplay").unwrap_or(false) {
+            continue;
+        }
+
+        let modified = file.metadata().and_then(|meta| meta.modified());
+        if let Ok(modified) = modified {
+            if modified < cutoff {
+                replays.push(path);
+            }
+        }
+    }
+
+    Ok(replays)
+}
+
+fn archive_replays(
+    replays_dir: PathBuf,
+    max_age: Duration,
+    metadata_provider: Arc<GameMetadataProvider>,
+    progress: &TaskProgress,
+) -> Result<BackgroundTaskCompletion, ToolkitError> {
+    let cutoff = SystemTime::now() - max_age;
+    let to_archive = replays_older_than(&replays_dir, cutoff)?;
+
+    let mut archive = ReplayArchive::load(&replays_dir)?;
+    if to_archive.is_empty() {
+        return Ok(BackgroundTaskCompletion::ReplaysArchived { archive, replays: Vec::new() });
+    }
+
+    fs::create_dir_all(&archive.archive_dir)?;
+
+    let now = Local::now();
+    let archive_name = format!("replays-{}.zip", now.format("%Y%m%d-%H%M%S"));
+    let archive_path = archive.archive_dir.join(&archive_name);
+
+    // Written to a temporary file so that a failure part way through leaves no partial archive behind
+    let temp_file = tempfile::Builder::new().prefix(".replays-").suffix(".zip").tempfile_in(&archive.archive_dir)?;
+    let mut zip = ZipWriter::new(temp_file);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated).compression_level(Some(9));
+
+    let mut archived = Vec::with_capacity(to_archive.len());
+    progress.set_total(to_archive.len() as u64);
+    for path in to_archive {
+        // Replays archived so far are still recorded in the index below
+        if progress.is_cancelled() {
+            break;
+        }
+        progress.increment();
+
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()).map(str::to_owned) else {
+            continue;
+        };
+
+        if archive.replays.contains_key(&file_name) {
+            debug!("{} is already archived, skipping", file_name);
+            continue;
+        }
+
+        // Don't archive anything we can't read back
+        let replay_file = match ReplayFile::from_file(&path) {
+            Ok(replay_file) => replay_file,
+            Err(e) => {
+                error!("not archiving {:?} as it could not be parsed: {:?}", path, e);
+                continue;
+            }
+        };
+
+        let replay_data = fs::read(&path)?;
+        zip.start_file(file_name.as_str(), options)?;
+        zip.write_all(&replay_data)?;
+
+        archived.push((
+            path,
+            ArchivedReplay {
+                archive: archive_name.clone(),
+                file_name,
+                size: replay_data.len() as u64,
+                raw_meta: replay_file.raw_meta.clone(),
+                archived_at: now,
+            },
+        ));
+    }
+
+    let temp_file = zip.finish()?;
+    if archived.is_empty() {
+        return Ok(BackgroundTaskCompletion::ReplaysArchived { archive, replays: Vec::new() });
+    }
+
+    temp_file.as_file().sync_all()?;
+    temp_file.persist(&archive_path).map_err(|err| err.error)?;
+
+    // Replays whose original could not be removed stay in the replays directory and are
+    // left out of the index so they aren't listed twice
+    let mut indexed = Vec::with_capacity(archived.len());
+    for (path, entry) in archived {
+        if let Err(e) = fs::remove_file(&path) {
+            error!("failed to remove archived replay {:?}, keeping the original: {:?}", path, e);
+            continue;
+        }
+
+        indexed.push(entry);
+    }
+
+    if indexed.is_empty() {
+        let _ = fs::remove_file(&archive_path);
+        return Ok(BackgroundTaskCompletion::ReplaysArchived { archive, replays: Vec::new() });
+    }
+
+    for entry in &indexed {
+        archive.replays.insert(entry.file_name.clone(), entry.clone());
+    }
+    archive.save()?;
+
+    let mut replays = Vec::with_capacity(indexed.len());
+    for entry in indexed {
+        let replay = Replay::from_archive_entry(entry.clone(), Arc::clone(&metadata_provider))?;
+        replays.push((archive.virtual_path(&entry), Arc::new(RwLock::new(replay))));
+    }
+
+    Ok(BackgroundTaskCompletion::ReplaysArchived { archive, replays })
+}
+
+pub fn start_archiving_replays(replays_dir: PathBuf, max_age: Duration, metadata_provider: Arc<GameMetadataProvider>) -> BackgroundTask {
+    let (tx, rx) = mpsc::channel();
+    let progress = Arc::new(TaskProgress::default());
+    let thread_progress = Arc::clone(&progress);
+
+    let _join_handle = std::thread::spawn(move || {
+        let _ = tx.send(archive_replays(replays_dir, max_age, metadata_provider, &thread_progress));
+    });
+
+    BackgroundTask::with_progress(rx, BackgroundTaskKind::ArchivingReplays, progress)
+}
@@ -1,17 +1,18 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     io::{BufWriter, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{atomic::AtomicBool, Arc}, time::Duration,
 };
 
-use crate::{app::TimedMessage, icons, twitch, update_background_task, util::build_tomato_gg_url, wows_data::ShipIcon};
-use chrono::{Local, NaiveDateTime, TimeZone};
+use crate::{app::TimedMessage, icons, start_background_task, twitch, util::build_tomato_gg_url, wows_data::ShipIcon};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 use egui::{mutex::Mutex, text::LayoutJob, Color32, FontId, Image, ImageSource, Label, OpenUrl, RichText, Sense, Separator, TextFormat, Vec2};
 use egui_extras::{Column, TableBuilder};
 
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 use tap::Pipe;
 use tracing::debug;
 
@@ -34,9 +35,24 @@ use crate::{
     app::{ReplayParserTabState, ToolkitTabViewer},
     error::ToolkitError,
     plaintext_viewer::{self, FileType},
+    replay_archive::ArchivedReplay,
     util::{self, build_ship_config_url, build_short_ship_config_url, build_wows_numbers_url, player_color_for_team_relation, separate_number},
 };
 
+/// Parses the `dateTime` field of replay metadata as local time
+pub fn replay_timestamp(date_time: &str) -> Result<DateTime<Local>, ToolkitError> {
+    timestamp_in(&Local, date_time)
+}
+
+/// Replays recorded in the hour repeated when clocks go back resolve to the earlier
+/// of the two times. Times skipped when clocks go forward can't be local times at
+/// all, so they are read as UTC rather than rejecting the replay.
+fn timestamp_in<Tz: TimeZone>(tz: &Tz, date_time: &str) -> Result<DateTime<Tz>, ToolkitError> {
+    let timestamp = NaiveDateTime::parse_from_str(date_time, "%d.%m.%Y %H:%M:%S").map_err(|_| ToolkitError::InvalidReplayDate(date_time.to_owned()))?;
+
+    Ok(tz.from_local_datetime(&timestamp).earliest().unwrap_or_else(|| tz.from_utc_datetime(&timestamp)))
+}
+
 const CHAT_VIEW_WIDTH: f32 = 500.0;
 const XP_INDEX: usize = 389;
 const DAMAGE_INDEX: usize = 412;
@@ -64,6 +80,28 @@ pub struct Replay {
     pub divisions: HashMap<u32, char>,
 
     pub remaining_div_identifiers: String,
+
+    /// Set if this replay lives in the replay archive. Only the metadata is
+    /// loaded until the replay is opened.
+    pub archive_entry: Option<ArchivedReplay>,
+}
+
+/// User-provided data attached to a replay
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ReplayAnnotation {
+    pub favorite: bool,
+    pub tags: BTreeSet<String>,
+    pub notes: String,
+}
+
+impl ReplayAnnotation {
+    pub fn is_empty(&self) -> bool {
+        !self.favorite && self.tags.is_empty() && self.notes.is_empty()
+    }
+}
+
+fn replay_annotation_key(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
 }
 
 fn player_name_with_clan(player: &Player) -> Cow<'_, str> {
@@ -82,11 +120,30 @@ impl Replay {
             battle_report: None,
             divisions: HashMap::new(),
             remaining_div_identifiers: "ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().rev().collect(),
+            archive_entry: None,
         }
     }
+
+    pub fn from_archive_entry(entry: ArchivedReplay, resource_loader: Arc<GameMetadataProvider>) -> Result<Self, ToolkitError> {
+        let replay_file = ReplayFile::from_decrypted_parts(entry.raw_meta.as_bytes().to_vec(), Vec::new())
+            .map_err(|_| ToolkitError::InvalidArchivedReplay(entry.file_name.clone()))?;
+
+        let mut replay = Replay::new(replay_file, resource_loader);
+        replay.archive_entry = Some(entry);
+
+        Ok(replay)
+    }
+
+    /// Whether the replay's packets still need to be read from the archive
+    pub fn needs_unarchiving(&self) -> bool {
+        self.archive_entry.is_some() && self.replay_file.packet_data.is_empty()
+    }
     pub fn parse(&self, expected_build: &str) -> Result<BattleReport, ToolkitError> {
-        let version_parts: Vec<_> = self.replay_file.meta.clientVersionFromExe.split(',').collect();
-        assert!(version_parts.len() == 4);
+        let client_version = &self.replay_file.meta.clientVersionFromExe;
+        let version_parts: Vec<_> = client_version.split(',').collect();
+        if version_parts.len() != 4 {
+            return Err(ToolkitError::InvalidReplayVersion(client_version.clone()));
+        }
         if version_parts[3] != expected_build {
             return Err(ToolkitError::ReplayVersionMismatch {
                 game_version: expected_build.to_string(),
@@ -135,7 +192,7 @@ impl ToolkitTabViewer<'_> {
             .and_then(|wows_data| wows_data.read().ship_icons.get(&species).cloned())
     }
 
-    fn metadata_provider(&self) -> Option<Arc<GameMetadataProvider>> {
+    pub(crate) fn metadata_provider(&self) -> Option<Arc<GameMetadataProvider>> {
         self.tab_state
             .world_of_warships_data
             .as_ref()
@@ -147,8 +204,6 @@ impl ToolkitTabViewer<'_> {
     }
 
     fn build_replay_player_list(&self, replay_file: &Replay, report: &BattleReport, ui: &mut egui::Ui) {
-        let match_timestamp = NaiveDateTime::parse_from_str(&replay_file.replay_file.meta.dateTime, "%d.%m.%Y %H:%M:%S").expect("parsing replay date failed");
-        let match_timestamp = Local.from_local_datetime(&match_timestamp).single().expect("failed to convert to local time");
         let twitch_state = self.tab_state.twitch_state.read();
 
         let is_dark_mode = ui.visuals().dark_mode;
@@ -463,31 +518,35 @@ impl ToolkitTabViewer<'_> {
                         });
                         ui.col(|ui| {
                             ui.menu_button(icons::DOTS_THREE, |ui| {
-                                if ui.small_button(format!("{} Open Build in Browser", icons::SHARE)).clicked() {
-                                    let metadata_provider = self.metadata_provider().unwrap();
+                                // Build links need game data to resolve the ship's modules
+                                let metadata_provider = self.metadata_provider();
+                                let has_metadata = metadata_provider.is_some();
 
-                                    let url = build_ship_config_url(entity, &metadata_provider);
+                                if ui.add_enabled(has_metadata, egui::Button::new(format!("{} Open Build in Browser", icons::SHARE)).small()).clicked() {
+                                    if let Some(metadata_provider) = metadata_provider.as_ref() {
+                                        let url = build_ship_config_url(entity, metadata_provider);
 
-                                    ui.ctx().open_url(OpenUrl::new_tab(url));
+                                        ui.ctx().open_url(OpenUrl::new_tab(url));
+                                    }
                                     ui.close_menu();
                                 }
 
-                                if ui.small_button(format!("{} Copy Build Link", icons::COPY)).clicked() {
-                                    let metadata_provider = self.metadata_provider().unwrap();
-
-                                    let url = build_ship_config_url(entity, &metadata_provider);
-                                    ui.output_mut(|output| output.copied_text = url);
-                                    *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Build link copied", icons::CHECK_CIRCLE)));
+                                if ui.add_enabled(has_metadata, egui::Button::new(format!("{} Copy Build Link", icons::COPY)).small()).clicked() {
+                                    if let Some(metadata_provider) = metadata_provider.as_ref() {
+                                        let url = build_ship_config_url(entity, metadata_provider);
+                                        ui.output_mut(|output| output.copied_text = url);
+                                        *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Build link copied", icons::CHECK_CIRCLE)));
+                                    }
 
                                     ui.close_menu();
                                 }
 
-                                if ui.small_button(format!("{} Copy Short Build Link", icons::COPY)).clicked() {
-                                    let metadata_provider = self.metadata_provider().unwrap();
-
-                                    let url = build_short_ship_config_url(entity, &metadata_provider);
-                                    ui.output_mut(|output| output.copied_text = url);
-                                    *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Build link copied", icons::CHECK_CIRCLE)));
+                                if ui.add_enabled(has_metadata, egui::Button::new(format!("{} Copy Short Build Link", icons::COPY)).small()).clicked() {
+                                    if let Some(metadata_provider) = metadata_provider.as_ref() {
+                                        let url = build_short_ship_config_url(entity, metadata_provider);
+                                        ui.output_mut(|output| output.copied_text = url);
+                                        *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Build link copied", icons::CHECK_CIRCLE)));
+                                    }
 
                                     ui.close_menu();
                                 }
@@ -768,8 +827,67 @@ impl ToolkitTabViewer<'_> {
         }
     }
 
+    fn build_replay_annotation_menu(&mut self, ui: &mut egui::Ui, replay_key: &str) {
+        let annotation = self.tab_state.settings.replay_annotations.entry(replay_key.to_owned()).or_default();
+        ui.checkbox(&mut annotation.favorite, format!("{} Favorite", icons::STAR));
+
+        ui.label(format!("{} Tags", icons::TAG));
+        ui.horizontal_wrapped(|ui| {
+            let mut remove_tag = None;
+            for tag in &annotation.tags {
+                if ui.small_button(format!("{} {}", tag, icons::X)).on_hover_text("Remove tag").clicked() {
+                    remove_tag = Some(tag.clone());
+                }
+            }
+
+            if let Some(tag) = remove_tag {
+                annotation.tags.remove(&tag);
+            }
+        });
+
+        let mut parser_tab = self.tab_state.replay_parser_tab.lock();
+        let response = ui.add(egui::TextEdit::singleline(&mut parser_tab.new_tag).hint_text("Add tag"));
+        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            let tag = parser_tab.new_tag.trim();
+            if !tag.is_empty() {
+                annotation.tags.insert(tag.to_owned());
+            }
+            parser_tab.new_tag.clear();
+            response.request_focus();
+        }
+
+        ui.label(format!("{} Notes", icons::NOTE_PENCIL));
+        ui.text_edit_multiline(&mut annotation.notes);
+    }
+
     fn build_file_listing(&mut self, ui: &mut egui::Ui) {
         ui.vertical(|ui| {
+            {
+                let known_tags: BTreeSet<String> = self.tab_state.settings.replay_annotations.values().flat_map(|annotation| annotation.tags.iter().cloned()).collect();
+                let mut parser_tab = self.tab_state.replay_parser_tab.lock();
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut parser_tab.favorites_only, format!("{} Favorites", icons::STAR));
+
+                    // Clear the filter if the tag no longer exists
+                    if parser_tab.tag_filter.as_ref().map(|tag| !known_tags.contains(tag)).unwrap_or(false) {
+                        parser_tab.tag_filter = None;
+                    }
+
+                    let selected_text = parser_tab.tag_filter.clone().unwrap_or_else(|| "All Tags".to_owned());
+                    egui::ComboBox::from_id_salt("replay_tag_filter").selected_text(selected_text).show_ui(ui, |ui| {
+                        ui.selectable_value(&mut parser_tab.tag_filter, None, "All Tags");
+                        for tag in &known_tags {
+                            ui.selectable_value(&mut parser_tab.tag_filter, Some(tag.clone()), tag.as_str());
+                        }
+                    });
+                });
+            }
+
+            let (favorites_only, tag_filter) = {
+                let parser_tab = self.tab_state.replay_parser_tab.lock();
+                (parser_tab.favorites_only, parser_tab.tag_filter.clone())
+            };
+
             egui::Grid::new("replay_files_grid").num_columns(1).striped(true).show(ui, |ui| {
                 if let Some(mut files) = self
                     .tab_state
@@ -779,8 +897,23 @@ impl ToolkitTabViewer<'_> {
                 {
                     // Sort by filename -- WoWs puts the date first in a sortable format
                     files.sort_by(|a, b| b.0.cmp(&a.0));
-                    let metadata_provider = self.metadata_provider().unwrap();
+                    // Replays are listed as soon as the replays directory is known, which may be before game data has loaded
+                    let Some(metadata_provider) = self.metadata_provider() else {
+                        ui.label("Replays will be listed once game data has loaded");
+                        return;
+                    };
                     for (path, replay) in files {
+                        let replay_key = replay_annotation_key(&path);
+                        let annotation = self.tab_state.settings.replay_annotations.get(&replay_key).cloned().unwrap_or_default();
+                        if favorites_only && !annotation.favorite {
+                            continue;
+                        }
+                        if let Some(tag) = tag_filter.as_ref() {
+                            if !annotation.tags.contains(tag) {
+                                continue;
+                            }
+                        }
+
                         let label = {
                             let file = replay.read();
                             let meta = &file.replay_file.meta;
@@ -792,13 +925,14 @@ impl ToolkitTabViewer<'_> {
                             let map_id = format!("IDS_{}", meta.mapName.to_uppercase());
                             let map_name = metadata_provider.localized_name_from_id(&map_id).unwrap_or_else(|| meta.mapName.clone());
 
+                            // Game modes and scenarios added after a patch may not have translations yet
                             let mode = metadata_provider
                                 .localized_name_from_id(&format!("IDS_{}", meta.gameType.to_ascii_uppercase()))
-                                .expect("failed to get game type translation");
+                                .unwrap_or_else(|| meta.gameType.clone());
 
                             let scenario = metadata_provider
                                 .localized_name_from_id(&format!("IDS_SCENARIO_{}", meta.scenario.to_ascii_uppercase()))
-                                .expect("failed to get scenario translation");
+                                .unwrap_or_else(|| meta.scenario.clone());
 
                             let time = meta.dateTime.as_str();
 
@@ -812,27 +946,71 @@ impl ToolkitTabViewer<'_> {
                             }
                         }
 
-                        let label = ui.add(Label::new(label_text).selectable(false).sense(Sense::click())).on_hover_text(label.as_str());
+                        let hover_text = if annotation.notes.is_empty() {
+                            label.clone()
+                        } else {
+                            format!("{}\n\n{}", label, annotation.notes)
+                        };
+
+                        let label = ui
+                            .horizontal(|ui| {
+                                let star_color = if annotation.favorite { Color32::GOLD } else { Color32::DARK_GRAY };
+                                if ui
+                                    .add(Label::new(RichText::new(icons::STAR).color(star_color)).selectable(false).sense(Sense::click()))
+                                    .on_hover_text("Favorite")
+                                    .clicked()
+                                {
+                                    let annotation = self.tab_state.settings.replay_annotations.entry(replay_key.clone()).or_default();
+                                    annotation.favorite = !annotation.favorite;
+                                }
+
+                                let label = ui.add(Label::new(label_text).selectable(false).sense(Sense::click())).on_hover_text(hover_text);
+
+                                for tag in &annotation.tags {
+                                    ui.label(RichText::new(tag).small().background_color(ui.visuals().faint_bg_color));
+                                }
+                                if !annotation.notes.is_empty() {
+                                    ui.label(icons::NOTE_PENCIL).on_hover_text(annotation.notes.as_str());
+                                }
+
+                                label
+                            })
+                            .inner;
                         label.context_menu(|ui| {
+                            // Archived replays only exist inside the zip they were archived to
+                            let disk_path = self
+                                .tab_state
+                                .world_of_warships_data
+                                .as_ref()
+                                .and_then(|wows_data| {
+                                    let wows_data = wows_data.read();
+                                    wows_data.replay_archive.entry_for_path(&path).map(|entry| wows_data.replay_archive.archive_path(entry))
+                                })
+                                .unwrap_or_else(|| path.clone());
                             if ui.button("Copy Path").clicked() {
-                                ui.output_mut(|output| output.copied_text = path.to_string_lossy().into_owned());
+                                ui.output_mut(|output| output.copied_text = disk_path.to_string_lossy().into_owned());
                                 ui.close_menu();
                             }
                             if ui.button("Show in File Explorer").clicked() {
-                                util::open_file_explorer(&path);
+                                util::open_file_explorer(&disk_path);
                                 ui.close_menu();
                             }
+                            ui.separator();
+                            self.build_replay_annotation_menu(ui, &replay_key);
                         });
 
                         if label.double_clicked() {
                             if let Some(wows_data) = self.tab_state.world_of_warships_data.as_ref() {
-                                update_background_task!(self.tab_state.background_task, wows_data.read().load_replay(replay.clone()));
+                                start_background_task!(self.tab_state.background_tasks, wows_data.read().load_replay(replay.clone()));
                             }
                         }
                         ui.end_row();
                     }
                 }
             });
+
+            // Don't persist annotations which were opened but never filled in
+            self.tab_state.settings.replay_annotations.retain(|_, annotation| !annotation.is_empty());
         });
     }
 
@@ -851,8 +1029,8 @@ impl ToolkitTabViewer<'_> {
                         self.tab_state.settings.current_replay_path = file;
 
                         if let Some(wows_data) = self.tab_state.world_of_warships_data.as_ref() {
-                            update_background_task!(
-                                self.tab_state.background_task,
+                            start_background_task!(
+                                self.tab_state.background_tasks,
                                 wows_data.read().parse_replay(self.tab_state.settings.current_replay_path.clone())
                             );
                         }
@@ -863,7 +1041,7 @@ impl ToolkitTabViewer<'_> {
                 if let Some(_replays_dir) = self.replays_dir() {
                     if ui.button(format!("{} Load Live Game", icons::DETECTIVE)).clicked() {
                         if let Some(wows_data) = self.tab_state.world_of_warships_data.as_ref() {
-                            update_background_task!(self.tab_state.background_task, wows_data.read().parse_live_replay());
+                            start_background_task!(self.tab_state.background_tasks, wows_data.read().parse_live_replay());
                         }
                     }
 
@@ -890,3 +1068,87 @@ impl ToolkitTabViewer<'_> {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, LocalResult, NaiveDate, NaiveTime, Timelike};
+
+    use super::*;
+
+    /// Clocks go back from +01:00 to +00:00 at 02:00 local time on 29 October 2023,
+    /// so 01:00 to 02:00 happens twice
+    #[derive(Clone, Copy, Debug)]
+    struct DaylightSavingEnd;
+
+    impl DaylightSavingEnd {
+        fn summer() -> FixedOffset {
+            FixedOffset::east_opt(3600).unwrap()
+        }
+
+        fn winter() -> FixedOffset {
+            FixedOffset::east_opt(0).unwrap()
+        }
+
+        fn change() -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(2023, 10, 29).unwrap().and_hms_opt(1, 0, 0).unwrap()
+        }
+    }
+
+    impl TimeZone for DaylightSavingEnd {
+        type Offset = FixedOffset;
+
+        fn from_offset(_offset: &FixedOffset) -> Self {
+            DaylightSavingEnd
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let change = Self::change();
+            if *local < change {
+                LocalResult::Single(Self::summer())
+            } else if *local < change + chrono::Duration::hours(1) {
+                LocalResult::Ambiguous(Self::summer(), Self::winter())
+            } else {
+                LocalResult::Single(Self::winter())
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            // The change happens at 01:00 UTC
+            if *utc < Self::change() {
+                Self::summer()
+            } else {
+                Self::winter()
+            }
+        }
+    }
+
+    #[test]
+    fn ambiguous_local_time_uses_earliest() {
+        let timestamp = timestamp_in(&DaylightSavingEnd, "29.10.2023 01:30:00").expect("ambiguous replay time was rejected");
+
+        assert_eq!(timestamp.offset(), &DaylightSavingEnd::summer());
+        assert_eq!(timestamp.naive_local().hour(), 1);
+        assert_eq!(timestamp.naive_local().minute(), 30);
+    }
+
+    #[test]
+    fn unambiguous_local_time() {
+        let timestamp = timestamp_in(&DaylightSavingEnd, "29.10.2023 03:15:00").expect("replay time was rejected");
+
+        assert_eq!(timestamp.offset(), &DaylightSavingEnd::winter());
+        assert_eq!(timestamp.naive_local().hour(), 3);
+    }
+
+    #[test]
+    fn invalid_replay_date() {
+        assert!(matches!(timestamp_in(&DaylightSavingEnd, "not a date"), Err(ToolkitError::InvalidReplayDate(_))));
+    }
+}
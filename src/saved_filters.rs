@@ -0,0 +1,120 @@
+//! Named filters and bookmarked paths for the resource unpacker, persisted in [Settings](crate::app::Settings).
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{TimedMessage, ToolkitTabViewer},
+    icons,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedFilter {
+    pub name: String,
+    /// A substring or glob, as typed into the unpacker's filter box
+    pub filter: String,
+}
+
+/// Unsaved input for the saved filters menu
+#[derive(Default)]
+pub struct SavedFiltersState {
+    pub new_filter_name: String,
+}
+
+impl ToolkitTabViewer<'_> {
+    /// Builds the menu for applying and managing saved filters and bookmarks
+    pub(crate) fn build_saved_filters_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button(icons::STAR, |ui| {
+            ui.set_min_width(250.0);
+
+            ui.strong("Filters");
+            let mut remove_filter = None;
+            for (i, saved) in self.tab_state.settings.saved_filters.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.small_button(icons::TRASH).on_hover_text("Remove").clicked() {
+                        remove_filter = Some(i);
+                    }
+                    if ui.button(saved.name.as_str()).on_hover_text(saved.filter.as_str()).clicked() {
+                        self.tab_state.filter = saved.filter.clone();
+                        ui.close_menu();
+                    }
+                });
+            }
+            if let Some(i) = remove_filter {
+                self.tab_state.settings.saved_filters.remove(i);
+            }
+
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.tab_state.saved_filters.new_filter_name)
+                        .hint_text("Name")
+                        .desired_width(150.0),
+                );
+                let can_save = !self.tab_state.filter.is_empty() && !self.tab_state.saved_filters.new_filter_name.trim().is_empty();
+                if ui
+                    .add_enabled(can_save, egui::Button::new(format!("{} Save Filter", icons::PLUS)))
+                    .on_hover_text("Save the current filter")
+                    .clicked()
+                {
+                    let name = std::mem::take(&mut self.tab_state.saved_filters.new_filter_name).trim().to_owned();
+                    let filter = self.tab_state.filter.clone();
+                    // Saving under an existing name replaces that filter
+                    match self.tab_state.settings.saved_filters.iter_mut().find(|saved| saved.name == name) {
+                        Some(existing) => existing.filter = filter,
+                        None => self.tab_state.settings.saved_filters.push(SavedFilter { name, filter }),
+                    }
+                }
+            });
+
+            ui.separator();
+            ui.strong("Bookmarks");
+            let mut remove_bookmark = None;
+            for (i, bookmark) in self.tab_state.settings.bookmarks.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.small_button(icons::TRASH).on_hover_text("Remove").clicked() {
+                        remove_bookmark = Some(i);
+                    }
+                    if ui.button(format!("res/{}", bookmark)).on_hover_text("Add to the selected files").clicked() {
+                        let node = self
+                            .tab_state
+                            .world_of_warships_data
+                            .as_ref()
+                            .and_then(|wows_data| wows_data.read().file_tree.find(bookmark).ok());
+                        match node {
+                            Some(node) => self.tab_state.items_to_extract.lock().push(node),
+                            None => {
+                                *self.tab_state.timed_message.write() =
+                                    Some(TimedMessage::new(format!("{} res/{} does not exist in this build", icons::WARNING, bookmark)))
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                });
+            }
+            if let Some(i) = remove_bookmark {
+                self.tab_state.settings.bookmarks.remove(i);
+            }
+
+            let has_selection = !self.tab_state.items_to_extract.lock().is_empty();
+            if ui
+                .add_enabled(has_selection, egui::Button::new(format!("{} Bookmark Selected Files", icons::BOOKMARK_SIMPLE)))
+                .clicked()
+            {
+                let selected: Vec<String> = self
+                    .tab_state
+                    .items_to_extract
+                    .lock()
+                    .iter()
+                    .filter_map(|node| node.path().ok())
+                    .map(|path| path.to_string_lossy().replace('\\', "/"))
+                    .collect();
+                for path in selected {
+                    if !self.tab_state.settings.bookmarks.contains(&path) {
+                        self.tab_state.settings.bookmarks.push(path);
+                    }
+                }
+            }
+        })
+        .response
+        .on_hover_text("Saved filters and bookmarks");
+    }
+}
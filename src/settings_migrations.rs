@@ -0,0 +1,166 @@
+//! Upgrades settings saved by older versions of the toolkit.
+//!
+//! Saved [Settings] carry the `schema_version` they were written with. On load
+//! every migration newer than that version runs in order and the version is
+//! bumped, so each migration runs exactly once. New migrations go at the end of
+//! [MIGRATIONS] and existing ones are never reordered or removed.
+//!
+//! The flags which came before the schema version, `has_default_value_fix_015`
+//! and `has_019_game_params_update`, are no longer read.
+
+use std::{fs, io, path::Path};
+
+use tracing::{info, warn};
+
+use crate::{app::Settings, game_params};
+
+pub struct Migration {
+    /// The schema version settings are at once this migration has run
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&mut Settings),
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Retire the 0.1.15 default value flag",
+        apply: retire_default_value_fix_015,
+    },
+    Migration {
+        version: 2,
+        description: "Remove the single-entry GameParams cache",
+        apply: remove_game_params_bin,
+    },
+];
+
+/// The schema version of settings written by this version of the toolkit
+pub const CURRENT_SETTINGS_VERSION: u32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// The 0.1.15 flag forced update checks and replay sharing back on at every start.
+/// Whatever is stored now is the user's choice, so it's kept as is.
+fn retire_default_value_fix_015(_settings: &mut Settings) {}
+
+/// GameParams used to be cached in a single `game_params.bin`, which older versions
+/// wrote to the working directory when there was no storage directory. Entries
+/// now live in the cache directory.
+fn remove_game_params_bin(_settings: &mut Settings) {
+    for path in [game_params::game_params_bin_path(), Path::new("game_params.bin").to_path_buf()] {
+        match fs::remove_file(&path) {
+            Ok(()) => info!("Removed old GameParams cache {:?}", path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!("failed to remove old GameParams cache {:?}: {}", path, e),
+        }
+    }
+}
+
+/// Runs every migration newer than the settings' schema version
+pub fn migrate_settings(settings: &mut Settings) {
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > settings.schema_version) {
+        info!("Migrating settings to version {}: {}", migration.version, migration.description);
+        (migration.apply)(settings);
+        settings.schema_version = migration.version;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads a settings blob the way eframe persists it
+    fn load(blob: &str) -> Settings {
+        let mut settings: Settings = ron::from_str(blob).expect("failed to deserialize settings blob");
+        migrate_settings(&mut settings);
+        settings
+    }
+
+    #[test]
+    fn migrations_are_ordered() {
+        assert!(MIGRATIONS.windows(2).all(|pair| pair[0].version < pair[1].version));
+        assert!(MIGRATIONS.iter().all(|migration| migration.version > 0));
+        assert_eq!(Settings::default().schema_version, CURRENT_SETTINGS_VERSION);
+    }
+
+    #[test]
+    fn settings_from_before_015_keep_preferences() {
+        // Settings first saved before 0.1.15 still carry the flag as false, including opt-outs of replay sharing
+        let settings = load(
+            r#"(
+                current_replay_path: "",
+                wows_dir: "C:\\Games\\World_of_Warships",
+                locale: Some("en"),
+                check_for_updates: false,
+                send_replay_data: false,
+                has_default_value_fix_015: false,
+                has_019_game_params_update: false,
+            )"#,
+        );
+
+        assert!(!settings.check_for_updates);
+        assert!(!settings.send_replay_data);
+        assert_eq!(settings.wows_dir, "C:\\Games\\World_of_Warships");
+        assert_eq!(settings.schema_version, CURRENT_SETTINGS_VERSION);
+    }
+
+    #[test]
+    fn unversioned_settings_keep_preferences() {
+        // Settings saved by 0.1.15 up to the migration framework, including the 0.1.19 flag
+        let settings = load(
+            r#"(
+                current_replay_path: "",
+                wows_dir: "C:\\Games\\World_of_Warships",
+                locale: Some("en"),
+                check_for_updates: false,
+                send_replay_data: false,
+                has_default_value_fix_015: true,
+                has_019_game_params_update: true,
+                twitch_monitored_channel: "some_channel",
+                replay_archive_age_days: 30,
+            )"#,
+        );
+
+        assert!(!settings.check_for_updates);
+        assert!(!settings.send_replay_data);
+        assert_eq!(settings.twitch_monitored_channel, "some_channel");
+        assert_eq!(settings.replay_archive_age_days, 30);
+        assert_eq!(settings.schema_version, CURRENT_SETTINGS_VERSION);
+    }
+
+    #[test]
+    fn current_settings_are_not_migrated() {
+        let blob = format!(
+            r#"(
+                current_replay_path: "",
+                wows_dir: "",
+                locale: Some("en"),
+                check_for_updates: false,
+                send_replay_data: false,
+                schema_version: {},
+            )"#,
+            CURRENT_SETTINGS_VERSION
+        );
+        let settings = load(&blob);
+
+        assert!(!settings.check_for_updates);
+        assert!(!settings.send_replay_data);
+    }
+
+    #[test]
+    fn migrated_settings_round_trip() {
+        let mut settings = load(
+            r#"(
+                current_replay_path: "",
+                wows_dir: "",
+                locale: Some("en"),
+            )"#,
+        );
+        settings.send_replay_data = false;
+
+        let blob = ron::to_string(&settings).expect("failed to serialize settings");
+        assert!(!blob.contains("has_default_value_fix_015"));
+
+        let settings = load(&blob);
+        assert!(!settings.send_replay_data);
+        assert_eq!(settings.schema_version, CURRENT_SETTINGS_VERSION);
+    }
+}
@@ -0,0 +1,552 @@
+//! Ship stat cards computed from the raw GameParams.
+//!
+//! The typed params exposed by [GameMetadataProvider] only cover what the replay
+//! parser needs, so stats are computed from the full GameParams converted to
+//! JSON. Ships are described by their `ShipUpgradeInfo`: every upgrade has a
+//! `ucType` (hull, artillery, torpedoes, ...) and lists the components it
+//! allows. A configuration picks one upgrade per `ucType`, and for each
+//! component type the first component allowed by all selected upgrades is used.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{mpsc, Arc},
+};
+
+use egui_extras::{Column, TableBuilder};
+use serde_json::{Map, Value};
+use tracing::debug;
+use wowsunpack::{
+    data::{idx::FileNode, pkg::PkgFileLoader},
+    game_params::{
+        convert::{game_params_to_pickle, pickle_to_json},
+        provider::GameMetadataProvider,
+    },
+};
+
+use crate::{
+    app::ToolkitTabViewer,
+    error::ToolkitError,
+    icons,
+    task::{BackgroundTask, BackgroundTaskCompletion, BackgroundTaskKind},
+};
+
+/// Torpedo `maxDist` values are in 30m units
+const TORPEDO_DISTANCE_UNIT_METERS: f64 = 30.0;
+
+/// Component types which make up a ship configuration
+const COMPONENT_TYPES: [&str; 7] = ["hull", "artillery", "torpedoes", "fireControl", "engine", "airDefense", "atba"];
+
+/// Ships and projectiles pulled out of the raw GameParams
+pub struct ShipDatabase {
+    /// Ship params keyed by param index
+    ships: BTreeMap<String, Value>,
+    /// Projectile params keyed by param name, which is how ships refer to their ammo
+    projectiles: HashMap<String, Value>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MainBattery {
+    pub reload: f64,
+    pub barrels: u64,
+    pub range_km: f64,
+    pub he_alpha: Option<f64>,
+    pub ap_alpha: Option<f64>,
+    pub sap_alpha: Option<f64>,
+}
+
+impl MainBattery {
+    pub fn dpm(&self, alpha: Option<f64>) -> Option<f64> {
+        alpha.filter(|_| self.reload > 0.0).map(|alpha| alpha * self.barrels as f64 * 60.0 / self.reload)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Torpedoes {
+    pub reload: f64,
+    pub tubes: u64,
+    pub range_km: f64,
+    pub damage: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AaAura {
+    pub name: String,
+    pub range_km: f64,
+    pub damage: f64,
+    pub hit_chance: f64,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ShipStats {
+    pub hp: Option<f64>,
+    pub speed_knots: Option<f64>,
+    pub concealment_sea_km: Option<f64>,
+    pub concealment_air_km: Option<f64>,
+    pub main_battery: Option<MainBattery>,
+    pub torpedoes: Option<Torpedoes>,
+    pub aa_auras: Vec<AaAura>,
+}
+
+/// A ship added to the comparison along with the upgrade chosen for each `ucType`
+pub struct ShipSelection {
+    pub index: String,
+    pub upgrades: BTreeMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct ShipStatsTabState {
+    pub database: Option<Arc<ShipDatabase>>,
+    pub search: String,
+    pub selections: Vec<ShipSelection>,
+}
+
+fn f64_field(value: &Value, field: &str) -> Option<f64> {
+    value.get(field).and_then(Value::as_f64)
+}
+
+fn type_info<'a>(value: &'a Value, field: &str) -> Option<&'a str> {
+    value.get("typeinfo").and_then(|info| info.get(field)).and_then(Value::as_str)
+}
+
+/// Finds the object which holds all params. Depending on the game version
+/// the params may be wrapped in a list or a region-keyed dictionary.
+fn params_root(value: &Value) -> Option<&Map<String, Value>> {
+    match value {
+        Value::Array(values) => values.first().and_then(params_root),
+        Value::Object(map) => {
+            if map.values().any(|value| value.get("typeinfo").is_some()) {
+                Some(map)
+            } else if map.len() == 1 {
+                map.values().next().and_then(params_root)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+impl ShipDatabase {
+    fn from_game_params(game_params: Value) -> Result<Self, ToolkitError> {
+        let root = params_root(&game_params).ok_or(ToolkitError::InvalidGameParams)?;
+
+        let mut ships = BTreeMap::new();
+        let mut projectiles = HashMap::new();
+        for (name, param) in root {
+            match type_info(param, "type") {
+                Some("Ship") => {
+                    if let Some(index) = param.get("index").and_then(Value::as_str) {
+                        ships.insert(index.to_owned(), param.clone());
+                    }
+                }
+                Some("Projectile") => {
+                    projectiles.insert(name.clone(), param.clone());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(ShipDatabase { ships, projectiles })
+    }
+
+    pub fn ship(&self, index: &str) -> Option<&Value> {
+        self.ships.get(index)
+    }
+
+    /// Upgrade names for a ship, grouped by `ucType`
+    pub fn upgrades(&self, index: &str) -> BTreeMap<String, Vec<String>> {
+        let mut upgrades: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let Some(upgrade_info) = self.ship(index).and_then(|ship| ship.get("ShipUpgradeInfo")).and_then(Value::as_object) else {
+            return upgrades;
+        };
+
+        for (name, upgrade) in upgrade_info {
+            if let Some(uc_type) = upgrade.get("ucType").and_then(Value::as_str) {
+                upgrades.entry(uc_type.to_owned()).or_default().push(name.clone());
+            }
+        }
+
+        // Stock upgrades have no previous upgrade, so put them first
+        for names in upgrades.values_mut() {
+            names.sort_by_key(|name| upgrade_info[name].get("prev").and_then(Value::as_str).map(|prev| !prev.is_empty()).unwrap_or(false));
+        }
+
+        upgrades
+    }
+
+    /// The stock configuration of a ship
+    pub fn default_upgrades(&self, index: &str) -> BTreeMap<String, String> {
+        self.upgrades(index)
+            .into_iter()
+            .filter_map(|(uc_type, names)| Some((uc_type, names.into_iter().next()?)))
+            .collect()
+    }
+
+    /// Resolves the component objects used by a configuration
+    fn components<'a>(&'a self, ship: &'a Value, upgrades: &BTreeMap<String, String>) -> HashMap<&'static str, &'a Value> {
+        let Some(upgrade_info) = ship.get("ShipUpgradeInfo") else {
+            return HashMap::new();
+        };
+
+        let selected: Vec<&Value> = upgrades.values().filter_map(|name| upgrade_info.get(name)).collect();
+
+        COMPONENT_TYPES
+            .iter()
+            .filter_map(|component_type| {
+                let allowed: Vec<Vec<&str>> = selected
+                    .iter()
+                    .filter_map(|upgrade| upgrade.get("components")?.get(*component_type)?.as_array())
+                    .filter(|names| !names.is_empty())
+                    .map(|names| names.iter().filter_map(Value::as_str).collect())
+                    .collect();
+
+                let first = allowed.first()?;
+                let name = first.iter().find(|name| allowed.iter().all(|names| names.contains(name)))?;
+
+                Some((*component_type, ship.get(*name)?))
+            })
+            .collect()
+    }
+
+    /// Weapon mounts are the objects in a component which have an ammo list
+    fn mounts(component: &Value) -> impl Iterator<Item = &Value> {
+        component
+            .as_object()
+            .into_iter()
+            .flat_map(|map| map.values())
+            .filter(|value| value.get("ammoList").is_some())
+    }
+
+    fn main_battery(&self, artillery: &Value, fire_control: Option<&Value>) -> Option<MainBattery> {
+        let mounts: Vec<&Value> = Self::mounts(artillery).collect();
+        let first_mount = mounts.first()?;
+
+        let mut battery = MainBattery {
+            reload: f64_field(first_mount, "shotDelay")?,
+            barrels: mounts.iter().filter_map(|mount| mount.get("numBarrels").and_then(Value::as_u64)).sum(),
+            range_km: f64_field(artillery, "maxDist").unwrap_or_default() * fire_control.and_then(|suo| f64_field(suo, "maxDistCoef")).unwrap_or(1.0) / 1000.0,
+            ..Default::default()
+        };
+
+        for ammo in first_mount["ammoList"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+            let Some(projectile) = self.projectiles.get(ammo) else {
+                continue;
+            };
+
+            let alpha = f64_field(projectile, "alphaDamage");
+            match projectile.get("ammoType").and_then(Value::as_str) {
+                Some("HE") => battery.he_alpha = alpha,
+                Some("AP") => battery.ap_alpha = alpha,
+                Some("CS") => battery.sap_alpha = alpha,
+                _ => {}
+            }
+        }
+
+        Some(battery)
+    }
+
+    fn torpedoes(&self, torpedoes: &Value) -> Option<Torpedoes> {
+        let mounts: Vec<&Value> = Self::mounts(torpedoes).collect();
+        let first_mount = mounts.first()?;
+        let ammo = first_mount["ammoList"].as_array()?.first()?.as_str()?;
+        let torpedo = self.projectiles.get(ammo)?;
+
+        Some(Torpedoes {
+            reload: f64_field(first_mount, "shotDelay")?,
+            tubes: mounts.iter().filter_map(|mount| mount.get("numBarrels").and_then(Value::as_u64)).sum(),
+            range_km: f64_field(torpedo, "maxDist").unwrap_or_default() * TORPEDO_DISTANCE_UNIT_METERS / 1000.0,
+            // Only a third of the alpha damage applies to the flooding-free hit
+            damage: f64_field(torpedo, "alphaDamage").unwrap_or_default() / 3.0 + f64_field(torpedo, "damage").unwrap_or_default(),
+        })
+    }
+
+    fn aa_auras(component: &Value) -> Vec<AaAura> {
+        let mut auras: Vec<AaAura> = component
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, aura)| {
+                Some(AaAura {
+                    name: aura.get("type").and_then(Value::as_str).unwrap_or(name.as_str()).to_owned(),
+                    range_km: f64_field(aura, "maxDistance")? / 1000.0,
+                    damage: f64_field(aura, "areaDamage")?,
+                    hit_chance: f64_field(aura, "hitChance").unwrap_or_default(),
+                })
+            })
+            .collect();
+
+        auras.sort_by(|a, b| b.range_km.total_cmp(&a.range_km));
+
+        auras
+    }
+
+    pub fn stats(&self, index: &str, upgrades: &BTreeMap<String, String>) -> Option<ShipStats> {
+        let ship = self.ship(index)?;
+        let components = self.components(ship, upgrades);
+
+        let hull = components.get("hull");
+        let mut stats = ShipStats {
+            hp: hull.and_then(|hull| f64_field(hull, "health")),
+            speed_knots: hull.and_then(|hull| f64_field(hull, "maxSpeed")),
+            concealment_sea_km: hull.and_then(|hull| f64_field(hull, "visibilityFactor")),
+            concealment_air_km: hull.and_then(|hull| f64_field(hull, "visibilityFactorByPlane")),
+            main_battery: components
+                .get("artillery")
+                .and_then(|artillery| self.main_battery(artillery, components.get("fireControl").copied())),
+            torpedoes: components.get("torpedoes").and_then(|torpedoes| self.torpedoes(torpedoes)),
+            aa_auras: Vec::new(),
+        };
+
+        for component_type in ["airDefense", "atba", "hull"] {
+            if let Some(component) = components.get(component_type) {
+                stats.aa_auras.extend(Self::aa_auras(component));
+            }
+        }
+
+        Some(stats)
+    }
+}
+
+fn load_ship_database(file_tree: FileNode, pkg_loader: Arc<PkgFileLoader>) -> Result<BackgroundTaskCompletion, ToolkitError> {
+    debug!("Loading raw GameParams for ship stats");
+
+    let game_params_file = file_tree.find("content/GameParams.data")?;
+    let mut game_params_data = Vec::with_capacity(game_params_file.file_info().map(|info| info.unpacked_size as usize).unwrap_or_default());
+    game_params_file.read_file(&pkg_loader, &mut game_params_data)?;
+
+    let pickle = game_params_to_pickle(game_params_data)?;
+    let database = ShipDatabase::from_game_params(pickle_to_json(pickle))?;
+
+    Ok(BackgroundTaskCompletion::ShipStatsLoaded(Arc::new(database)))
+}
+
+pub fn start_loading_ship_database(file_tree: FileNode, pkg_loader: Arc<PkgFileLoader>) -> BackgroundTask {
+    let (tx, rx) = mpsc::channel();
+
+    let _join_handle = std::thread::spawn(move || {
+        let _ = tx.send(load_ship_database(file_tree, pkg_loader));
+    });
+
+    BackgroundTask::new(rx, BackgroundTaskKind::LoadingShipStats)
+}
+
+fn ship_name(metadata_provider: Option<&Arc<GameMetadataProvider>>, index: &str) -> String {
+    metadata_provider
+        .and_then(|metadata| metadata.localized_name_from_id(&format!("IDS_{}", index)))
+        .map(|name| format!("{} ({})", name, index))
+        .unwrap_or_else(|| index.to_owned())
+}
+
+fn format_stat(value: Option<f64>, precision: usize, unit: &str) -> String {
+    value.map(|value| format!("{:.*}{}", precision, value, unit)).unwrap_or_else(|| "-".to_owned())
+}
+
+/// Rows of the comparison table. Each row renders one stat for a ship.
+const STAT_ROWS: [(&str, fn(&ShipStats) -> String); 14] = [
+    ("HP", |stats| format_stat(stats.hp, 0, "")),
+    ("Speed", |stats| format_stat(stats.speed_knots, 1, " kn")),
+    ("Concealment (sea)", |stats| format_stat(stats.concealment_sea_km, 2, " km")),
+    ("Concealment (air)", |stats| format_stat(stats.concealment_air_km, 2, " km")),
+    ("Main battery range", |stats| format_stat(stats.main_battery.as_ref().map(|mb| mb.range_km), 2, " km")),
+    ("Main battery reload", |stats| format_stat(stats.main_battery.as_ref().map(|mb| mb.reload), 2, " s")),
+    ("Main battery barrels", |stats| {
+        format_stat(stats.main_battery.as_ref().map(|mb| mb.barrels as f64), 0, "")
+    }),
+    ("HE alpha / DPM", |stats| {
+        let mb = stats.main_battery.as_ref();
+        format!(
+            "{} / {}",
+            format_stat(mb.and_then(|mb| mb.he_alpha), 0, ""),
+            format_stat(mb.and_then(|mb| mb.dpm(mb.he_alpha)), 0, "")
+        )
+    }),
+    ("AP alpha / DPM", |stats| {
+        let mb = stats.main_battery.as_ref();
+        format!(
+            "{} / {}",
+            format_stat(mb.and_then(|mb| mb.ap_alpha), 0, ""),
+            format_stat(mb.and_then(|mb| mb.dpm(mb.ap_alpha)), 0, "")
+        )
+    }),
+    ("SAP alpha / DPM", |stats| {
+        let mb = stats.main_battery.as_ref();
+        format!(
+            "{} / {}",
+            format_stat(mb.and_then(|mb| mb.sap_alpha), 0, ""),
+            format_stat(mb.and_then(|mb| mb.dpm(mb.sap_alpha)), 0, "")
+        )
+    }),
+    ("Torpedo range", |stats| format_stat(stats.torpedoes.as_ref().map(|torps| torps.range_km), 2, " km")),
+    ("Torpedo damage", |stats| format_stat(stats.torpedoes.as_ref().map(|torps| torps.damage), 0, "")),
+    ("Torpedo reload / tubes", |stats| {
+        let torps = stats.torpedoes.as_ref();
+        format!(
+            "{} / {}",
+            format_stat(torps.map(|torps| torps.reload), 1, " s"),
+            format_stat(torps.map(|torps| torps.tubes as f64), 0, "")
+        )
+    }),
+    ("AA auras", |stats| {
+        stats
+            .aa_auras
+            .iter()
+            .map(|aura| format!("{}: {:.0} dps @ {:.1} km ({:.0}%)", aura.name, aura.damage, aura.range_km, aura.hit_chance * 100.0))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }),
+];
+
+impl ToolkitTabViewer<'_> {
+    /// Builds the ship stats comparison tab
+    pub fn build_ship_stats_tab(&mut self, ui: &mut egui::Ui) {
+        let metadata_provider = self.metadata_provider();
+        let game_data = self.tab_state.world_of_warships_data.as_ref().map(|wows_data| {
+            let wows_data = wows_data.read();
+            (wows_data.file_tree.clone(), wows_data.pkg_loader.clone())
+        });
+
+        let Some(database) = self.tab_state.ship_stats_tab.database.clone() else {
+            ui.label("Ship stats are computed from the full GameParams, which takes a while to load.");
+            let can_load = game_data.is_some()
+                && !self
+                    .tab_state
+                    .background_tasks
+                    .is_running(|kind| matches!(kind, BackgroundTaskKind::LoadingShipStats));
+            if ui
+                .add_enabled(can_load, egui::Button::new(format!("{} Load Ship Data", icons::DOWNLOAD_SIMPLE)))
+                .clicked()
+            {
+                if let Some((file_tree, pkg_loader)) = game_data {
+                    self.tab_state.background_tasks.start(start_loading_ship_database(file_tree, pkg_loader));
+                }
+            }
+            return;
+        };
+
+        let state = &mut self.tab_state.ship_stats_tab;
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.search).hint_text("Ship name or index"));
+            let search = state.search.to_lowercase();
+            ui.menu_button(format!("{} Add Ship", icons::PLUS), |ui| {
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for index in database.ships.keys() {
+                        let name = ship_name(metadata_provider.as_ref(), index);
+                        if !search.is_empty() && !name.to_lowercase().contains(&search) {
+                            continue;
+                        }
+
+                        if ui.button(&name).clicked() {
+                            state.selections.push(ShipSelection {
+                                index: index.clone(),
+                                upgrades: database.default_upgrades(index),
+                            });
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+            if ui.button(format!("{} Clear", icons::TRASH)).clicked() {
+                state.selections.clear();
+            }
+        });
+
+        if state.selections.is_empty() {
+            ui.label("Add ships to compare their stats");
+            return;
+        }
+
+        ui.separator();
+
+        let mut remove = None;
+        let stats: Vec<ShipStats> = state
+            .selections
+            .iter()
+            .map(|selection| database.stats(&selection.index, &selection.upgrades).unwrap_or_default())
+            .collect();
+
+        egui::ScrollArea::both().id_source("ship_stats_scroll_area").show(ui, |ui| {
+            let mut table = TableBuilder::new(ui)
+                .striped(true)
+                .resizable(true)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .vscroll(false)
+                .column(Column::initial(160.0).clip(true));
+            for _ in &state.selections {
+                table = table.column(Column::initial(220.0).clip(true));
+            }
+
+            table
+                .header(20.0, |mut header| {
+                    header.col(|ui| {
+                        ui.strong("Stat");
+                    });
+                    for (i, selection) in state.selections.iter().enumerate() {
+                        header.col(|ui| {
+                            if ui.small_button(icons::X).on_hover_text("Remove from comparison").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.strong(ship_name(metadata_provider.as_ref(), &selection.index));
+                        });
+                    }
+                })
+                .body(|mut body| {
+                    // Module selection for each ship
+                    let upgrade_types: Vec<String> = state
+                        .selections
+                        .iter()
+                        .flat_map(|selection| database.upgrades(&selection.index).into_keys())
+                        .collect::<std::collections::BTreeSet<_>>()
+                        .into_iter()
+                        .collect();
+
+                    for uc_type in &upgrade_types {
+                        body.row(24.0, |mut row| {
+                            row.col(|ui| {
+                                ui.label(uc_type.trim_start_matches('_'));
+                            });
+                            for (i, selection) in state.selections.iter_mut().enumerate() {
+                                row.col(|ui| {
+                                    let options = database.upgrades(&selection.index).remove(uc_type).unwrap_or_default();
+                                    if options.is_empty() {
+                                        return;
+                                    }
+
+                                    let selected = selection.upgrades.entry(uc_type.clone()).or_insert_with(|| options[0].clone());
+                                    egui::ComboBox::new(("ship_stats_upgrade", i, uc_type.as_str()), "")
+                                        .selected_text(selected.as_str())
+                                        .show_ui(ui, |ui| {
+                                            for option in options {
+                                                ui.selectable_value(selected, option.clone(), option);
+                                            }
+                                        });
+                                });
+                            }
+                        });
+                    }
+
+                    for (label, stat) in STAT_ROWS {
+                        let height = if label == "AA auras" {
+                            20.0 * stats.iter().map(|stats| stats.aa_auras.len()).max().unwrap_or(1).max(1) as f32
+                        } else {
+                            20.0
+                        };
+                        body.row(height, |mut row| {
+                            row.col(|ui| {
+                                ui.label(label);
+                            });
+                            for ship_stats in &stats {
+                                row.col(|ui| {
+                                    ui.label(stat(ship_stats));
+                                });
+                            }
+                        });
+                    }
+                });
+        });
+
+        if let Some(remove) = remove {
+            state.selections.remove(remove);
+        }
+    }
+}
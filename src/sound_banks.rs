@@ -0,0 +1,297 @@
+//! Browsing and extracting the streams in the game's Wwise sound banks.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+};
+
+use egui::OpenUrl;
+use egui_extras::{Column, TableBuilder};
+use rayon::prelude::*;
+use wowsunpack::data::{idx::FileNode, pkg::PkgFileLoader};
+
+use crate::{
+    app::{TimedMessage, ToolkitTabViewer},
+    error::ToolkitError,
+    icons,
+    unpacker_jobs::{JobOutcome, UnpackerJob},
+    wwise::{self, SoundContainer, SoundContainerKind, WemEntry},
+};
+
+const SOUND_CONTAINER_FILE_TYPES: [&str; 2] = [".bnk", ".pck"];
+
+#[derive(Default)]
+pub struct SoundBankTabState {
+    pub filter: String,
+    pub convert_to_wav: bool,
+    /// Build the list of sound containers was built for, and the list itself
+    containers: Option<(usize, Vec<(PathBuf, FileNode)>)>,
+    loading: Option<mpsc::Receiver<(PathBuf, Result<SoundContainer, ToolkitError>)>>,
+    selected: Option<(PathBuf, Arc<SoundContainer>)>,
+}
+
+fn load_container(node: &FileNode, pkg_loader: &PkgFileLoader) -> Result<SoundContainer, ToolkitError> {
+    let mut data = Vec::with_capacity(node.file_info().map(|info| info.unpacked_size as usize).unwrap_or_default());
+    node.read_file(pkg_loader, &mut data)?;
+
+    SoundContainer::parse(data)
+}
+
+/// Produces the file name and contents for a stream, as WAV if requested and the codec allows it
+fn stream_contents(container: &SoundContainer, entry: &WemEntry, convert_to_wav: bool) -> Result<(String, Vec<u8>), ToolkitError> {
+    let wem = container.wem_data(entry);
+    let can_convert = entry.info.map(|info| info.can_convert_to_wav()).unwrap_or(false);
+
+    if convert_to_wav && can_convert {
+        let file_name = Path::new(&entry.file_name()).with_extension("wav").to_string_lossy().into_owned();
+        Ok((file_name, wwise::wem_to_wav(wem)?))
+    } else {
+        Ok((entry.file_name(), wem.to_vec()))
+    }
+}
+
+/// Writes a stream to `dir`, returning the path it was written to
+fn write_stream(dir: &Path, container: &SoundContainer, entry: &WemEntry, convert_to_wav: bool) -> Result<PathBuf, ToolkitError> {
+    let (file_name, data) = stream_contents(container, entry, convert_to_wav)?;
+    let path = dir.join(file_name);
+    fs::write(&path, data)?;
+
+    Ok(path)
+}
+
+fn format_duration(secs: f64) -> String {
+    format!("{}:{:04.1}", (secs / 60.0) as u64, secs % 60.0)
+}
+
+impl ToolkitTabViewer<'_> {
+    fn extract_sound_streams(&mut self, container_path: &Path, container: Arc<SoundContainer>) {
+        let stem = container_path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let output_dir = Path::new(self.tab_state.output_dir.as_str()).join("sounds").join(stem);
+        let convert_to_wav = self.tab_state.sound_bank_tab.convert_to_wav;
+
+        let job = UnpackerJob::spawn(format!("Extracting sounds to {}", output_dir.display()), move |progress| {
+            fs::create_dir_all(&output_dir)?;
+            progress.set_totals(container.entries.len(), container.entries.iter().map(|entry| entry.size() as u64).sum());
+
+            container.entries.par_iter().try_for_each(|entry| {
+                if progress.is_cancelled() {
+                    return Ok(());
+                }
+                progress.file_started(entry.file_name());
+                write_stream(&output_dir, &container, entry, convert_to_wav)?;
+                progress.file_finished(entry.size() as u64);
+
+                Ok::<(), ToolkitError>(())
+            })?;
+
+            Ok(JobOutcome::Message(format!(
+                "Extracted {} sounds to {}",
+                container.entries.len(),
+                output_dir.display()
+            )))
+        });
+
+        self.tab_state.unpacker_jobs.push(job);
+    }
+
+    /// Builds the sound bank browser tab
+    pub fn build_sound_banks_tab(&mut self, ui: &mut egui::Ui) {
+        let Some((build, pkg_loader)) = self
+            .tab_state
+            .world_of_warships_data
+            .as_ref()
+            .map(|wows_data| wows_data.read())
+            .map(|wows_data| (wows_data.game_version, wows_data.pkg_loader.clone()))
+        else {
+            ui.label("Game data has not been loaded. Set your World of Warships directory in the settings tab.");
+            return;
+        };
+
+        let state = &mut self.tab_state.sound_bank_tab;
+        if state.containers.as_ref().map(|(containers_build, _)| *containers_build != build).unwrap_or(true) {
+            let containers = self
+                .tab_state
+                .world_of_warships_data
+                .as_ref()
+                .map(|wows_data| {
+                    wows_data
+                        .read()
+                        .file_tree
+                        .paths()
+                        .into_iter()
+                        .filter(|(path, node)| node.is_file() && SOUND_CONTAINER_FILE_TYPES.iter().any(|extension| path.to_string_lossy().ends_with(extension)))
+                        .map(|(path, node)| (PathBuf::from(&*path), node))
+                        .collect()
+                })
+                .unwrap_or_default();
+            state.containers = Some((build, containers));
+            state.selected = None;
+        }
+
+        if let Some(rx) = state.loading.as_ref() {
+            match rx.try_recv() {
+                Ok((path, Ok(container))) => {
+                    state.selected = Some((path, Arc::new(container)));
+                    state.loading = None;
+                }
+                Ok((path, Err(e))) => {
+                    *self.tab_state.timed_message.write() = Some(TimedMessage::new(format!("{} Could not read {}: {}", icons::WARNING, path.display(), e)));
+                    state.loading = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => ui.ctx().request_repaint(),
+                Err(mpsc::TryRecvError::Disconnected) => state.loading = None,
+            }
+        }
+
+        let mut load = None;
+        egui::SidePanel::left("sound_banks_left").show_inside(ui, |ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.filter).hint_text("Filter"));
+            ui.separator();
+
+            let filter = state.filter.to_lowercase();
+            let containers: Vec<&(PathBuf, FileNode)> = state
+                .containers
+                .iter()
+                .flat_map(|(_, containers)| containers.iter())
+                .filter(|(path, _)| path.to_string_lossy().to_lowercase().contains(&filter))
+                .collect();
+            let selected = state.selected.as_ref().map(|(path, _)| path);
+
+            egui::ScrollArea::vertical()
+                .id_source("sound_banks_list")
+                .show_rows(ui, 18.0, containers.len(), |ui, range| {
+                    for (path, node) in &containers[range] {
+                        if ui.selectable_label(selected == Some(path), path.to_string_lossy()).clicked() {
+                            load = Some((path.clone(), node.clone()));
+                        }
+                    }
+                });
+        });
+
+        if let Some((path, node)) = load {
+            let (tx, rx) = mpsc::channel();
+            state.loading = Some(rx);
+            let _load_thread = std::thread::spawn(move || {
+                let result = load_container(&node, &pkg_loader);
+                let _ = tx.send((path, result));
+            });
+        }
+
+        let mut extract_all = None;
+        egui::CentralPanel::default().show_inside(ui, |ui| {
+            if state.loading.is_some() {
+                ui.spinner();
+                return;
+            }
+            let Some((path, container)) = state.selected.as_ref() else {
+                ui.label("Select a sound bank or package to view its streams");
+                return;
+            };
+
+            ui.horizontal(|ui| {
+                let kind = match container.kind {
+                    SoundContainerKind::Bank => "Sound bank",
+                    SoundContainerKind::Package => "File package",
+                };
+                ui.heading(path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default());
+                ui.label(format!("{}, {} streams", kind, container.entries.len()));
+            });
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!container.entries.is_empty(), egui::Button::new(format!("{} Extract All", icons::EXPORT)))
+                    .on_hover_text("Extract every stream to <output path>/sounds")
+                    .clicked()
+                {
+                    extract_all = Some((path.clone(), Arc::clone(container)));
+                }
+                ui.checkbox(&mut state.convert_to_wav, "Convert to WAV")
+                    .on_hover_text("Decode PCM and ADPCM streams to .wav. Vorbis and Opus streams are always extracted as .wem");
+            });
+            ui.separator();
+
+            let mut message = None;
+            TableBuilder::new(ui)
+                .striped(true)
+                .resizable(true)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .column(Column::initial(100.0))
+                .column(Column::initial(100.0))
+                .column(Column::initial(80.0))
+                .column(Column::initial(70.0))
+                .column(Column::initial(80.0))
+                .column(Column::initial(70.0))
+                .column(Column::initial(80.0))
+                .column(Column::remainder())
+                .min_scrolled_height(0.0)
+                .id_salt("sound_bank_streams")
+                .header(20.0, |mut header| {
+                    for title in ["Id", "Bank", "Codec", "Channels", "Sample Rate", "Duration", "Size", ""] {
+                        header.col(|ui| {
+                            ui.strong(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, container.entries.len(), |mut row| {
+                        let entry = &container.entries[row.index()];
+                        row.col(|ui| {
+                            ui.label(entry.id.to_string());
+                        });
+                        row.col(|ui| {
+                            ui.label(entry.bank_id.map(|id| id.to_string()).unwrap_or_default());
+                        });
+                        row.col(|ui| {
+                            ui.label(entry.info.map(|info| info.codec_name()).unwrap_or_else(|| "Unknown".to_owned()));
+                        });
+                        row.col(|ui| {
+                            ui.label(entry.info.map(|info| info.channels.to_string()).unwrap_or_default());
+                        });
+                        row.col(|ui| {
+                            ui.label(entry.info.map(|info| format!("{} Hz", info.sample_rate)).unwrap_or_default());
+                        });
+                        row.col(|ui| {
+                            ui.label(entry.info.and_then(|info| info.duration_secs()).map(format_duration).unwrap_or_default());
+                        });
+                        row.col(|ui| {
+                            ui.label(humansize::format_size(entry.size(), humansize::DECIMAL));
+                        });
+                        row.col(|ui| {
+                            let can_convert = entry.info.map(|info| info.can_convert_to_wav()).unwrap_or(false);
+                            if ui
+                                .add_enabled(can_convert, egui::Button::new(icons::PLAY).small())
+                                .on_hover_text("Play in the default audio player")
+                                .clicked()
+                            {
+                                let preview_dir = std::env::temp_dir().join("wows_toolkit_sounds");
+                                let result = fs::create_dir_all(&preview_dir)
+                                    .map_err(ToolkitError::from)
+                                    .and_then(|_| write_stream(&preview_dir, container, entry, true));
+                                match result {
+                                    Ok(path) => ui.ctx().open_url(OpenUrl::new_tab(format!("file://{}", path.display()))),
+                                    Err(e) => message = Some(format!("{} Could not preview sound: {}", icons::WARNING, e)),
+                                }
+                            }
+                            if ui.add(egui::Button::new(icons::FLOPPY_DISK).small()).on_hover_text("Save").clicked() {
+                                message = match stream_contents(container, entry, state.convert_to_wav) {
+                                    Ok((file_name, data)) => rfd::FileDialog::new().set_file_name(file_name).save_file().map(|path| match fs::write(&path, data) {
+                                        Ok(()) => format!("{} Saved {}", icons::CHECK_CIRCLE, path.display()),
+                                        Err(e) => format!("{} Could not save sound: {}", icons::WARNING, e),
+                                    }),
+                                    Err(e) => Some(format!("{} Could not convert sound: {}", icons::WARNING, e)),
+                                };
+                            }
+                        });
+                    });
+                });
+
+            if let Some(message) = message {
+                *self.tab_state.timed_message.write() = Some(TimedMessage::new(message));
+            }
+        });
+
+        if let Some((path, container)) = extract_all {
+            self.extract_sound_streams(&path, container);
+        }
+    }
+}
@@ -1,10 +1,10 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     fs::{read_dir, File},
     io::Cursor,
     path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         mpsc::{self, TryRecvError},
         Arc, Mutex,
     },
@@ -36,11 +36,15 @@ use wowsunpack::{
 use zip::ZipArchive;
 
 use crate::{
+    build_diff::BuildDiff,
     build_tracker,
     error::ToolkitError,
     game_params::load_game_params,
-    player_tracker::{self, PlayerTracker},
+    game_params_diff::GameParamsDiff,
+    player_store::PlayerStore,
+    replay_archive::{read_archived_replay, ReplayArchive},
     replay_parser::Replay,
+    ship_stats::ShipDatabase,
     twitch::{self, Token, TwitchState, TwitchUpdate},
     wows_data::{self, ShipIcon, WorldOfWarshipsData},
     WowsToolkitApp,
@@ -54,6 +58,9 @@ pub struct DownloadProgress {
 pub struct BackgroundTask {
     pub receiver: mpsc::Receiver<Result<BackgroundTaskCompletion, ToolkitError>>,
     pub kind: BackgroundTaskKind,
+    pub progress: Arc<TaskProgress>,
+    /// Whether the task's thread checks [TaskProgress::is_cancelled] and still sends a result when cancelled
+    pub cooperative_cancel: bool,
 }
 
 pub enum BackgroundTaskKind {
@@ -64,42 +71,111 @@ pub enum BackgroundTaskKind {
         last_progress: Option<DownloadProgress>,
     },
     PopulatePlayerInspectorFromReplays,
+    ArchivingReplays,
+    ComparingBuilds,
+    ComparingGameParams,
+    LoadingShipStats,
+}
+
+impl BackgroundTaskKind {
+    /// Whether starting another task of this kind makes a running one's result stale
+    pub fn is_superseded_by_newer(&self) -> bool {
+        matches!(self, BackgroundTaskKind::LoadingData | BackgroundTaskKind::LoadingReplay)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            BackgroundTaskKind::LoadingData => "Loading game data",
+            BackgroundTaskKind::LoadingReplay => "Loading replay",
+            BackgroundTaskKind::Updating { .. } => "Downloading update",
+            BackgroundTaskKind::PopulatePlayerInspectorFromReplays => "Populating player inspector from historical replays",
+            BackgroundTaskKind::ArchivingReplays => "Archiving old replays",
+            BackgroundTaskKind::ComparingBuilds => "Comparing game builds",
+            BackgroundTaskKind::ComparingGameParams => "Comparing GameParams",
+            BackgroundTaskKind::LoadingShipStats => "Loading ship data",
+        }
+    }
+}
+
+/// Progress and cancellation shared between a background task's thread and the UI.
+/// Tasks which don't report progress are shown with a spinner, and tasks which
+/// don't check for cancellation have their result discarded when cancelled.
+#[derive(Default)]
+pub struct TaskProgress {
+    done: AtomicU64,
+    total: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl TaskProgress {
+    pub fn set_total(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    pub fn increment(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, done: u64, total: u64) {
+        self.done.store(done, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// How far along the task is, if it reports progress
+    pub fn fraction(&self) -> Option<f32> {
+        let total = self.total.load(Ordering::Relaxed);
+        (total > 0).then(|| self.done.load(Ordering::Relaxed) as f32 / total as f32)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
 }
 
 impl BackgroundTask {
-    pub fn build_description(&mut self, ui: &mut egui::Ui) -> Option<Result<BackgroundTaskCompletion, ToolkitError>> {
-        match self.receiver.try_recv() {
-            Ok(result) => Some(result),
-            Err(TryRecvError::Empty) => {
-                match &mut self.kind {
-                    BackgroundTaskKind::LoadingData => {
-                        ui.spinner();
-                        ui.label("Loading game data...");
-                    }
-                    BackgroundTaskKind::LoadingReplay => {
-                        ui.spinner();
-                        ui.label("Loading replay...");
-                    }
-                    BackgroundTaskKind::Updating { rx, last_progress } => {
-                        match rx.try_recv() {
-                            Ok(progress) => {
-                                *last_progress = Some(progress);
-                            }
-                            Err(TryRecvError::Empty) => {}
-                            Err(TryRecvError::Disconnected) => {}
-                        }
+    pub fn new(receiver: mpsc::Receiver<Result<BackgroundTaskCompletion, ToolkitError>>, kind: BackgroundTaskKind) -> Self {
+        BackgroundTask {
+            receiver,
+            kind,
+            progress: Default::default(),
+            cooperative_cancel: false,
+        }
+    }
 
-                        if let Some(progress) = last_progress {
-                            ui.add(egui::ProgressBar::new(progress.downloaded as f32 / progress.total as f32).text("Downloading Update"));
-                        }
-                    }
-                    BackgroundTaskKind::PopulatePlayerInspectorFromReplays => {
-                        ui.spinner();
-                        ui.label("Populating player inspector from historical replays...");
-                    }
-                }
-                None
+    /// Creates a task whose thread reports to `progress` and stops early once it is cancelled
+    pub fn with_progress(receiver: mpsc::Receiver<Result<BackgroundTaskCompletion, ToolkitError>>, kind: BackgroundTaskKind, progress: Arc<TaskProgress>) -> Self {
+        BackgroundTask {
+            receiver,
+            kind,
+            progress,
+            cooperative_cancel: true,
+        }
+    }
+
+    /// A task which has already failed, so that its error is reported like any other task's
+    pub fn failed(kind: BackgroundTaskKind, error: ToolkitError) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let _ = tx.send(Err(error));
+
+        BackgroundTask::new(rx, kind)
+    }
+
+    /// Returns the task's result once it has finished
+    pub fn poll(&mut self) -> Option<Result<BackgroundTaskCompletion, ToolkitError>> {
+        if let BackgroundTaskKind::Updating { rx, last_progress } = &mut self.kind {
+            while let Ok(progress) = rx.try_recv() {
+                self.progress.set(progress.downloaded, progress.total);
+                *last_progress = Some(progress);
             }
+        }
+
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
             Err(TryRecvError::Disconnected) => Some(Err(ToolkitError::BackgroundTaskCompleted)),
         }
     }
@@ -116,6 +192,13 @@ pub enum BackgroundTaskCompletion {
     },
     UpdateDownloaded(PathBuf),
     PopulatePlayerInspectorFromReplays,
+    ReplaysArchived {
+        archive: ReplayArchive,
+        replays: Vec<(PathBuf, Arc<RwLock<Replay>>)>,
+    },
+    BuildsCompared(Arc<BuildDiff>),
+    GameParamsCompared(Arc<GameParamsDiff>),
+    ShipStatsLoaded(Arc<ShipDatabase>),
 }
 
 impl std::fmt::Debug for BackgroundTaskCompletion {
@@ -130,22 +213,31 @@ impl std::fmt::Debug for BackgroundTaskCompletion {
             Self::ReplayLoaded { replay } => f.debug_struct("ReplayLoaded").field("replay", &"<...>").finish(),
             Self::UpdateDownloaded(arg0) => f.debug_tuple("UpdateDownloaded").field(arg0).finish(),
             Self::PopulatePlayerInspectorFromReplays => f.write_str("PopulatePlayerInspectorFromReplays"),
+            Self::ReplaysArchived { archive: _, replays } => f.debug_struct("ReplaysArchived").field("replays", &replays.len()).finish(),
+            Self::BuildsCompared(diff) => f
+                .debug_struct("BuildsCompared")
+                .field("old_build", &diff.old_build)
+                .field("new_build", &diff.new_build)
+                .field("entries", &diff.entries.len())
+                .finish(),
+            Self::GameParamsCompared(diff) => f.debug_struct("GameParamsCompared").field("changes", &diff.change_count()).finish(),
+            Self::ShipStatsLoaded(_) => f.write_str("ShipStatsLoaded"),
         }
     }
 }
 
-fn replay_filepaths(replays_dir: &Path) -> Option<Vec<PathBuf>> {
+fn replay_filepaths(replays_dir: &Path) -> Result<Option<Vec<PathBuf>>, ToolkitError> {
     let mut files = Vec::new();
 
     if replays_dir.exists() {
-        for file in std::fs::read_dir(&replays_dir).expect("failed to read replay dir").flatten() {
-            if !file.file_type().expect("failed to get file type").is_file() {
+        for file in std::fs::read_dir(&replays_dir)?.flatten() {
+            if !file.file_type().map(|ty| ty.is_file()).unwrap_or(false) {
                 continue;
             }
 
             let file_path = file.path();
 
-            if let Some("wowsreplay") = file_path.extension().map(|s| s.to_str().expect("failed to convert extension to str")) {
+            if let Some("wowsreplay") = file_path.extension().and_then(|s| s.to_str()) {
                 if file.file_name() != "temp.wowsreplay" {
                     files.push(file_path);
                 }
@@ -153,12 +245,13 @@ fn replay_filepaths(replays_dir: &Path) -> Option<Vec<PathBuf>> {
         }
     }
     if !files.is_empty() {
-        files.sort_by_key(|a| a.metadata().unwrap().created().unwrap());
+        // Replays whose creation time can't be read sort last
+        files.sort_by_cached_key(|a| a.metadata().and_then(|metadata| metadata.created()).ok());
         files.reverse();
 
-        Some(files)
+        Ok(Some(files))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -173,19 +266,30 @@ fn load_ship_icons(file_tree: FileNode, pkg_loader: &PkgFileLoader) -> HashMap<S
         Species::Auxiliary,
     ];
 
-    let icons: HashMap<Species, Arc<ShipIcon>> = HashMap::from_iter(species.iter().map(|species| {
-        let path = format!("gui/fla/minimap/ship_icons/minimap_{}.svg", <&'static str>::from(species).to_ascii_lowercase());
-        let icon_node = file_tree.find(&path).expect("failed to find file");
-
-        let mut icon_data = Vec::with_capacity(icon_node.file_info().unwrap().unpacked_size as usize);
-        icon_node.read_file(pkg_loader, &mut icon_data).expect("failed to read ship icon");
-
-        (species.clone(), Arc::new(ShipIcon { path, data: icon_data }))
+    // A missing icon only costs the class icon in the replay view, so don't fail the whole load
+    let icons: HashMap<Species, Arc<ShipIcon>> = HashMap::from_iter(species.iter().filter_map(|species| match load_ship_icon(&file_tree, pkg_loader, species) {
+        Ok(icon) => Some((species.clone(), Arc::new(icon))),
+        Err(e) => {
+            error!("{}", e);
+            None
+        }
     }));
 
     icons
 }
 
+fn load_ship_icon(file_tree: &FileNode, pkg_loader: &PkgFileLoader, species: &Species) -> Result<ShipIcon, ToolkitError> {
+    let path = format!("gui/fla/minimap/ship_icons/minimap_{}.svg", <&'static str>::from(species).to_ascii_lowercase());
+    let icon_node = file_tree.find(&path).map_err(|_| ToolkitError::MissingShipIcon(path.clone()))?;
+
+    let mut icon_data = Vec::with_capacity(icon_node.file_info().map(|info| info.unpacked_size as usize).unwrap_or_default());
+    icon_node
+        .read_file(pkg_loader, &mut icon_data)
+        .map_err(|_| ToolkitError::MissingShipIcon(path.clone()))?;
+
+    Ok(ShipIcon { path, data: icon_data })
+}
+
 fn current_build_from_preferences(path: &Path) -> Option<String> {
     let data = std::fs::read_to_string(path).ok()?;
     let start_of_node = data.find("<last_server_version>")?;
@@ -195,7 +299,7 @@ fn current_build_from_preferences(path: &Path) -> Option<String> {
     Some(version_str.to_string())
 }
 
-pub fn load_wows_files(wows_directory: PathBuf, locale: &str) -> Result<BackgroundTaskCompletion, crate::error::ToolkitError> {
+pub fn load_wows_files(wows_directory: PathBuf, locale: &str, max_cache_size: u64) -> Result<BackgroundTaskCompletion, crate::error::ToolkitError> {
     let mut idx_files = Vec::new();
     let bin_dir = wows_directory.join("bin");
     if !wows_directory.exists() || !bin_dir.exists() {
@@ -217,14 +321,16 @@ pub fn load_wows_files(wows_directory: PathBuf, locale: &str) -> Result<Backgrou
             }
 
             // We want to build the version string without the build component to get the replays dir
-            let friendly_build = parts[..=2].join(".");
-            let friendly_build_with_extra_component = friendly_build.clone() + ".0";
-
-            for temp_replays_dir in [replays_dir.join(friendly_build), replays_dir.join(friendly_build_with_extra_component)] {
-                debug!("Looking for build-specific replays dir at {:?}", temp_replays_dir);
-                if temp_replays_dir.exists() {
-                    replays_dir = temp_replays_dir;
-                    break;
+            if let Some(version_parts) = parts.get(..=2) {
+                let friendly_build = version_parts.join(".");
+                let friendly_build_with_extra_component = friendly_build.clone() + ".0";
+
+                for temp_replays_dir in [replays_dir.join(friendly_build), replays_dir.join(friendly_build_with_extra_component)] {
+                    debug!("Looking for build-specific replays dir at {:?}", temp_replays_dir);
+                    if temp_replays_dir.exists() {
+                        replays_dir = temp_replays_dir;
+                        break;
+                    }
                 }
             }
         }
@@ -257,11 +363,16 @@ pub fn load_wows_files(wows_directory: PathBuf, locale: &str) -> Result<Backgrou
 
     let number = latest_build.unwrap();
     for file in read_dir(wows_directory.join("bin").join(format!("{}", number)).join("idx"))? {
-        let file = file.unwrap();
-        if file.file_type().unwrap().is_file() {
-            let file_data = std::fs::read(file.path()).unwrap();
+        let file = file?;
+        if file.file_type()?.is_file() {
+            let path = file.path();
+            let file_data = std::fs::read(&path)?;
             let mut file = Cursor::new(file_data.as_slice());
-            idx_files.push(idx::parse(&mut file).unwrap());
+            let idx_file = idx::parse(&mut file).map_err(|e| ToolkitError::InvalidIdxFile {
+                path: path.clone(),
+                reason: format!("{:?}", e),
+            })?;
+            idx_files.push(idx_file);
         }
     }
 
@@ -275,16 +386,19 @@ pub fn load_wows_files(wows_directory: PathBuf, locale: &str) -> Result<Backgrou
     let file_tree = idx::build_file_tree(idx_files.as_slice());
     let files = file_tree.paths();
 
-    let language_tag: LanguageTag = locale.parse().unwrap();
-    let attempted_dirs = [locale, language_tag.primary_language(), "en"];
+    let language_tag: Option<LanguageTag> = locale.parse().ok();
+    let attempted_dirs = [Some(locale), language_tag.as_ref().map(|tag| tag.primary_language()), Some("en")];
     let mut found_catalog = None;
-    for dir in attempted_dirs {
+    for dir in attempted_dirs.into_iter().flatten() {
         let localization_path = wows_directory.join(format!("bin/{}/res/texts/{}/LC_MESSAGES/global.mo", number, dir));
         if !localization_path.exists() {
             continue;
         }
-        let global = File::open(localization_path).expect("failed to open localization file");
-        let catalog = Catalog::parse(global).expect("could not parse catalog");
+        let global = File::open(&localization_path)?;
+        let catalog = Catalog::parse(global).map_err(|e| ToolkitError::InvalidGameTextCatalog {
+            path: localization_path.clone(),
+            reason: e.to_string(),
+        })?;
         found_catalog = Some(catalog);
         break;
     }
@@ -292,7 +406,7 @@ pub fn load_wows_files(wows_directory: PathBuf, locale: &str) -> Result<Backgrou
     debug!("Loading GameParams");
 
     // Try loading GameParams.data
-    let metadata_provider = load_game_params(&file_tree, &pkg_loader, number).ok().map(|mut metadata_provider| {
+    let metadata_provider = load_game_params(&file_tree, &pkg_loader, number, max_cache_size).ok().map(|mut metadata_provider| {
         if let Some(catalog) = found_catalog {
             metadata_provider.set_translations(catalog)
         }
@@ -303,22 +417,20 @@ pub fn load_wows_files(wows_directory: PathBuf, locale: &str) -> Result<Backgrou
     debug!("Loading icons");
     let icons = load_ship_icons(file_tree.clone(), &pkg_loader);
 
-    let data = WorldOfWarshipsData {
-        game_metadata: metadata_provider.clone(),
-        file_tree: file_tree,
-        pkg_loader: pkg_loader,
-        filtered_files: files,
-        game_version: number,
-        ship_icons: icons,
-        replays_dir: replays_dir.clone(),
-    };
+    debug!("Loading replay archive");
+    let replay_archive = ReplayArchive::load(&replays_dir).unwrap_or_else(|e| {
+        error!("failed to load replay archive: {:?}", e);
+        Default::default()
+    });
 
     debug!("Loading replays");
-    let replays = replay_filepaths(&replays_dir).map(|replays| {
+    // Replays can't be shown without GameParams
+    let replay_paths = if metadata_provider.is_some() { replay_filepaths(&replays_dir)? } else { None };
+    let mut replays = replay_paths.map(|replays| {
         let iter = replays.into_iter().filter_map(|path| {
             // Filter out any replays that don't parse correctly
             let replay_file = ReplayFile::from_file(&path).ok()?;
-            let replay = Arc::new(RwLock::new(Replay::new(replay_file, metadata_provider.clone().unwrap())));
+            let replay = Arc::new(RwLock::new(Replay::new(replay_file, metadata_provider.clone()?)));
 
             Some((path, replay))
         });
@@ -326,6 +438,23 @@ pub fn load_wows_files(wows_directory: PathBuf, locale: &str) -> Result<Backgrou
         HashMap::from_iter(iter)
     });
 
+    if let Some(metadata_provider) = metadata_provider.as_ref() {
+        if !replay_archive.is_empty() {
+            replays.get_or_insert_with(HashMap::new).extend(replay_archive.library_replays(metadata_provider));
+        }
+    }
+
+    let data = WorldOfWarshipsData {
+        game_metadata: metadata_provider.clone(),
+        file_tree: file_tree,
+        pkg_loader: pkg_loader,
+        filtered_files: files,
+        game_version: number,
+        ship_icons: icons,
+        replays_dir: replays_dir.clone(),
+        replay_archive,
+    };
+
     debug!("Sending background task completion");
 
     Ok(BackgroundTaskCompletion::DataLoaded {
@@ -384,13 +513,13 @@ pub fn start_download_update_task(runtime: &Runtime, release: &Asset) -> Backgro
         let _ = tx.send(result);
     });
 
-    BackgroundTask {
-        receiver: rx,
-        kind: BackgroundTaskKind::Updating {
+    BackgroundTask::new(
+        rx,
+        BackgroundTaskKind::Updating {
             rx: progress_rx,
             last_progress: None,
         },
-    }
+    )
 }
 
 async fn update_twitch_token(twitch_state: &RwLock<TwitchState>, token: &Token) {
@@ -513,7 +642,7 @@ fn parse_replay_data_in_background(
     wows_data: &WorldOfWarshipsData,
     client: &reqwest::blocking::Client,
     should_send_replays: Arc<AtomicBool>,
-    player_tracker: Arc<RwLock<PlayerTracker>>,
+    player_store: &PlayerStore,
 ) -> Result<(), ()> {
     // Files may be getting written to. If we fail to parse the replay,
     // let's try try to parse this at least 3 times.
@@ -535,6 +664,11 @@ fn parse_replay_data_in_background(
                             if should_send_replays.load(Ordering::Relaxed) {
                                 // Send the replay builds to the remote server
                                 for player in report.player_entities() {
+                                    // Entities without player info can't be attributed to a realm
+                                    let Some(realm) = player.player().map(|player| player.realm().to_string()) else {
+                                        continue;
+                                    };
+
                                     #[cfg(not(feature = "shipbuilds_debugging"))]
                                     let url = "https://shipbuilds.com/api/ship_builds";
                                     #[cfg(feature = "shipbuilds_debugging")]
@@ -545,7 +679,7 @@ fn parse_replay_data_in_background(
                                         .post(url)
                                         .json(&build_tracker::BuildTrackerPayload::build_from(
                                             player,
-                                            player.player().map(|player| player.realm().to_string()).unwrap(),
+                                            realm,
                                             report.version(),
                                             game_type.clone(),
                                             &metadata_provider,
@@ -563,7 +697,9 @@ fn parse_replay_data_in_background(
 
                             // Update the player tracker
                             replay.battle_report = Some(report);
-                            player_tracker.write().update_from_replay(&replay);
+                            if let Err(e) = player_store.record_replay(&replay) {
+                                error!("failed to update player tracker: {}", e);
+                            }
 
                             return Ok(());
                         }
@@ -585,12 +721,25 @@ fn parse_replay_data_in_background(
     Err(())
 }
 
+/// Treats replays as unsent if the store can't be read, since sending one twice is harmless
+fn is_replay_sent(player_store: &PlayerStore, path: &str) -> bool {
+    player_store.is_replay_sent(path).unwrap_or_else(|e| {
+        error!("failed to check whether replay {} was sent: {}", path, e);
+        false
+    })
+}
+
+fn mark_replay_sent(player_store: &PlayerStore, path: &str) {
+    if let Err(e) = player_store.mark_replay_sent(path) {
+        error!("failed to record sent replay {}: {}", path, e);
+    }
+}
+
 pub fn start_background_parsing_thread(
     rx: mpsc::Receiver<PathBuf>,
-    sent_replays: Arc<RwLock<HashSet<String>>>,
     wows_data: Arc<RwLock<WorldOfWarshipsData>>,
     should_send_replays: Arc<AtomicBool>,
-    player_tracker: Arc<RwLock<PlayerTracker>>,
+    player_store: Arc<PlayerStore>,
 ) {
     debug!("starting background parsing thread");
     let _join_handle = std::thread::spawn(move || {
@@ -598,20 +747,11 @@ pub fn start_background_parsing_thread(
 
         #[cfg(not(feature = "shipbuilds_debugging"))]
         {
-            debug!("Attempting to prune old replay paths from settings");
-
-            // Prune files that no longer exist to prevent the settings from growing too large
-            let mut sent_replays = sent_replays.write();
-            let mut to_remove = Vec::new();
-            for file_path in &*sent_replays {
-                if !Path::new(file_path).exists() {
-                    to_remove.push(file_path.clone());
-                    // do nothing
-                }
-            }
+            debug!("Attempting to prune old replay paths from the player data store");
 
-            for file_path in to_remove {
-                sent_replays.remove(&file_path);
+            // Prune files that no longer exist to prevent the store from growing too large
+            if let Err(e) = player_store.prune_sent_replays() {
+                error!("failed to prune sent replays: {}", e);
             }
         }
 
@@ -631,12 +771,11 @@ pub fn start_background_parsing_thread(
                             }
 
                             let path_str = path.to_string_lossy();
-                            let sent_replay = { sent_replays.read().contains(path_str.as_ref()) } || cfg!(feature = "shipbuilds_debugging");
+                            let sent_replay = is_replay_sent(&player_store, &path_str) || cfg!(feature = "shipbuilds_debugging");
 
                             if !sent_replay {
-                                if let Ok(_) = parse_replay_data_in_background(&path, &*wows_data, &client, Arc::clone(&should_send_replays), Arc::clone(&player_tracker))
-                                {
-                                    sent_replays.write().insert(path_str.into_owned());
+                                if let Ok(_) = parse_replay_data_in_background(&path, &*wows_data, &client, Arc::clone(&should_send_replays), &player_store) {
+                                    mark_replay_sent(&player_store, &path_str);
                                 }
                             }
                         }
@@ -651,13 +790,13 @@ pub fn start_background_parsing_thread(
         debug!("Beginning backgorund replay receive loop");
         while let Some(path) = rx.recv().ok() {
             let path_str = path.to_string_lossy();
-            let sent_replay = { sent_replays.read().contains(path_str.as_ref()) };
+            let sent_replay = is_replay_sent(&player_store, &path_str);
 
             if !sent_replay {
                 debug!("Attempting to send replay at {}", path_str);
                 let wows_data = wows_data.read();
-                if let Ok(_) = parse_replay_data_in_background(&path, &*wows_data, &client, Arc::clone(&should_send_replays), Arc::clone(&player_tracker)) {
-                    sent_replays.write().insert(path_str.into_owned());
+                if let Ok(_) = parse_replay_data_in_background(&path, &*wows_data, &client, Arc::clone(&should_send_replays), &player_store) {
+                    mark_replay_sent(&player_store, &path_str);
                 }
             } else {
                 debug!("Not sending replay as it's already been sent");
@@ -666,33 +805,52 @@ pub fn start_background_parsing_thread(
     });
 }
 
-pub fn start_populating_player_inspector(
-    replays: Vec<PathBuf>,
-    wows_data: Arc<RwLock<WorldOfWarshipsData>>,
-    player_tracker: Arc<RwLock<PlayerTracker>>,
-) -> BackgroundTask {
+pub fn start_populating_player_inspector(replays: Vec<PathBuf>, wows_data: Arc<RwLock<WorldOfWarshipsData>>, player_store: Arc<PlayerStore>) -> BackgroundTask {
     let (tx, rx) = mpsc::channel();
+    let progress = Arc::new(TaskProgress::default());
+    let thread_progress = Arc::clone(&progress);
     std::thread::spawn(move || {
+        thread_progress.set_total(replays.len() as u64);
         for path in replays {
-            match ReplayFile::from_file(&path) {
+            if thread_progress.is_cancelled() {
+                break;
+            }
+            thread_progress.increment();
+
+            // Only hold the lock long enough to look things up so the UI isn't blocked while decompressing
+            let (archived, metadata_provider, game_version) = {
+                let wows_data = wows_data.read();
+                let archived = wows_data
+                    .replay_archive
+                    .entry_for_path(&path)
+                    .map(|entry| (wows_data.replay_archive.archive_path(entry), entry.clone()));
+
+                (archived, wows_data.game_metadata.clone(), wows_data.game_version)
+            };
+            let replay_file = if let Some((archive_path, entry)) = archived {
+                read_archived_replay(&archive_path, &entry).map_err(|e| format!("{:?}", e))
+            } else {
+                ReplayFile::from_file(&path).map_err(|e| format!("{:?}", e))
+            };
+            match replay_file {
                 Ok(replay_file) => {
-                    let wows_data = wows_data.read();
-                    let (metadata_provider, game_version) = { (wows_data.game_metadata.clone(), wows_data.game_version) };
                     if let Some(metadata_provider) = metadata_provider {
                         let mut replay = Replay::new(replay_file, Arc::clone(&metadata_provider));
                         match replay.parse(game_version.to_string().as_str()) {
                             Ok(report) => {
                                 replay.battle_report = Some(report);
-                                player_tracker.write().update_from_replay(&replay);
+                                if let Err(e) = player_store.record_replay(&replay) {
+                                    error!("error attempting to update player tracker from replay: {:?}", e);
+                                }
                             }
                             Err(e) => {
-                                println!("error attempting to parse replay for replay inspector: {:?}", e);
+                                error!("error attempting to parse replay for replay inspector: {:?}", e);
                             }
                         }
                     }
                 }
                 Err(e) => {
-                    println!("error attempting to open replay for replay inspector: {:?}", e);
+                    error!("error attempting to open replay for replay inspector: {:?}", e);
                 }
             }
         }
@@ -700,10 +858,7 @@ pub fn start_populating_player_inspector(
         let _ = tx.send(Ok(BackgroundTaskCompletion::PopulatePlayerInspectorFromReplays));
     });
 
-    BackgroundTask {
-        receiver: rx,
-        kind: BackgroundTaskKind::PopulatePlayerInspectorFromReplays,
-    }
+    BackgroundTask::with_progress(rx, BackgroundTaskKind::PopulatePlayerInspectorFromReplays, progress)
 }
 
 pub fn begin_startup_tasks(toolkit: &WowsToolkitApp, token_rx: tokio::sync::mpsc::Receiver<TwitchUpdate>) {
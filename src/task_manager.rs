@@ -0,0 +1,221 @@
+//! Runs any number of [BackgroundTask]s at once and keeps a short history of
+//! the ones which finished, shown in the panel above the status bar.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+use egui::Color32;
+
+use crate::{
+    error::ToolkitError,
+    icons,
+    task::{BackgroundTask, BackgroundTaskCompletion, BackgroundTaskKind},
+    unpacker_jobs::format_duration,
+};
+
+const MAX_HISTORY: usize = 50;
+
+struct ManagedTask {
+    task: BackgroundTask,
+    started: Instant,
+    /// Set when a newer task of the same kind was started. Its result is dropped.
+    superseded: bool,
+}
+
+pub enum TaskOutcome {
+    Completed,
+    Failed(String),
+    Cancelled,
+}
+
+pub struct TaskHistoryEntry {
+    pub description: &'static str,
+    pub finished_at: DateTime<Local>,
+    pub duration: Duration,
+    pub outcome: TaskOutcome,
+}
+
+/// A task which is no longer running. Cancelled tasks which don't check for
+/// cancellation finish with [ToolkitError::BackgroundTaskCancelled].
+pub struct FinishedTask {
+    pub kind: BackgroundTaskKind,
+    pub result: Result<BackgroundTaskCompletion, ToolkitError>,
+}
+
+#[derive(Default)]
+pub struct TaskManager {
+    tasks: Vec<ManagedTask>,
+    /// Tasks which were cancelled since the last [TaskManager::poll]
+    abandoned: Vec<FinishedTask>,
+    history: VecDeque<TaskHistoryEntry>,
+    pub expanded: bool,
+}
+
+impl TaskManager {
+    /// Starts tracking `task`. Starting a task of a kind where only the latest
+    /// result matters cancels the running ones, so the last one started wins.
+    pub fn start(&mut self, task: BackgroundTask) {
+        if task.kind.is_superseded_by_newer() {
+            let kind = std::mem::discriminant(&task.kind);
+            while let Some(index) = self
+                .tasks
+                .iter()
+                .position(|managed| !managed.superseded && std::mem::discriminant(&managed.task.kind) == kind)
+            {
+                self.tasks[index].superseded = true;
+                self.cancel(index);
+            }
+        }
+
+        self.tasks.push(ManagedTask {
+            task,
+            started: Instant::now(),
+            superseded: false,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Whether a task matching `predicate` is still running
+    pub fn is_running(&self, predicate: impl Fn(&BackgroundTaskKind) -> bool) -> bool {
+        self.tasks.iter().any(|managed| predicate(&managed.task.kind))
+    }
+
+    fn record(&mut self, description: &'static str, started: Instant, outcome: TaskOutcome) {
+        if self.history.len() == MAX_HISTORY {
+            self.history.pop_back();
+        }
+        self.history.push_front(TaskHistoryEntry {
+            description,
+            finished_at: Local::now(),
+            duration: started.elapsed(),
+            outcome,
+        });
+    }
+
+    /// Removes every task which finished or was cancelled since the last call
+    pub fn poll(&mut self) -> Vec<FinishedTask> {
+        let mut finished = std::mem::take(&mut self.abandoned);
+
+        let mut i = 0;
+        while i < self.tasks.len() {
+            let Some(result) = self.tasks[i].task.poll() else {
+                i += 1;
+                continue;
+            };
+
+            let ManagedTask { task, started, superseded } = self.tasks.remove(i);
+            let result = if superseded { Err(ToolkitError::BackgroundTaskCancelled) } else { result };
+            let outcome = match &result {
+                _ if task.progress.is_cancelled() => TaskOutcome::Cancelled,
+                Ok(_) | Err(ToolkitError::BackgroundTaskCompleted) => TaskOutcome::Completed,
+                Err(e) => TaskOutcome::Failed(e.to_string()),
+            };
+            self.record(task.kind.description(), started, outcome);
+            finished.push(FinishedTask { kind: task.kind, result });
+        }
+
+        finished
+    }
+
+    fn cancel(&mut self, index: usize) {
+        let progress = &self.tasks[index].task.progress;
+        progress.cancel();
+
+        // Tasks which don't check for cancellation are abandoned and their result dropped
+        if !self.tasks[index].task.cooperative_cancel {
+            let ManagedTask { task, started, .. } = self.tasks.remove(index);
+            self.record(task.kind.description(), started, TaskOutcome::Cancelled);
+            self.abandoned.push(FinishedTask {
+                kind: task.kind,
+                result: Err(ToolkitError::BackgroundTaskCancelled),
+            });
+        }
+    }
+
+    /// Builds the one-line summary of running tasks for the status bar
+    pub fn build_summary(&mut self, ui: &mut egui::Ui) {
+        let toggle_icon = if self.expanded { icons::CARET_DOWN } else { icons::CARET_UP };
+        let toggle_text = if self.tasks.is_empty() {
+            toggle_icon.to_owned()
+        } else {
+            format!("{} {} running", toggle_icon, self.tasks.len())
+        };
+        if ui.button(toggle_text).on_hover_text("Show background tasks").clicked() {
+            self.expanded = !self.expanded;
+        }
+
+        let Some(first) = self.tasks.first() else {
+            return;
+        };
+
+        ui.spinner();
+        let mut text = format!("{}...", first.task.kind.description());
+        if let Some(fraction) = first.task.progress.fraction() {
+            text.push_str(&format!(" {:.0}%", fraction * 100.0));
+        }
+        if self.tasks.len() > 1 {
+            text.push_str(&format!(" (+{} more)", self.tasks.len() - 1));
+        }
+        ui.label(text);
+    }
+
+    /// Builds the expanded list of running tasks and recently finished ones
+    pub fn build_task_list(&mut self, ui: &mut egui::Ui) {
+        let mut cancel = None;
+        egui::ScrollArea::vertical().id_source("background_task_list").show(ui, |ui| {
+            ui.strong("Running");
+            if self.tasks.is_empty() {
+                ui.label("No tasks are running");
+            }
+            for (i, managed) in self.tasks.iter().enumerate() {
+                let progress = &managed.task.progress;
+                ui.horizontal(|ui| {
+                    if progress.is_cancelled() {
+                        ui.spinner();
+                    } else if ui.button(icons::STOP).on_hover_text("Cancel").clicked() {
+                        cancel = Some(i);
+                    }
+
+                    let text = format!("{} ({})", managed.task.kind.description(), format_duration(managed.started.elapsed()));
+                    match progress.fraction() {
+                        Some(fraction) => {
+                            ui.add(egui::ProgressBar::new(fraction).text(text));
+                        }
+                        None => {
+                            ui.spinner();
+                            ui.label(text);
+                        }
+                    }
+                });
+            }
+
+            ui.separator();
+            ui.strong("History");
+            for entry in &self.history {
+                ui.horizontal(|ui| {
+                    match &entry.outcome {
+                        TaskOutcome::Completed => ui.label(icons::CHECK_CIRCLE),
+                        TaskOutcome::Failed(e) => ui.colored_label(Color32::LIGHT_RED, icons::WARNING).on_hover_text(e.as_str()),
+                        TaskOutcome::Cancelled => ui.label(icons::PROHIBIT),
+                    };
+                    ui.label(format!(
+                        "{} {} ({})",
+                        entry.finished_at.format("%H:%M:%S"),
+                        entry.description,
+                        format_duration(entry.duration)
+                    ));
+                });
+            }
+        });
+
+        if let Some(i) = cancel {
+            self.cancel(i);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Long-running unpacker operations (extraction, verification, GameParams
+//! dumps) run as jobs. Each job has its own progress and cancellation flag so
+//! that several can run at once.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+use crate::{error::ToolkitError, extraction_manifest::ExtractionReport, icons};
+
+/// Progress shared between a job's worker threads and the UI
+#[derive(Default)]
+pub struct JobProgress {
+    files_done: AtomicUsize,
+    files_total: AtomicUsize,
+    bytes_done: AtomicU64,
+    bytes_total: AtomicU64,
+    current_file: Mutex<String>,
+    cancelled: AtomicBool,
+}
+
+impl JobProgress {
+    pub fn set_totals(&self, files: usize, bytes: u64) {
+        self.files_total.store(files, Ordering::Relaxed);
+        self.bytes_total.store(bytes, Ordering::Relaxed);
+    }
+
+    pub fn file_started(&self, name: String) {
+        *self.current_file.lock() = name;
+    }
+
+    pub fn file_finished(&self, bytes: u64) {
+        self.files_done.fetch_add(1, Ordering::Relaxed);
+        self.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// What a job produced when it finished
+pub enum JobOutcome {
+    Message(String),
+    Report(ExtractionReport),
+}
+
+pub struct UnpackerJob {
+    pub description: String,
+    progress: Arc<JobProgress>,
+    started: Instant,
+    handle: JoinHandle<Result<JobOutcome, ToolkitError>>,
+}
+
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+impl UnpackerJob {
+    pub fn spawn<F>(description: impl Into<String>, job: F) -> Self
+    where
+        F: FnOnce(&JobProgress) -> Result<JobOutcome, ToolkitError> + Send + 'static,
+    {
+        let progress = Arc::new(JobProgress::default());
+        let thread_progress = Arc::clone(&progress);
+        let handle = std::thread::spawn(move || job(&thread_progress));
+
+        UnpackerJob {
+            description: description.into(),
+            progress,
+            started: Instant::now(),
+            handle,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+
+    /// The job's progress, for cancelling it from outside the job list
+    pub fn progress(&self) -> Arc<JobProgress> {
+        Arc::clone(&self.progress)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the job's thread and returns its result. A panicking job is
+    /// reported the same way as a job which failed.
+    pub fn join(self) -> Result<JobOutcome, ToolkitError> {
+        let description = self.description;
+        self.handle.join().unwrap_or_else(|_| Err(ToolkitError::UnpackerJobFailed(description)))
+    }
+
+    /// Builds the progress display for this job. Returns whether cancellation was requested.
+    pub fn build_progress(&self, ui: &mut egui::Ui) -> bool {
+        let progress = &self.progress;
+        let files_done = progress.files_done.load(Ordering::Relaxed);
+        let files_total = progress.files_total.load(Ordering::Relaxed);
+        let bytes_done = progress.bytes_done.load(Ordering::Relaxed);
+        let bytes_total = progress.bytes_total.load(Ordering::Relaxed);
+
+        let elapsed = self.started.elapsed();
+        let throughput = bytes_done as f64 / elapsed.as_secs_f64().max(0.001);
+        let eta = (throughput > 0.0 && bytes_total > bytes_done).then(|| Duration::from_secs_f64((bytes_total - bytes_done) as f64 / throughput));
+
+        let fraction = if bytes_total > 0 {
+            bytes_done as f32 / bytes_total as f32
+        } else if files_total > 0 {
+            files_done as f32 / files_total as f32
+        } else {
+            0.0
+        };
+
+        let mut cancel = false;
+        ui.horizontal(|ui| {
+            if progress.is_cancelled() {
+                ui.spinner();
+            } else if ui.button(icons::STOP).on_hover_text("Cancel").clicked() {
+                cancel = true;
+            }
+
+            let mut text = format!("{}: {}/{} files", self.description, files_done, files_total);
+            if bytes_total > 0 {
+                text.push_str(&format!(
+                    ", {} / {} ({}/s)",
+                    humansize::format_size(bytes_done, humansize::DECIMAL),
+                    humansize::format_size(bytes_total, humansize::DECIMAL),
+                    humansize::format_size(throughput as u64, humansize::DECIMAL)
+                ));
+            }
+            if let Some(eta) = eta {
+                text.push_str(&format!(", {} remaining", format_duration(eta)));
+            }
+
+            ui.add(egui::ProgressBar::new(fraction).text(text))
+                .on_hover_text(progress.current_file.lock().as_str());
+        });
+
+        cancel
+    }
+}
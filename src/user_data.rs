@@ -0,0 +1,221 @@
+//! Exporting and importing everything the user has entered or collected, so it
+//! can be moved to another PC or shared.
+//!
+//! The export is a JSON file holding the [Settings] and the contents of the
+//! [PlayerStore] along with a format version. The Twitch token is never exported,
+//! and the machine-specific paths are left alone when importing.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{Settings, TimedMessage, ToolkitTabViewer},
+    error::ToolkitError,
+    icons,
+    player_store::{PlayerStore, StoredPlayer},
+    replay_parser::ReplayAnnotation,
+    settings_migrations,
+};
+
+/// Bumped whenever the export layout changes in a way older versions can't read
+pub const USER_DATA_FORMAT_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct UserDataExport {
+    format_version: u32,
+    app_version: &'static str,
+    exported_at: DateTime<Local>,
+    settings: serde_json::Value,
+    players: Vec<StoredPlayer>,
+    sent_replays: Vec<String>,
+}
+
+/// Version 1 exports kept the players and sent replays inside the settings
+#[derive(Deserialize)]
+struct UserDataImport {
+    format_version: u32,
+    settings: Settings,
+    #[serde(default)]
+    players: Vec<StoredPlayer>,
+    #[serde(default)]
+    sent_replays: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Adds the imported players, notes, replays and filters to the existing ones
+    Merge,
+    /// Replaces all user data with the imported data
+    Replace,
+}
+
+fn user_data_error(path: &Path, e: impl std::fmt::Display) -> ToolkitError {
+    ToolkitError::UserDataFile(format!("{}: {}", path.display(), e))
+}
+
+pub fn export_user_data(path: &Path, settings: &Settings, player_store: &PlayerStore) -> Result<(), ToolkitError> {
+    let mut settings = serde_json::to_value(settings).map_err(ToolkitError::SettingsSerialization)?;
+    if let Some(settings) = settings.as_object_mut() {
+        settings.remove("twitch_token");
+    }
+
+    let export = UserDataExport {
+        format_version: USER_DATA_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION"),
+        exported_at: Local::now(),
+        settings,
+        players: player_store.players()?,
+        sent_replays: player_store.sent_replays()?,
+    };
+
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, &export).map_err(|e| user_data_error(path, e))
+}
+
+fn read_user_data(path: &Path) -> Result<UserDataImport, ToolkitError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut import: UserDataImport = serde_json::from_reader(reader).map_err(|e| user_data_error(path, e))?;
+    if import.format_version > USER_DATA_FORMAT_VERSION {
+        return Err(ToolkitError::UnsupportedUserDataVersion(import.format_version));
+    }
+
+    settings_migrations::migrate_settings(&mut import.settings);
+    {
+        let mut player_tracker = import.settings.player_tracker.write();
+        import.players.extend(player_tracker.legacy_players());
+        player_tracker.clear_legacy_players();
+    }
+    import.sent_replays.extend(import.settings.sent_replays.drain());
+
+    Ok(import)
+}
+
+fn merge_annotation(annotation: &mut ReplayAnnotation, other: ReplayAnnotation) {
+    annotation.favorite |= other.favorite;
+    annotation.tags.extend(other.tags);
+    if annotation.notes.is_empty() {
+        annotation.notes = other.notes;
+    } else if !other.notes.is_empty() && !annotation.notes.contains(other.notes.as_str()) {
+        annotation.notes = format!("{}\n\n{}", annotation.notes, other.notes);
+    }
+}
+
+/// Applies imported settings and players. Paths, the locale and the Twitch token
+/// belong to this machine and are never changed.
+fn apply_user_data(settings: &mut Settings, player_store: &PlayerStore, import: UserDataImport, mode: ImportMode) -> Result<(), ToolkitError> {
+    // The store is written first so that a failed import leaves the settings untouched
+    player_store.import(&import.players, &import.sent_replays, mode == ImportMode::Replace)?;
+
+    let imported = import.settings;
+    match mode {
+        ImportMode::Merge => {
+            for (replay, annotation) in imported.replay_annotations {
+                merge_annotation(settings.replay_annotations.entry(replay).or_default(), annotation);
+            }
+            for filter in imported.saved_filters {
+                if !settings.saved_filters.iter().any(|saved| saved.name == filter.name) {
+                    settings.saved_filters.push(filter);
+                }
+            }
+            for bookmark in imported.bookmarks {
+                if !settings.bookmarks.contains(&bookmark) {
+                    settings.bookmarks.push(bookmark);
+                }
+            }
+            if settings.twitch_monitored_channel.is_empty() {
+                settings.twitch_monitored_channel = imported.twitch_monitored_channel;
+            }
+        }
+        ImportMode::Replace => {
+            settings.replay_annotations = imported.replay_annotations;
+            settings.saved_filters = imported.saved_filters;
+            settings.bookmarks = imported.bookmarks;
+            settings.twitch_monitored_channel = imported.twitch_monitored_channel;
+            settings.replay_settings = imported.replay_settings;
+            settings.check_for_updates = imported.check_for_updates;
+            settings.send_replay_data = imported.send_replay_data;
+            settings.replay_archive_age_days = imported.replay_archive_age_days;
+            settings.game_params_cache_size_mb = imported.game_params_cache_size_mb;
+        }
+    }
+
+    Ok(())
+}
+
+impl ToolkitTabViewer<'_> {
+    fn import_user_data(&mut self, path: &Path, mode: ImportMode) -> Result<(), ToolkitError> {
+        let imported = read_user_data(path)?;
+        let previous_channel = self.tab_state.settings.twitch_monitored_channel.clone();
+        let player_store = self.tab_state.player_store.as_ref().ok_or(ToolkitError::PlayerStoreUnavailable)?;
+        apply_user_data(&mut self.tab_state.settings, player_store, imported, mode)?;
+
+        let settings = &self.tab_state.settings;
+        self.tab_state
+            .should_send_replays
+            .store(settings.send_replay_data, std::sync::atomic::Ordering::Relaxed);
+        if settings.twitch_monitored_channel != previous_channel {
+            if let Some(tx) = self.tab_state.twitch_update_sender.as_ref() {
+                let _ = tx.blocking_send(crate::twitch::TwitchUpdate::User(settings.twitch_monitored_channel.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds the export and import controls for the settings tab
+    pub(crate) fn build_user_data_settings(&mut self, ui: &mut egui::Ui) {
+        let mut message = None;
+        ui.horizontal(|ui| {
+            if ui
+                .button(format!("{} Export User Data...", icons::EXPORT))
+                .on_hover_text("Saves settings, tracked players and their notes, replay annotations and saved filters. Your Twitch token is not included.")
+                .clicked()
+            {
+                let file_name = format!("wows_toolkit_data_{}.json", Local::now().format("%Y%m%d"));
+                if let Some(path) = rfd::FileDialog::new().set_file_name(file_name).add_filter("JSON", &["json"]).save_file() {
+                    let result = self
+                        .tab_state
+                        .player_store
+                        .as_ref()
+                        .ok_or(ToolkitError::PlayerStoreUnavailable)
+                        .and_then(|player_store| export_user_data(&path, &self.tab_state.settings, player_store));
+                    message = Some(match result {
+                        Ok(()) => format!("{} Exported user data to {}", icons::CHECK_CIRCLE, path.display()),
+                        Err(e) => format!("{} Could not export user data: {}", icons::WARNING, e),
+                    });
+                }
+            }
+
+            ui.menu_button(format!("{} Import User Data", icons::DOWNLOAD_SIMPLE), |ui| {
+                for (mode, label, hover_text) in [
+                    (ImportMode::Merge, "Merge...", "Adds the imported players, notes, annotations and filters to your own"),
+                    (
+                        ImportMode::Replace,
+                        "Replace...",
+                        "Replaces your tracked players, notes, annotations, filters and settings with the imported ones",
+                    ),
+                ] {
+                    if ui.button(label).on_hover_text(hover_text).clicked() {
+                        ui.close_menu();
+                        if let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() {
+                            message = Some(match self.import_user_data(&path, mode) {
+                                Ok(()) => format!("{} Imported user data from {}", icons::CHECK_CIRCLE, path.display()),
+                                Err(e) => format!("{} Could not import user data: {}", icons::WARNING, e),
+                            });
+                        }
+                    }
+                }
+            });
+        });
+
+        if let Some(message) = message {
+            *self.tab_state.timed_message.write() = Some(TimedMessage::new(message));
+        }
+    }
+}
@@ -13,6 +13,8 @@ use wowsunpack::{
 
 use crate::{
     build_tracker,
+    error::ToolkitError,
+    replay_archive::{self, ReplayArchive},
     replay_parser::Replay,
     task::{BackgroundTask, BackgroundTaskCompletion, BackgroundTaskKind},
 };
@@ -37,6 +39,15 @@ pub struct WorldOfWarshipsData {
     pub game_version: usize,
 
     pub replays_dir: PathBuf,
+
+    pub replay_archive: ReplayArchive,
+}
+
+fn invalid_replay(path: &Path, e: impl std::fmt::Debug) -> ToolkitError {
+    ToolkitError::InvalidReplay {
+        path: path.to_owned(),
+        reason: format!("{:?}", e),
+    }
 }
 
 impl WorldOfWarshipsData {
@@ -52,8 +63,11 @@ impl WorldOfWarshipsData {
             return None;
         }
 
-        let replay_file: ReplayFile = ReplayFile::from_decrypted_parts(meta_data.unwrap(), replay_data.unwrap()).unwrap();
         let game_metadata = self.game_metadata.clone()?;
+        let replay_file: ReplayFile = match ReplayFile::from_decrypted_parts(meta_data.unwrap(), replay_data.unwrap()) {
+            Ok(replay_file) => replay_file,
+            Err(e) => return Some(BackgroundTask::failed(BackgroundTaskKind::LoadingReplay, invalid_replay(&replay, e))),
+        };
         let replay = Replay::new(replay_file, game_metadata);
 
         self.load_replay(Arc::new(RwLock::new(replay)))
@@ -62,8 +76,11 @@ impl WorldOfWarshipsData {
     pub fn parse_replay<P: AsRef<Path>>(&self, replay_path: P) -> Option<BackgroundTask> {
         let path = replay_path.as_ref();
 
-        let replay_file: ReplayFile = ReplayFile::from_file(path).unwrap();
         let game_metadata = self.game_metadata.clone()?;
+        let replay_file: ReplayFile = match ReplayFile::from_file(path) {
+            Ok(replay_file) => replay_file,
+            Err(e) => return Some(BackgroundTask::failed(BackgroundTaskKind::LoadingReplay, invalid_replay(path, e))),
+        };
         let replay = Replay::new(replay_file, game_metadata);
 
         self.load_replay(Arc::new(RwLock::new(replay)))
@@ -75,9 +92,24 @@ impl WorldOfWarshipsData {
 
         let (tx, rx) = mpsc::channel();
 
-        let metadata_provider = self.game_metadata.as_ref().unwrap().clone();
+        let metadata_provider = self.game_metadata.clone()?;
+        let archived_replay = {
+            let replay = replay.read();
+            replay
+                .archive_entry
+                .as_ref()
+                .filter(|_| replay.needs_unarchiving())
+                .map(|entry| (self.replay_archive.archive_path(entry), entry.clone()))
+        };
         let _join_handle = std::thread::spawn(move || {
-            let res = { replay.read().parse(game_version.to_string().as_str()) };
+            let res = if let Some((archive_path, entry)) = archived_replay {
+                replay_archive::read_archived_replay(&archive_path, &entry).map(|replay_file| {
+                    replay.write().replay_file = replay_file;
+                })
+            } else {
+                Ok(())
+            };
+            let res = res.and_then(|_| replay.read().parse(game_version.to_string().as_str()));
             let res = res.map(move |report| {
                 // // Send the replay builds to the remote server
                 // for player in report.player_entities() {
@@ -104,9 +136,6 @@ impl WorldOfWarshipsData {
             let _ = tx.send(res);
         });
 
-        Some(BackgroundTask {
-            receiver: rx,
-            kind: BackgroundTaskKind::LoadingReplay,
-        })
+        Some(BackgroundTask::new(rx, BackgroundTaskKind::LoadingReplay))
     }
 }
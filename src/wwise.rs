@@ -0,0 +1,469 @@
+//! Parsing of Wwise sound bank (`.bnk`) and file package (`.pck`) containers.
+//!
+//! Both formats embed `.wem` streams, which are RIFF/WAVE files using a Wwise
+//! specific codec. PCM and Wwise IMA ADPCM streams are converted to standard
+//! WAV files. Vorbis and Opus streams need Wwise's codebooks to decode, so they
+//! can only be extracted as `.wem`.
+
+use std::ops::Range;
+
+use crate::error::ToolkitError;
+
+const BANK_HEADER: &[u8; 4] = b"BKHD";
+const PACKAGE_MAGIC: &[u8; 4] = b"AKPK";
+
+/// Size of a DIDX (data index) entry in a sound bank: id, offset and size
+const DIDX_ENTRY_SIZE: usize = 12;
+
+const FORMAT_PCM: u16 = 0x0001;
+const FORMAT_IMA_ADPCM: u16 = 0x0002;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+const FORMAT_VORBIS: u16 = 0xFFFF;
+const FORMAT_OPUS: u16 = 0x3040;
+const FORMAT_OPUS_WEM: u16 = 0x3041;
+
+/// Bytes of each channel's sub-block in a Wwise IMA ADPCM frame
+const IMA_CHANNEL_BLOCK_SIZE: usize = 0x24;
+
+const IMA_STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279,
+    307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428,
+    4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+const IMA_INDEX_TABLE: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundContainerKind {
+    Bank,
+    Package,
+}
+
+/// A `.wem` stream inside a container
+#[derive(Debug, Clone)]
+pub struct WemEntry {
+    pub id: u64,
+    /// The sound bank the stream is embedded in, for streams inside a bank in a package
+    pub bank_id: Option<u64>,
+    /// Location of the stream within the container's data
+    pub range: Range<usize>,
+    pub info: Option<WemInfo>,
+}
+
+impl WemEntry {
+    pub fn size(&self) -> usize {
+        self.range.len()
+    }
+
+    pub fn file_name(&self) -> String {
+        match self.bank_id {
+            Some(bank_id) => format!("{}_{}.wem", bank_id, self.id),
+            None => format!("{}.wem", self.id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WemInfo {
+    pub format: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub avg_bytes_per_sec: u32,
+    pub block_align: u16,
+    pub bits_per_sample: u16,
+    pub data_len: usize,
+}
+
+impl WemInfo {
+    pub fn codec_name(&self) -> String {
+        match self.format {
+            FORMAT_PCM | FORMAT_EXTENSIBLE => "PCM".to_owned(),
+            FORMAT_IMA_ADPCM => "IMA ADPCM".to_owned(),
+            FORMAT_VORBIS => "Vorbis".to_owned(),
+            FORMAT_OPUS | FORMAT_OPUS_WEM => "Opus".to_owned(),
+            other => format!("0x{:04X}", other),
+        }
+    }
+
+    pub fn can_convert_to_wav(&self) -> bool {
+        match self.format {
+            FORMAT_PCM | FORMAT_EXTENSIBLE => self.bits_per_sample == 16 || self.bits_per_sample == 8,
+            FORMAT_IMA_ADPCM => self.channels > 0,
+            _ => false,
+        }
+    }
+
+    /// Approximate duration in seconds, from the stream's average byte rate
+    pub fn duration_secs(&self) -> Option<f64> {
+        (self.avg_bytes_per_sec > 0).then(|| self.data_len as f64 / self.avg_bytes_per_sec as f64)
+    }
+}
+
+pub struct SoundContainer {
+    pub kind: SoundContainerKind,
+    pub data: Vec<u8>,
+    pub entries: Vec<WemEntry>,
+}
+
+fn invalid(reason: &'static str) -> ToolkitError {
+    ToolkitError::InvalidSoundBank(reason)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, ToolkitError> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| invalid("unexpected end of data"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, ToolkitError> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| invalid("unexpected end of data"))
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Result<u64, ToolkitError> {
+    data.get(offset..offset + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| invalid("unexpected end of data"))
+}
+
+fn checked_range(data: &[u8], start: usize, len: usize) -> Result<Range<usize>, ToolkitError> {
+    let end = start
+        .checked_add(len)
+        .filter(|end| *end <= data.len())
+        .ok_or_else(|| invalid("entry out of bounds"))?;
+
+    Ok(start..end)
+}
+
+/// Iterates over the `(tag, data range)` chunks of a sound bank starting at `base`
+fn bank_chunks(data: &[u8], base: usize, len: usize) -> Result<Vec<([u8; 4], Range<usize>)>, ToolkitError> {
+    let mut chunks = Vec::new();
+    let end = base + len;
+    let mut pos = base;
+    while pos + 8 <= end {
+        let tag: [u8; 4] = data[pos..pos + 4].try_into().unwrap();
+        let size = u32_at(data, pos + 4)? as usize;
+        let range = checked_range(data, pos + 8, size)?;
+        pos = range.end;
+        chunks.push((tag, range));
+    }
+
+    Ok(chunks)
+}
+
+/// Lists the streams embedded in the sound bank at `base..base + len`
+fn bank_entries(data: &[u8], base: usize, len: usize, bank_id: Option<u64>) -> Result<Vec<WemEntry>, ToolkitError> {
+    let chunks = bank_chunks(data, base, len)?;
+    let Some(index) = chunks.iter().find(|(tag, _)| tag == b"DIDX").map(|(_, range)| range.clone()) else {
+        // Banks which only hold events and no media don't have an index
+        return Ok(Vec::new());
+    };
+    let media = chunks
+        .iter()
+        .find(|(tag, _)| tag == b"DATA")
+        .map(|(_, range)| range.clone())
+        .ok_or_else(|| invalid("bank has an index but no data"))?;
+
+    let mut entries = Vec::with_capacity(index.len() / DIDX_ENTRY_SIZE);
+    for entry in index.step_by(DIDX_ENTRY_SIZE) {
+        let id = u32_at(data, entry)? as u64;
+        let offset = u32_at(data, entry + 4)? as usize;
+        let size = u32_at(data, entry + 8)? as usize;
+        let range = checked_range(data, media.start + offset, size)?;
+        if range.end > media.end {
+            return Err(invalid("stream extends past the bank's data"));
+        }
+
+        entries.push(WemEntry {
+            id,
+            bank_id,
+            info: parse_wem_info(&data[range.clone()]).ok(),
+            range,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// One entry of a package's bank or stream table
+struct PackageFile {
+    id: u64,
+    range: Range<usize>,
+}
+
+fn package_table(data: &[u8], table: Range<usize>) -> Result<Vec<PackageFile>, ToolkitError> {
+    if table.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let count = u32_at(data, table.start)? as usize;
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    // Newer packages use 64 bit ids in some tables, which makes each entry 4 bytes larger
+    let entry_size = table.len().checked_sub(4).ok_or_else(|| invalid("package table is truncated"))? / count;
+    let wide_ids = match entry_size {
+        20 => false,
+        24 => true,
+        _ => return Err(invalid("unexpected package table entry size")),
+    };
+
+    let mut files = Vec::with_capacity(count);
+    for i in 0..count {
+        let entry = table.start + 4 + i * entry_size;
+        let (id, rest) = if wide_ids {
+            (u64_at(data, entry)?, entry + 8)
+        } else {
+            (u32_at(data, entry)? as u64, entry + 4)
+        };
+        let block_size = u32_at(data, rest)?.max(1) as usize;
+        let size = u32_at(data, rest + 4)? as usize;
+        let start_block = u32_at(data, rest + 8)? as usize;
+        let start = start_block.checked_mul(block_size).ok_or_else(|| invalid("package file offset is out of range"))?;
+
+        files.push(PackageFile {
+            id,
+            range: checked_range(data, start, size)?,
+        });
+    }
+
+    Ok(files)
+}
+
+fn package_entries(data: &[u8]) -> Result<Vec<WemEntry>, ToolkitError> {
+    let header_size = u32_at(data, 4)? as usize;
+    let language_map_size = u32_at(data, 12)? as usize;
+    let banks_size = u32_at(data, 16)? as usize;
+    let streams_size = u32_at(data, 20)? as usize;
+    // Later versions add a table of externals, which we don't need
+    let size_fields = if header_size >= 20 + language_map_size + banks_size + streams_size { 4 } else { 3 };
+
+    let tables_start = 12 + size_fields * 4;
+    let banks = tables_start + language_map_size..tables_start + language_map_size + banks_size;
+    let streams = banks.end..banks.end + streams_size;
+
+    let mut entries = Vec::new();
+    for bank in package_table(data, banks)? {
+        entries.extend(bank_entries(data, bank.range.start, bank.range.len(), Some(bank.id))?);
+    }
+    for stream in package_table(data, streams)? {
+        entries.push(WemEntry {
+            id: stream.id,
+            bank_id: None,
+            info: parse_wem_info(&data[stream.range.clone()]).ok(),
+            range: stream.range,
+        });
+    }
+
+    Ok(entries)
+}
+
+impl SoundContainer {
+    pub fn parse(data: Vec<u8>) -> Result<Self, ToolkitError> {
+        let (kind, entries) = match data.get(..4) {
+            Some(magic) if magic == BANK_HEADER => (SoundContainerKind::Bank, bank_entries(&data, 0, data.len(), None)?),
+            Some(magic) if magic == PACKAGE_MAGIC => (SoundContainerKind::Package, package_entries(&data)?),
+            _ => return Err(invalid("not a sound bank or package")),
+        };
+
+        Ok(SoundContainer { kind, data, entries })
+    }
+
+    pub fn wem_data(&self, entry: &WemEntry) -> &[u8] {
+        &self.data[entry.range.clone()]
+    }
+}
+
+/// Returns the `fmt ` and `data` chunk ranges of a RIFF/WAVE file
+fn riff_chunks(wem: &[u8]) -> Result<(Range<usize>, Range<usize>), ToolkitError> {
+    if wem.get(..4) != Some(b"RIFF".as_slice()) || wem.get(8..12) != Some(b"WAVE".as_slice()) {
+        return Err(invalid("stream is not a RIFF/WAVE file"));
+    }
+
+    let mut fmt = None;
+    let mut media = None;
+    let mut pos = 12;
+    while pos + 8 <= wem.len() {
+        let size = u32_at(wem, pos + 4)? as usize;
+        let start = pos + 8;
+        // The last chunk is sometimes truncated
+        let end = start.saturating_add(size).min(wem.len());
+        match &wem[pos..pos + 4] {
+            b"fmt " => fmt = Some(start..end),
+            b"data" => media = Some(start..end),
+            _ => {}
+        }
+        // Chunks are padded to an even size
+        pos = end + (size & 1);
+    }
+
+    match (fmt, media) {
+        (Some(fmt), Some(media)) => Ok((fmt, media)),
+        _ => Err(invalid("stream is missing its format or data")),
+    }
+}
+
+pub fn parse_wem_info(wem: &[u8]) -> Result<WemInfo, ToolkitError> {
+    let (fmt, media) = riff_chunks(wem)?;
+
+    Ok(WemInfo {
+        format: u16_at(wem, fmt.start)?,
+        channels: u16_at(wem, fmt.start + 2)?,
+        sample_rate: u32_at(wem, fmt.start + 4)?,
+        avg_bytes_per_sec: u32_at(wem, fmt.start + 8)?,
+        block_align: u16_at(wem, fmt.start + 12)?,
+        bits_per_sample: u16_at(wem, fmt.start + 14)?,
+        data_len: media.len(),
+    })
+}
+
+fn write_wav(channels: u16, sample_rate: u32, bits_per_sample: u16, samples: &[u8]) -> Result<Vec<u8>, ToolkitError> {
+    let block_align = channels.checked_mul(bits_per_sample).ok_or_else(|| invalid("stream block size is out of range"))? / 8;
+    let byte_rate = sample_rate
+        .checked_mul(block_align as u32)
+        .ok_or_else(|| invalid("stream byte rate is out of range"))?;
+    let data_len = u32::try_from(samples.len())
+        .ok()
+        .filter(|len| len.checked_add(36).is_some())
+        .ok_or_else(|| invalid("stream is too large for a WAV file"))?;
+
+    let mut wav = Vec::with_capacity(44 + samples.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.extend_from_slice(samples);
+
+    Ok(wav)
+}
+
+/// Decodes one channel's sub-block of a Wwise IMA ADPCM frame
+fn decode_ima_block(block: &[u8], out: &mut Vec<i16>) {
+    let mut predictor = i16::from_le_bytes([block[0], block[1]]) as i32;
+    let mut index = (block[2] as i32).clamp(0, 88);
+    out.push(predictor as i16);
+
+    for byte in &block[4..] {
+        for nibble in [byte & 0x0F, byte >> 4] {
+            let step = IMA_STEP_TABLE[index as usize];
+            let mut diff = step >> 3;
+            if nibble & 1 != 0 {
+                diff += step >> 2;
+            }
+            if nibble & 2 != 0 {
+                diff += step >> 1;
+            }
+            if nibble & 4 != 0 {
+                diff += step;
+            }
+            if nibble & 8 != 0 {
+                diff = -diff;
+            }
+
+            predictor = (predictor + diff).clamp(i16::MIN as i32, i16::MAX as i32);
+            index = (index + IMA_INDEX_TABLE[(nibble & 7) as usize]).clamp(0, 88);
+            out.push(predictor as i16);
+        }
+    }
+}
+
+fn decode_ima_adpcm(info: &WemInfo, data: &[u8]) -> Result<Vec<u8>, ToolkitError> {
+    let channels = info.channels as usize;
+    if channels == 0 {
+        return Err(invalid("stream has no channels"));
+    }
+    let frame_size = if info.block_align > 0 {
+        info.block_align as usize
+    } else {
+        IMA_CHANNEL_BLOCK_SIZE * channels
+    };
+    // Every channel needs a block of the same size in each frame
+    let channel_block_size = frame_size / channels;
+    if channel_block_size <= 4 || frame_size % channels != 0 {
+        return Err(invalid("unexpected ADPCM block size"));
+    }
+
+    let mut samples = Vec::with_capacity(data.len() * 4);
+    let mut decoded: Vec<Vec<i16>> = vec![Vec::new(); channels];
+    for frame in data.chunks_exact(frame_size) {
+        // Wwise stores each channel's block one after another rather than interleaving them
+        for (channel, block) in frame.chunks_exact(channel_block_size).enumerate() {
+            decoded[channel].clear();
+            decode_ima_block(block, &mut decoded[channel]);
+        }
+        for i in 0..decoded[0].len() {
+            for channel in &decoded {
+                samples.extend_from_slice(&channel[i].to_le_bytes());
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Converts a `.wem` stream to a standard PCM WAV file
+pub fn wem_to_wav(wem: &[u8]) -> Result<Vec<u8>, ToolkitError> {
+    let info = parse_wem_info(wem)?;
+    if !info.can_convert_to_wav() {
+        return Err(ToolkitError::UnsupportedSoundCodec(info.codec_name()));
+    }
+
+    let (_fmt, media) = riff_chunks(wem)?;
+    let data = &wem[media];
+
+    match info.format {
+        FORMAT_IMA_ADPCM => write_wav(info.channels, info.sample_rate, 16, &decode_ima_adpcm(&info, data)?),
+        _ => write_wav(info.channels, info.sample_rate, info.bits_per_sample, data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adpcm_info(channels: u16, block_align: u16) -> WemInfo {
+        WemInfo {
+            format: FORMAT_IMA_ADPCM,
+            channels,
+            sample_rate: 48000,
+            avg_bytes_per_sec: 0,
+            block_align,
+            bits_per_sample: 4,
+            data_len: 0,
+        }
+    }
+
+    #[test]
+    fn decodes_adpcm_frames() {
+        let info = adpcm_info(2, 72);
+        let samples = decode_ima_adpcm(&info, &[0; 144]).expect("failed to decode ADPCM");
+
+        // Each 36 byte channel block holds a header sample and 64 nibbles, for 2 frames of 2 channels
+        assert_eq!(samples.len(), 65 * 2 * 2 * 2);
+    }
+
+    #[test]
+    fn uneven_channel_blocks_are_rejected() {
+        // 35 bytes split 6 ways leaves 5 byte blocks plus a remainder, which used to decode as a 7th channel
+        let info = adpcm_info(6, 35);
+
+        assert!(matches!(decode_ima_adpcm(&info, &[0; 70]), Err(ToolkitError::InvalidSoundBank(_))));
+    }
+
+    #[test]
+    fn streams_without_channels_are_rejected() {
+        let info = adpcm_info(0, 36);
+
+        assert!(matches!(decode_ima_adpcm(&info, &[0; 36]), Err(ToolkitError::InvalidSoundBank(_))));
+    }
+}